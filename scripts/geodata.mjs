@@ -0,0 +1,73 @@
+// scripts/geodata.mjs
+// 下载经纬网叠加层使用的 Natural Earth 1:110m 海岸线和国界数据
+//
+// 固定在发布标签上，并按 src-tauri/resources/geo/SHA256SUMS 校验。
+// SHA256SUMS 里还没有某个文件时记录下它的校验和，提交后之后的下载都会校验。
+
+import crypto from 'crypto';
+import fs from 'fs';
+import path from 'path';
+
+const TAG = 'v5.1.2';
+const BASE = `https://raw.githubusercontent.com/nvkelso/natural-earth-vector/${TAG}/geojson`;
+const FILES = ['ne_110m_coastline.geojson', 'ne_110m_admin_0_boundary_lines_land.geojson'];
+const DIR = path.join(process.cwd(), 'src-tauri', 'resources', 'geo');
+const SUMS = path.join(DIR, 'SHA256SUMS');
+
+function readSums() {
+  if (!fs.existsSync(SUMS)) {
+    return new Map();
+  }
+  const sums = new Map();
+  for (const line of fs.readFileSync(SUMS, 'utf8').split('\n')) {
+    const [hash, file] = line.trim().split(/\s+/);
+    if (hash && file) {
+      sums.set(file, hash);
+    }
+  }
+  return sums;
+}
+
+function writeSums(sums) {
+  const lines = [...sums.entries()].sort(([a], [b]) => a.localeCompare(b)).map(([file, hash]) => `${hash}  ${file}`);
+  fs.writeFileSync(SUMS, lines.join('\n') + '\n');
+}
+
+async function download(file, sums) {
+  const res = await fetch(`${BASE}/${file}`);
+  if (!res.ok) {
+    throw new Error(`${file}: HTTP ${res.status}`);
+  }
+  const body = Buffer.from(await res.arrayBuffer());
+  const hash = crypto.createHash('sha256').update(body).digest('hex');
+  const expected = sums.get(file);
+  if (expected && expected !== hash) {
+    throw new Error(`${file}: SHA-256 mismatch, expected ${expected}, got ${hash}`);
+  }
+  // 校验是合法的 GeoJSON 再写入，保存原始字节以便再次校验
+  const json = JSON.parse(body.toString('utf8'));
+  if (!Array.isArray(json.features)) {
+    throw new Error(`${file}: not a FeatureCollection`);
+  }
+  fs.writeFileSync(path.join(DIR, file), body);
+  if (!expected) {
+    sums.set(file, hash);
+    console.warn(`${file}: recorded SHA-256 ${hash}, commit src-tauri/resources/geo/SHA256SUMS`);
+  }
+  console.log(`${file}: ${json.features.length} features`);
+}
+
+fs.mkdirSync(DIR, { recursive: true });
+const sums = readSums();
+const known = sums.size;
+for (const file of FILES) {
+  try {
+    await download(file, sums);
+  } catch (e) {
+    console.error(e.message);
+    process.exit(1);
+  }
+}
+if (sums.size !== known) {
+  writeSums(sums);
+}
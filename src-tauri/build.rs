@@ -1,3 +1,17 @@
+use std::path::Path;
+
+/// 经纬网叠加层需要的 Natural Earth 数据，由 `pnpm geodata` 下载到 resources/geo
+const GEO_FILES: [&str; 2] = ["ne_110m_coastline.geojson", "ne_110m_admin_0_boundary_lines_land.geojson"];
+
 fn main() {
+    println!("cargo:rerun-if-changed=resources/geo");
+    let missing: Vec<&str> = GEO_FILES
+        .into_iter()
+        .filter(|file| !Path::new("resources/geo").join(file).is_file())
+        .collect();
+    // 数据随应用打包，缺少时 tauri_build 也会因资源找不到而失败，这里先给出明确的提示
+    if !missing.is_empty() {
+        panic!("resources/geo 缺少 {}，请在项目根目录运行 pnpm geodata", missing.join("、"));
+    }
     tauri_build::build()
 }
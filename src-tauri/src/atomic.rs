@@ -0,0 +1,177 @@
+use image::{ImageFormat, ImageResult};
+use std::fs::{self, OpenOptions};
+use std::path::{Path, PathBuf};
+use std::process;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, SystemTime};
+use tracing::warn;
+
+use crate::error::{EarthError, EarthResult};
+
+/// 临时文件的后缀，清理残留时按它识别
+const TEMP_SUFFIX: &str = ".tmp";
+
+/// 超过这个时间的临时文件视为崩溃残留
+const STALE_AFTER: Duration = Duration::from_secs(60 * 60);
+
+static NEXT_TEMP: AtomicU64 = AtomicU64::new(0);
+
+/// 目标文件同目录下的临时文件名，形如 .earth_20250620_1200.png.1234.0.tmp
+///
+/// 放在同一目录保证 rename 不跨文件系统；加上进程号和序号避免并发写入时撞名。
+fn temp_path(path: &Path) -> PathBuf {
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+    let seq = NEXT_TEMP.fetch_add(1, Ordering::SeqCst);
+    path.with_file_name(format!(".{}.{}.{}{}", name, process::id(), seq, TEMP_SUFFIX))
+}
+
+/// 原子写入：write 先写到同目录的临时文件，fsync 后重命名到目标路径
+///
+/// 桌面在写入过程中读取壁纸时只会看到旧文件或完整的新文件；失败时删除临时文件。
+pub fn write_with<F>(path: &Path, write: F) -> EarthResult<()>
+where
+    F: FnOnce(&Path) -> EarthResult<()>,
+{
+    let tmp = temp_path(path);
+    let result = write(&tmp).and_then(|_| {
+        // Windows 上 FlushFileBuffers 需要写权限，只读打开的句柄无法 fsync
+        OpenOptions::new()
+            .write(true)
+            .open(&tmp)
+            .and_then(|file| file.sync_all())
+            .map_err(|e| EarthError::io(&tmp, "同步文件失败", e))?;
+        fs::rename(&tmp, path).map_err(|e| EarthError::io(path, "替换文件失败", e))
+    });
+    match result {
+        Ok(()) => {
+            sync_dir(path);
+            Ok(())
+        }
+        Err(e) => {
+            let _ = fs::remove_file(&tmp);
+            Err(e)
+        }
+    }
+}
+
+/// 原子写入一段字节
+pub fn write_bytes(path: &Path, data: impl AsRef<[u8]>, context: &str) -> EarthResult<()> {
+    write_with(path, |tmp| fs::write(tmp, data).map_err(|e| EarthError::io(path, context, e)))
+}
+
+/// 原子保存图片，格式按目标路径的扩展名确定
+///
+/// `save` 一般是 `|tmp, format| img.save_with_format(tmp, format)`，
+/// 临时文件的扩展名不是图片格式，不能用 `save` 自动推断。
+pub fn save_image<F>(path: &Path, context: &str, save: F) -> EarthResult<()>
+where
+    F: FnOnce(&Path, ImageFormat) -> ImageResult<()>,
+{
+    let format = ImageFormat::from_path(path).map_err(|e| EarthError::image(path, context, e))?;
+    write_with(path, |tmp| save(tmp, format).map_err(|e| EarthError::image(path, context, e)))
+}
+
+/// rename 之后同步目录，确保断电后目录项也已落盘；Windows 上无法打开目录，跳过
+fn sync_dir(path: &Path) {
+    #[cfg(unix)]
+    if let Some(dir) = path.parent()
+        && let Err(e) = fs::File::open(dir).and_then(|dir| dir.sync_all())
+    {
+        tracing::debug!(dir = %dir.display(), error = %e, "同步目录失败");
+    }
+    #[cfg(not(unix))]
+    let _ = path;
+}
+
+/// 删除图片目录及其子目录（monitor_*/live 等）中崩溃残留的临时文件，返回删除的个数
+pub fn remove_stale_temp_files(dir: &Path) -> usize {
+    remove_stale_in(dir, SystemTime::now(), 2)
+}
+
+fn remove_stale_in(dir: &Path, now: SystemTime, depth: u32) -> usize {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return 0;
+    };
+    let mut removed = 0;
+    for path in entries.flatten().map(|entry| entry.path()) {
+        if path.is_dir() {
+            if depth > 0 {
+                removed += remove_stale_in(&path, now, depth - 1);
+            }
+            continue;
+        }
+        if !is_stale_temp(&path, now) {
+            continue;
+        }
+        match fs::remove_file(&path) {
+            Ok(()) => removed += 1,
+            Err(e) => warn!(path = %path.display(), error = %e, "删除残留临时文件失败"),
+        }
+    }
+    removed
+}
+
+fn is_stale_temp(path: &Path, now: SystemTime) -> bool {
+    let is_temp = path
+        .file_name()
+        .and_then(|name| name.to_str())
+        .is_some_and(|name| name.starts_with('.') && name.ends_with(TEMP_SUFFIX));
+    is_temp
+        && fs::metadata(path)
+            .and_then(|meta| meta.modified())
+            .is_ok_and(|modified| now.duration_since(modified).unwrap_or_default() >= STALE_AFTER)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn write_with_replaces_target() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("earth.txt");
+        fs::write(&path, "old").unwrap();
+        write_with(&path, |tmp| fs::write(tmp, "new").map_err(|e| EarthError::io(tmp, "写入失败", e))).unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "new");
+        // 临时文件已重命名，目录里只剩目标文件
+        assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 1);
+    }
+
+    #[test]
+    fn failed_write_keeps_old_file_and_removes_temp() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("earth.txt");
+        fs::write(&path, "old").unwrap();
+        let result = write_with(&path, |tmp| {
+            fs::write(tmp, "partial").unwrap();
+            Err(EarthError::invalid("写入失败"))
+        });
+        assert!(result.is_err());
+        assert_eq!(fs::read_to_string(&path).unwrap(), "old");
+        assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 1);
+    }
+
+    #[test]
+    fn save_image_uses_target_extension() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("earth.png");
+        let img = image::RgbaImage::from_pixel(2, 3, image::Rgba([1, 2, 3, 255]));
+        save_image(&path, "保存失败", |tmp, format| img.save_with_format(tmp, format)).unwrap();
+        assert_eq!(image::open(&path).unwrap().to_rgba8(), img);
+    }
+
+    #[test]
+    fn only_old_hidden_temp_files_are_stale() {
+        let dir = tempfile::tempdir().unwrap();
+        let temp = dir.path().join(".earth.png.1.0.tmp");
+        let plain = dir.path().join("earth.tmp");
+        fs::write(&temp, "").unwrap();
+        fs::write(&plain, "").unwrap();
+        let later = SystemTime::now() + STALE_AFTER;
+        assert!(is_stale_temp(&temp, later));
+        assert!(!is_stale_temp(&temp, SystemTime::now()));
+        assert!(!is_stale_temp(&plain, later));
+        assert_eq!(remove_stale_in(dir.path(), later, 0), 1);
+        assert!(plain.exists());
+    }
+}
@@ -0,0 +1,351 @@
+use reqwest::blocking::Client;
+use serde::{Deserialize, Serialize};
+use std::io::Read;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::error::{EarthError, EarthResult};
+
+/// 向日葵卫星每 10 分钟发布一次新图
+pub const PUBLISH_INTERVAL: Duration = Duration::from_secs(10 * 60);
+
+const USER_AGENT: &str = "Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/91.0.4472.124 Safari/537.36";
+
+/// 每次从响应中读取的块大小
+const CHUNK_SIZE: usize = 8 * 1024;
+
+/// 建立连接的超时时间
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// 单次读写的超时时间，避免睡眠唤醒后连接假死导致下载永远挂起
+const IO_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// 可取消的等待每次最多睡这么久，然后检查一次取消标记
+const CANCEL_POLL: Duration = Duration::from_millis(200);
+
+/// 下载配置，前端可在运行时修改
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct DownloadConfig {
+    /// 每秒最多下载的字节数，None 表示不限速
+    pub rate_limit_bps: Option<u64>,
+    /// 涓流下载：把所有瓦片均匀分摊到一个发布周期内下载
+    pub trickle: bool,
+}
+
+/// 取消标记，同一批次的下载共享一个标记
+#[derive(Clone, Default)]
+pub struct CancelToken {
+    flag: Arc<AtomicBool>,
+    parent: Option<Arc<CancelToken>>,
+}
+
+impl CancelToken {
+    /// 派生一个子标记：父标记取消时子标记也视为已取消，取消子标记不影响父标记
+    pub fn child(&self) -> CancelToken {
+        CancelToken {
+            flag: Arc::default(),
+            parent: Some(Arc::new(self.clone())),
+        }
+    }
+
+    pub fn cancel(&self) {
+        self.flag.store(true, Ordering::SeqCst);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.flag.load(Ordering::SeqCst) || self.parent.as_ref().is_some_and(|p| p.is_cancelled())
+    }
+
+    /// 已取消时返回错误，便于在循环中用 `?` 提前退出
+    pub fn check(&self) -> EarthResult<()> {
+        if self.is_cancelled() {
+            Err(EarthError::cancelled())
+        } else {
+            Ok(())
+        }
+    }
+
+    /// 可被取消的 sleep，返回 false 表示等待期间被取消
+    pub fn sleep(&self, duration: Duration) -> bool {
+        let deadline = Instant::now() + duration;
+        loop {
+            if self.is_cancelled() {
+                return false;
+            }
+            let now = Instant::now();
+            if now >= deadline {
+                return true;
+            }
+            thread::sleep((deadline - now).min(CANCEL_POLL));
+        }
+    }
+}
+
+/// 令牌桶：按固定速率补充令牌，允许最多一秒的突发流量
+pub struct TokenBucket {
+    rate: f64,
+    capacity: f64,
+    tokens: f64,
+    last: Instant,
+}
+
+impl TokenBucket {
+    pub fn new(rate_bps: u64) -> Self {
+        let rate = rate_bps.max(1) as f64;
+        TokenBucket {
+            rate,
+            capacity: rate,
+            tokens: rate,
+            last: Instant::now(),
+        }
+    }
+
+    fn refill(&mut self) {
+        let now = Instant::now();
+        let elapsed = now.duration_since(self.last).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * self.rate).min(self.capacity);
+        self.last = now;
+    }
+
+    /// 取走 n 个令牌，返回调用方需要等待的时间；令牌不足时记为欠账，由等待时间偿还
+    pub fn take(&mut self, n: u64) -> Duration {
+        self.refill();
+        self.tokens -= n as f64;
+        if self.tokens >= 0.0 {
+            Duration::ZERO
+        } else {
+            Duration::from_secs_f64(-self.tokens / self.rate)
+        }
+    }
+}
+
+/// 可在多个下载之间共享的限速器，限速值修改后对正在进行的下载立即生效
+#[derive(Clone, Default)]
+pub struct Throttle {
+    bucket: Arc<Mutex<Option<TokenBucket>>>,
+}
+
+impl Throttle {
+    pub fn set_rate(&self, rate_limit_bps: Option<u64>) {
+        let mut bucket = self.bucket.lock().unwrap();
+        *bucket = rate_limit_bps.filter(|&r| r > 0).map(TokenBucket::new);
+    }
+
+    /// 记录已下载的字节数，超出限速时阻塞当前线程，直到额度恢复或被取消
+    pub fn consume(&self, n: usize, cancel: &CancelToken) {
+        let wait = match self.bucket.lock().unwrap().as_mut() {
+            Some(bucket) => bucket.take(n as u64),
+            None => Duration::ZERO,
+        };
+        if !wait.is_zero() {
+            cancel.sleep(wait);
+        }
+    }
+}
+
+/// 托管在 Tauri 中的下载状态
+#[derive(Default)]
+pub struct DownloadState {
+    pub config: Mutex<DownloadConfig>,
+    pub throttle: Throttle,
+}
+
+impl DownloadState {
+    pub fn update(&self, config: DownloadConfig) {
+        self.throttle.set_rate(config.rate_limit_bps);
+        *self.config.lock().unwrap() = config;
+    }
+}
+
+/// 创建带超时的瓦片下载客户端
+pub fn build_client() -> EarthResult<Client> {
+    Client::builder()
+        .connect_timeout(CONNECT_TIMEOUT)
+        .timeout(IO_TIMEOUT)
+        .build()
+        .map_err(|e| EarthError::internal("创建 HTTP 客户端失败", e))
+}
+
+/// 下载单个瓦片，按限速器分块读取响应体
+pub fn download_tile(
+    client: &Client,
+    url: &str,
+    throttle: &Throttle,
+    cancel: &CancelToken,
+) -> EarthResult<Vec<u8>> {
+    cancel.check()?;
+    let mut response = client
+        .get(url)
+        .header("User-Agent", USER_AGENT)
+        .send()
+        .map_err(|e| EarthError::network(url, e))?;
+
+    let mut data = Vec::new();
+    let mut buf = [0u8; CHUNK_SIZE];
+    loop {
+        let n = response
+            .read(&mut buf)
+            .map_err(|e| EarthError::network(url, e))?;
+        if n == 0 {
+            break;
+        }
+        throttle.consume(n, cancel);
+        cancel.check()?;
+        data.extend_from_slice(&buf[..n]);
+    }
+    Ok(data)
+}
+
+/// 涓流模式下每个瓦片占用的时间片，非涓流模式返回 None
+pub fn tile_slot(config: &DownloadConfig, tile_count: u32) -> Option<Duration> {
+    if config.trickle && tile_count > 0 {
+        Some(PUBLISH_INTERVAL / tile_count)
+    } else {
+        None
+    }
+}
+
+/// 瓦片下载完成后，等到该瓦片的时间片结束再开始下一个
+pub fn wait_for_slot(slot: Option<Duration>, started: Instant, cancel: &CancelToken) -> EarthResult<()> {
+    if let Some(slot) = slot {
+        let elapsed = started.elapsed();
+        if elapsed < slot {
+            cancel.sleep(slot - elapsed);
+        }
+    }
+    cancel.check()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{BufRead, BufReader, Write};
+    use std::net::TcpListener;
+
+    /// 在 127.0.0.1 上起一个只响应一次的 HTTP 服务，返回瓦片地址
+    fn serve_once(body: Vec<u8>) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            let mut reader = BufReader::new(stream);
+            let mut line = String::new();
+            while reader.read_line(&mut line).unwrap() > 2 {
+                line.clear();
+            }
+            let mut stream = reader.into_inner();
+            let header = format!(
+                "HTTP/1.1 200 OK\r\nContent-Type: image/png\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+                body.len()
+            );
+            stream.write_all(header.as_bytes()).unwrap();
+            // 客户端取消后会断开连接，写失败不算错误
+            let _ = stream.write_all(&body);
+        });
+        format!("http://{}/tile_0_0.png", addr)
+    }
+
+    fn body(len: usize) -> Vec<u8> {
+        (0..len).map(|i| (i % 251) as u8).collect()
+    }
+
+    #[test]
+    fn downloads_tile_from_server() {
+        let data = body(100 * 1024);
+        let url = serve_once(data.clone());
+        let client = build_client().unwrap();
+        let tile = download_tile(&client, &url, &Throttle::default(), &CancelToken::default()).unwrap();
+        assert_eq!(tile, data);
+    }
+
+    #[test]
+    fn rate_limit_slows_download() {
+        // 令牌桶一开始有一秒的额度，剩下的 64 KB 要再等一秒
+        let url = serve_once(body(128 * 1024));
+        let throttle = Throttle::default();
+        throttle.set_rate(Some(64 * 1024));
+        let client = build_client().unwrap();
+        let started = Instant::now();
+        let tile = download_tile(&client, &url, &throttle, &CancelToken::default()).unwrap();
+        let elapsed = started.elapsed();
+        assert_eq!(tile.len(), 128 * 1024);
+        assert!(elapsed >= Duration::from_millis(900), "{:?}", elapsed);
+        assert!(elapsed < Duration::from_secs(5), "{:?}", elapsed);
+    }
+
+    #[test]
+    fn cancel_interrupts_consume() {
+        let throttle = Throttle::default();
+        throttle.set_rate(Some(1));
+        let cancel = CancelToken::default();
+        let canceller = cancel.clone();
+        thread::spawn(move || {
+            thread::sleep(Duration::from_millis(100));
+            canceller.cancel();
+        });
+        // 按 1 B/s 要等十几分钟，取消后应在一个轮询周期内返回
+        let started = Instant::now();
+        throttle.consume(1000, &cancel);
+        assert!(started.elapsed() < Duration::from_secs(2));
+        assert!(cancel.check().is_err());
+    }
+
+    #[test]
+    fn cancelled_download_stops() {
+        let url = serve_once(body(256 * 1024));
+        let throttle = Throttle::default();
+        throttle.set_rate(Some(16 * 1024));
+        let cancel = CancelToken::default();
+        let canceller = cancel.clone();
+        thread::spawn(move || {
+            thread::sleep(Duration::from_millis(200));
+            canceller.cancel();
+        });
+        let client = build_client().unwrap();
+        let started = Instant::now();
+        assert!(download_tile(&client, &url, &throttle, &cancel).is_err());
+        assert!(started.elapsed() < Duration::from_secs(3));
+    }
+
+    #[test]
+    fn token_bucket_allows_one_second_burst() {
+        let mut bucket = TokenBucket::new(1000);
+        assert_eq!(bucket.take(1000), Duration::ZERO);
+        let wait = bucket.take(500);
+        assert!(wait > Duration::from_millis(450) && wait <= Duration::from_millis(500), "{:?}", wait);
+    }
+
+    #[test]
+    fn trickle_slot_splits_publish_interval() {
+        let trickle = DownloadConfig {
+            rate_limit_bps: None,
+            trickle: true,
+        };
+        assert_eq!(tile_slot(&trickle, 16), Some(Duration::from_millis(37_500)));
+        assert_eq!(tile_slot(&trickle, 1), Some(PUBLISH_INTERVAL));
+        assert_eq!(tile_slot(&trickle, 0), None);
+        assert_eq!(tile_slot(&DownloadConfig::default(), 16), None);
+    }
+
+    #[test]
+    fn wait_for_slot_sleeps_out_the_remainder() {
+        let cancel = CancelToken::default();
+        let started = Instant::now();
+        wait_for_slot(Some(Duration::from_millis(300)), started, &cancel).unwrap();
+        assert!(started.elapsed() >= Duration::from_millis(300));
+        // 时间片已用完时不再等待
+        let before = Instant::now();
+        wait_for_slot(Some(Duration::from_millis(300)), started, &cancel).unwrap();
+        assert!(before.elapsed() < Duration::from_millis(100));
+        wait_for_slot(None, started, &cancel).unwrap();
+    }
+
+    #[test]
+    fn wait_for_slot_reports_cancel() {
+        let cancel = CancelToken::default();
+        cancel.cancel();
+        assert!(wait_for_slot(Some(Duration::from_secs(60)), Instant::now(), &cancel).is_err());
+    }
+}
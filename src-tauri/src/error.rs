@@ -0,0 +1,176 @@
+use serde::Serialize;
+use std::fmt;
+use std::path::Path;
+
+use crate::i18n;
+
+/// 所有命令统一返回的错误类型
+///
+/// 序列化为 `{ "code": "network", "url": "...", "message": "..." }`，
+/// 前端按 code 分类处理，message 按当前语言生成，只用于展示。code 一经发布不再修改。
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "code", rename_all = "snake_case")]
+pub enum EarthError {
+    /// 网络请求失败或超时
+    Network { url: String, message: String },
+    /// 瓦片内容异常：服务器返回占位图或无法解码
+    Tile { x: u32, y: u32, url: String, message: String },
+    /// 文件或目录读写失败
+    Io { path: String, message: String },
+    /// 图片解码、编码或处理失败
+    Image { path: String, message: String },
+    /// 显示器不存在或无法获取显示器信息
+    Monitor { monitor: Option<usize>, message: String },
+    /// 当前平台或桌面环境不支持
+    Unsupported { platform: String, message: String },
+    /// 天气服务返回错误
+    Weather { message: String },
+    /// 参数不合法
+    InvalidInput { message: String },
+    /// 外部程序（ffmpeg、gsettings 等）执行失败
+    External { program: String, message: String },
+    /// 操作被取消
+    Cancelled { message: String },
+    /// 其他内部错误
+    Internal { message: String },
+}
+
+pub type EarthResult<T> = Result<T, EarthError>;
+
+/// 上下文按当前语言翻译，底层错误原样附在后面
+fn join(context: &str, e: impl fmt::Display) -> String {
+    format!("{}: {}", i18n::tr(context), e)
+}
+
+/// 调用方传入的完整文案，目录中有翻译时替换
+fn localize(message: impl Into<String>) -> String {
+    let message = message.into();
+    match i18n::lookup(&message) {
+        Some(translated) => translated.to_string(),
+        None => message,
+    }
+}
+
+impl EarthError {
+    pub fn network(url: impl Into<String>, e: impl fmt::Display) -> Self {
+        EarthError::Network {
+            url: url.into(),
+            message: join("网络请求失败", e),
+        }
+    }
+
+    pub fn tile(x: u32, y: u32, url: impl Into<String>, message: impl Into<String>) -> Self {
+        EarthError::Tile {
+            x,
+            y,
+            url: url.into(),
+            message: localize(message),
+        }
+    }
+
+    pub fn io(path: impl AsRef<Path>, context: &str, e: impl fmt::Display) -> Self {
+        EarthError::Io {
+            path: path.as_ref().to_string_lossy().into_owned(),
+            message: join(context, e),
+        }
+    }
+
+    pub fn image(path: impl AsRef<Path>, context: &str, e: impl fmt::Display) -> Self {
+        EarthError::Image {
+            path: path.as_ref().to_string_lossy().into_owned(),
+            message: join(context, e),
+        }
+    }
+
+    pub fn monitor(monitor: Option<usize>, message: impl Into<String>) -> Self {
+        EarthError::Monitor {
+            monitor,
+            message: localize(message),
+        }
+    }
+
+    pub fn unsupported(platform: impl Into<String>, message: impl Into<String>) -> Self {
+        EarthError::Unsupported {
+            platform: platform.into(),
+            message: localize(message),
+        }
+    }
+
+    pub fn weather(message: impl Into<String>) -> Self {
+        EarthError::Weather {
+            message: localize(message),
+        }
+    }
+
+    pub fn invalid(message: impl Into<String>) -> Self {
+        EarthError::InvalidInput {
+            message: localize(message),
+        }
+    }
+
+    pub fn external(program: impl Into<String>, context: &str, e: impl fmt::Display) -> Self {
+        EarthError::External {
+            program: program.into(),
+            message: join(context, e),
+        }
+    }
+
+    pub fn cancelled() -> Self {
+        EarthError::Cancelled {
+            message: i18n::tr("操作已取消").to_string(),
+        }
+    }
+
+    pub fn internal(context: &str, e: impl fmt::Display) -> Self {
+        EarthError::Internal {
+            message: join(context, e),
+        }
+    }
+
+    /// 稳定的错误码，与序列化后的 code 字段一致
+    pub fn code(&self) -> &'static str {
+        match self {
+            EarthError::Network { .. } => "network",
+            EarthError::Tile { .. } => "tile",
+            EarthError::Io { .. } => "io",
+            EarthError::Image { .. } => "image",
+            EarthError::Monitor { .. } => "monitor",
+            EarthError::Unsupported { .. } => "unsupported",
+            EarthError::Weather { .. } => "weather",
+            EarthError::InvalidInput { .. } => "invalid_input",
+            EarthError::External { .. } => "external",
+            EarthError::Cancelled { .. } => "cancelled",
+            EarthError::Internal { .. } => "internal",
+        }
+    }
+
+    pub fn message(&self) -> &str {
+        match self {
+            EarthError::Network { message, .. }
+            | EarthError::Tile { message, .. }
+            | EarthError::Io { message, .. }
+            | EarthError::Image { message, .. }
+            | EarthError::Monitor { message, .. }
+            | EarthError::Unsupported { message, .. }
+            | EarthError::Weather { message }
+            | EarthError::InvalidInput { message }
+            | EarthError::External { message, .. }
+            | EarthError::Cancelled { message }
+            | EarthError::Internal { message } => message,
+        }
+    }
+}
+
+impl fmt::Display for EarthError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "[{}] {}", self.code(), self.message())
+    }
+}
+
+impl std::error::Error for EarthError {}
+
+impl From<tauri::Error> for EarthError {
+    fn from(e: tauri::Error) -> Self {
+        EarthError::internal("Tauri 调用失败", e)
+    }
+}
@@ -0,0 +1,101 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::fs;
+use std::path::Path;
+
+use crate::atomic;
+use crate::error::{EarthError, EarthResult};
+use crate::i18n;
+
+/// 历史索引的文件名，放在图片目录下
+const INDEX_FILE: &str = "history.json";
+
+/// 一次拍摄的记录
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Capture {
+    /// 拍摄时间戳，格式 YYYYMMDD_HHMM，同时作为唯一编号
+    pub id: String,
+    pub satellite: String,
+    pub product: String,
+    /// 瓦片层级，4 表示 4x4
+    pub level: u32,
+    pub timestamp: DateTime<Utc>,
+    pub merged_image: String,
+    pub black_image: String,
+    /// 原图和黑边图的总大小（字节）
+    pub size: u64,
+    /// 原图的 SHA-256
+    pub checksum: String,
+}
+
+/// 图片目录下所有拍摄的索引，按时间从旧到新排列
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct HistoryIndex {
+    pub captures: Vec<Capture>,
+}
+
+impl HistoryIndex {
+    pub fn load(base: &Path) -> Self {
+        fs::read_to_string(base.join(INDEX_FILE))
+            .ok()
+            .and_then(|s| serde_json::from_str(&s).ok())
+            .unwrap_or_default()
+    }
+
+    pub fn save(&self, base: &Path) -> EarthResult<()> {
+        let path = base.join(INDEX_FILE);
+        let json = serde_json::to_string_pretty(self)
+            .map_err(|e| EarthError::internal("序列化历史索引失败", e))?;
+        atomic::write_bytes(&path, json, "保存历史索引失败")
+    }
+
+    /// 加入一条记录，同一时间戳的旧记录会被替换
+    pub fn record(&mut self, capture: Capture) {
+        self.captures.retain(|c| c.id != capture.id);
+        self.captures.push(capture);
+        self.captures.sort_by_key(|c| c.timestamp);
+    }
+
+    /// 去掉文件已被删除的记录，返回是否有改动
+    ///
+    /// 只要原图或黑边图还在就保留：设置壁纸用的黑边图缺失时由原图重新生成，
+    /// 延时动画只用原图，原图缺失的记录会在导出时跳过。
+    pub fn prune_missing(&mut self) -> bool {
+        let before = self.captures.len();
+        self.captures
+            .retain(|c| Path::new(&c.merged_image).exists() || Path::new(&c.black_image).exists());
+        self.captures.len() != before
+    }
+
+    pub fn get(&self, id: &str) -> Option<&Capture> {
+        self.captures.iter().find(|c| c.id == id)
+    }
+
+    /// 按时间范围筛选，两端都包含
+    pub fn filter(&self, from: Option<DateTime<Utc>>, to: Option<DateTime<Utc>>) -> Vec<&Capture> {
+        self.captures
+            .iter()
+            .filter(|c| from.is_none_or(|from| c.timestamp >= from))
+            .filter(|c| to.is_none_or(|to| c.timestamp <= to))
+            .collect()
+    }
+}
+
+/// 计算文件的 SHA-256
+pub fn checksum(path: &Path) -> EarthResult<String> {
+    let data = fs::read(path).map_err(|e| EarthError::io(path, "读取图片失败", e))?;
+    let digest = Sha256::digest(&data);
+    Ok(digest.iter().map(|b| format!("{:02x}", b)).collect())
+}
+
+/// 解析前端传来的 RFC 3339 时间
+pub fn parse_time(value: Option<String>) -> EarthResult<Option<DateTime<Utc>>> {
+    value
+        .map(|s| {
+            DateTime::parse_from_rfc3339(&s)
+                .map(|t| t.with_timezone(&Utc))
+                .map_err(|e| EarthError::invalid(i18n::trf("无效的时间 {}: {}", &[&s, &e])))
+        })
+        .transpose()
+}
@@ -0,0 +1,272 @@
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::sync::RwLock;
+
+/// 后端支持的界面语言
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Locale {
+    #[default]
+    #[serde(rename = "zh-CN")]
+    ZhCn,
+    #[serde(rename = "en")]
+    En,
+    #[serde(rename = "ja")]
+    Ja,
+}
+
+impl Locale {
+    /// 解析 BCP 47 / POSIX 风格的语言标签，如 zh-CN、zh_Hans、en-US、ja_JP.UTF-8
+    pub fn from_tag(tag: &str) -> Option<Self> {
+        let lang = tag
+            .split(['-', '_', '.'])
+            .next()
+            .unwrap_or_default()
+            .to_ascii_lowercase();
+        match lang.as_str() {
+            "zh" => Some(Locale::ZhCn),
+            "en" => Some(Locale::En),
+            "ja" => Some(Locale::Ja),
+            _ => None,
+        }
+    }
+
+    pub fn tag(self) -> &'static str {
+        match self {
+            Locale::ZhCn => "zh-CN",
+            Locale::En => "en",
+            Locale::Ja => "ja",
+        }
+    }
+
+    /// 按系统语言选择；系统语言不在支持列表中时用英文
+    pub fn detect() -> Self {
+        match tauri_plugin_os::locale() {
+            Some(tag) => Locale::from_tag(&tag).unwrap_or(Locale::En),
+            None => Locale::default(),
+        }
+    }
+
+    /// ISO 639-1 语言代码，和风天气和 Open-Meteo 的 lang/language 参数
+    pub fn language(self) -> &'static str {
+        match self {
+            Locale::ZhCn => "zh",
+            Locale::En => "en",
+            Locale::Ja => "ja",
+        }
+    }
+
+    /// OpenWeather 接口的 lang 参数
+    pub fn openweather_lang(self) -> &'static str {
+        match self {
+            Locale::ZhCn => "zh_cn",
+            Locale::En => "en",
+            Locale::Ja => "ja",
+        }
+    }
+}
+
+static CURRENT: RwLock<Locale> = RwLock::new(Locale::ZhCn);
+
+pub fn current() -> Locale {
+    *CURRENT.read().unwrap()
+}
+
+pub fn set(locale: Locale) {
+    *CURRENT.write().unwrap() = locale;
+}
+
+/// 文案目录：(中文原文, English, 日本語)
+///
+/// 以中文原文作为 msgid，代码里照常写中文，查不到翻译时原样显示。
+/// 带参数的文案用 `{}` 占位，按顺序替换，见 [`trf`]。
+const CATALOG: &[(&str, &str, &str)] = &[
+    // 托盘与窗口菜单
+    ("退出", "Quit", "終了"),
+    ("显示窗口", "Show Window", "ウィンドウを表示"),
+    ("立即更新壁纸", "Update Wallpaper Now", "今すぐ壁紙を更新"),
+    ("自动设置壁纸", "Set Wallpaper Automatically", "壁紙を自動で設定"),
+    ("主菜单", "Main Menu", "メインメニュー"),
+    ("Tauri 文档", "Tauri Docs", "Tauri ドキュメント"),
+    // 天气
+    ("{} {} 湿度{}% ({})", "{} {}, humidity {}% ({})", "{} {} 湿度{}% ({})"),
+    ("体感 {}，风速 {}", "Feels like {}, wind {}", "体感 {}、風速 {}"),
+    ("读取{}响应失败: {}", "Failed to read {} response: {}", "{} の応答を読み込めませんでした: {}"),
+    ("解析{}响应失败: {}", "Failed to parse {} response: {}", "{} の応答を解析できませんでした: {}"),
+    ("未获取到天气信息", "No weather data returned", "天気情報を取得できませんでした"),
+    ("缺少{}的 API Key", "Missing {} API key", "{} の API キーがありません"),
+    ("未找到该城市", "City not found", "都市が見つかりません"),
+    ("{} 返回错误码 {}", "{} returned error code {}", "{} がエラーコード {} を返しました"),
+    ("读取钥匙串失败", "Failed to read from the system keychain", "キーチェーンから読み込めませんでした"),
+    ("保存到钥匙串失败", "Failed to save to the system keychain", "キーチェーンに保存できませんでした"),
+    // 位置
+    ("经纬度超出范围", "Latitude or longitude out of range", "緯度または経度が範囲外です"),
+    ("无法确定当前位置", "Cannot determine the current location", "現在地を特定できません"),
+    // 文字叠加
+    ("字号需在 8 到 200 之间", "Font size must be between 8 and 200", "フォントサイズは 8〜200 の範囲で指定してください"),
+    ("颜色格式应为 #RRGGBB 或 #RRGGBBAA", "Color must be #RRGGBB or #RRGGBBAA", "色は #RRGGBB または #RRGGBBAA で指定してください"),
+    // 经纬网和海岸线
+    ("经纬线间隔需在 1 到 45 度之间", "Grid spacing must be between 1 and 45 degrees", "経緯線の間隔は 1〜45 度の範囲で指定してください"),
+    ("线宽需在 0.5 到 10 之间", "Line width must be between 0.5 and 10", "線の太さは 0.5〜10 の範囲で指定してください"),
+    ("不透明度需在 0 到 1 之间", "Opacity must be between 0 and 1", "不透明度は 0〜1 の範囲で指定してください"),
+    ("解析地图数据失败", "Failed to parse map data", "地図データを解析できませんでした"),
+    ("无法获取资源目录", "Cannot resolve the resource directory", "リソースフォルダを取得できません"),
+    ("地点名称不能为空", "Place name cannot be empty", "地点名を入力してください"),
+    ("地点名称不能超过 40 个字符", "Place name cannot exceed 40 characters", "地点名は 40 文字以内で入力してください"),
+    ("设置格式错误", "Invalid settings format", "設定の形式が正しくありません"),
+    ("设置格式错误: {}", "Invalid settings format: {}", "設定の形式が正しくありません: {}"),
+    ("未知的设置项: {}", "Unknown setting: {}", "不明な設定項目: {}"),
+    ("不支持的分辨率档位", "Unsupported resolution level", "対応していない解像度レベルです"),
+    ("更新间隔需在 10 到 1440 分钟之间", "Update interval must be between 10 and 1440 minutes", "更新間隔は 10〜1440 分の範囲で指定してください"),
+    ("没有可用的地球图像", "No Earth image available", "利用できる地球画像がありません"),
+    ("序列化设置失败", "Failed to serialize settings", "設定をシリアライズできませんでした"),
+    ("设置方案", "Profiles", "設定プロファイル"),
+    ("设置文件格式错误: {}", "Invalid settings file: {}", "設定ファイルの形式が正しくありません: {}"),
+    ("不是本应用导出的设置文件", "Not a settings file exported by this app", "このアプリからエクスポートされた設定ファイルではありません"),
+    ("读取设置文件失败", "Failed to read the settings file", "設定ファイルを読み込めませんでした"),
+    ("创建导出目录失败", "Failed to create the export folder", "エクスポート先フォルダを作成できませんでした"),
+    ("导出设置失败", "Failed to export settings", "設定をエクスポートできませんでした"),
+    ("设置方案不存在: {}", "Profile not found: {}", "設定プロファイルが見つかりません: {}"),
+    ("方案名称需为 1 到 32 个字符", "Profile name must be 1 to 32 characters", "プロファイル名は 1〜32 文字で入力してください"),
+    ("序列化设置方案失败", "Failed to serialize profiles", "設定プロファイルをシリアライズできませんでした"),
+    ("保存设置方案失败", "Failed to save profiles", "設定プロファイルを保存できませんでした"),
+    ("读取设置方案失败", "Failed to read profiles", "設定プロファイルを読み込めませんでした"),
+    ("备份无法解析的设置方案失败", "Failed to back up the unreadable profiles file", "読み込めない設定プロファイルをバックアップできませんでした"),
+    ("晴", "Clear sky", "快晴"),
+    ("大部晴朗", "Mainly clear", "晴れ"),
+    ("多云", "Partly cloudy", "一部曇り"),
+    ("阴", "Overcast", "曇り"),
+    ("雾", "Fog", "霧"),
+    ("毛毛雨", "Drizzle", "霧雨"),
+    ("雨", "Rain", "雨"),
+    ("冻雨", "Freezing rain", "着氷性の雨"),
+    ("雪", "Snow", "雪"),
+    ("阵雨", "Rain showers", "にわか雨"),
+    ("阵雪", "Snow showers", "にわか雪"),
+    ("雷暴", "Thunderstorm", "雷雨"),
+    ("未知天气", "Unknown", "不明"),
+    // 通用错误
+    ("网络请求失败", "Network request failed", "ネットワーク要求に失敗しました"),
+    ("操作已取消", "Operation cancelled", "操作はキャンセルされました"),
+    ("Tauri 调用失败", "Tauri call failed", "Tauri の呼び出しに失敗しました"),
+    ("无法获取应用本地数据目录", "Cannot resolve the app local data directory", "アプリのローカルデータフォルダを取得できません"),
+    ("获取显示器信息失败: {}", "Failed to get monitor info: {}", "モニター情報を取得できませんでした: {}"),
+    ("获取主屏幕失败: {}", "Failed to get the primary monitor: {}", "メインモニターを取得できませんでした: {}"),
+    ("无效的显示器索引: {}", "Invalid monitor index: {}", "無効なモニター番号: {}"),
+    ("不支持的平台", "Unsupported platform", "サポートされていないプラットフォームです"),
+    ("序列化结果失败", "Failed to serialize result", "結果のシリアライズに失敗しました"),
+    ("序列化显示器信息失败", "Failed to serialize monitor info", "モニター情報のシリアライズに失敗しました"),
+    ("读取设置失败", "Failed to read settings", "設定を読み込めませんでした"),
+    ("保存设置失败", "Failed to save settings", "設定を保存できませんでした"),
+    ("无法获取日志目录", "Cannot resolve the log directory", "ログフォルダを取得できません"),
+    ("读取日志目录失败", "Failed to read the log directory", "ログフォルダを読み込めませんでした"),
+    ("读取日志文件失败", "Failed to read the log file", "ログファイルを読み込めませんでした"),
+    // 下载
+    ("流程异常退出", "Run exited unexpectedly", "処理が異常終了しました"),
+    ("下载任务异常退出", "Download task exited unexpectedly", "ダウンロードタスクが異常終了しました"),
+    ("生成黑边图任务异常退出", "Padding task exited unexpectedly", "黒枠画像の生成タスクが異常終了しました"),
+    ("设置壁纸任务异常退出", "Wallpaper task exited unexpectedly", "壁紙設定タスクが異常終了しました"),
+    ("创建 HTTP 客户端失败", "Failed to create HTTP client", "HTTP クライアントを作成できませんでした"),
+    ("无法创建基础目录", "Failed to create the image directory", "画像フォルダを作成できませんでした"),
+    ("创建瓦片目录失败", "Failed to create the tiles directory", "タイルフォルダを作成できませんでした"),
+    ("本次爬取失败，tile({},{})大小异常", "Download failed: tile ({},{}) has an unexpected size", "取得に失敗しました: タイル({},{})のサイズが異常です"),
+    ("瓦片解码失败: {}", "Failed to decode tile: {}", "タイルをデコードできませんでした: {}"),
+    ("保存瓦片失败", "Failed to save tile", "タイルを保存できませんでした"),
+    ("保存拼接大图失败", "Failed to save the stitched image", "結合画像を保存できませんでした"),
+    ("读取拼接后大图失败", "Failed to read the stitched image", "結合画像を読み込めませんでした"),
+    ("保存加黑边大图失败", "Failed to save the bordered image", "黒枠付き画像を保存できませんでした"),
+    ("序列化图片路径失败", "Failed to serialize image paths", "画像パスのシリアライズに失敗しました"),
+    // 历史与清理
+    ("读取图片失败", "Failed to read image", "画像を読み込めませんでした"),
+    ("无效的时间 {}: {}", "Invalid time {}: {}", "無効な時刻 {}: {}"),
+    ("序列化历史索引失败", "Failed to serialize the history index", "履歴インデックスのシリアライズに失敗しました"),
+    ("保存历史索引失败", "Failed to save the history index", "履歴インデックスを保存できませんでした"),
+    ("序列化历史记录失败", "Failed to serialize captures", "履歴のシリアライズに失敗しました"),
+    ("历史记录不存在: {}", "Capture not found: {}", "履歴が見つかりません: {}"),
+    ("历史图片已被删除", "The capture image has been deleted", "履歴画像は削除されています"),
+    ("序列化当前壁纸记录失败", "Failed to serialize the active wallpaper record", "現在の壁紙記録のシリアライズに失敗しました"),
+    ("保存当前壁纸记录失败", "Failed to save the active wallpaper record", "現在の壁紙記録を保存できませんでした"),
+    ("无法获取缓存目录", "Cannot resolve the cache directory", "キャッシュフォルダを取得できません"),
+    ("创建缓存目录失败", "Failed to create the cache directory", "キャッシュフォルダを作成できませんでした"),
+    ("序列化天气缓存失败", "Failed to serialize the weather cache", "天気キャッシュのシリアライズに失敗しました"),
+    ("保存天气缓存失败", "Failed to save the weather cache", "天気キャッシュを保存できませんでした"),
+    ("同步文件失败", "Failed to sync file", "ファイルを同期できませんでした"),
+    ("替换文件失败", "Failed to replace file", "ファイルを置き換えられませんでした"),
+    ("序列化清理结果失败", "Failed to serialize the cleanup result", "クリーンアップ結果のシリアライズに失敗しました"),
+    // 延时动画与视频
+    ("结束时间早于开始时间", "End time is earlier than start time", "終了時刻が開始時刻より前です"),
+    ("无效的开始时间: {}", "Invalid start time: {}", "無効な開始時刻: {}"),
+    ("帧数超过上限 {}，请缩小时间范围或增大步长", "More than {} frames; narrow the time range or increase the step", "フレーム数が上限 {} を超えています。期間を短くするか間隔を広げてください"),
+    ("所选时间范围内没有可用的图片", "No images available in the selected time range", "選択した期間に利用できる画像がありません"),
+    ("输出尺寸无效", "Invalid output size", "出力サイズが無効です"),
+    ("GIF 的宽高不能超过 65535 像素", "GIF width and height cannot exceed 65535 pixels", "GIF の幅と高さは 65535 ピクセル以下にしてください"),
+    ("打开图片失败", "Failed to open image", "画像を開けませんでした"),
+    ("创建延时动画目录失败", "Failed to create the time-lapse directory", "タイムラプス用フォルダを作成できませんでした"),
+    ("创建输出文件失败", "Failed to create the output file", "出力ファイルを作成できませんでした"),
+    ("GIF 编码失败", "Failed to encode GIF", "GIF をエンコードできませんでした"),
+    ("APNG 编码失败", "Failed to encode APNG", "APNG をエンコードできませんでした"),
+    ("WebP 编码失败", "Failed to encode WebP", "WebP をエンコードできませんでした"),
+    ("创建序列帧目录失败", "Failed to create the frames directory", "連番フレーム用フォルダを作成できませんでした"),
+    ("保存序列帧失败", "Failed to save frame", "フレームを保存できませんでした"),
+    ("导出任务异常退出", "Export task exited unexpectedly", "エクスポートタスクが異常終了しました"),
+    ("序列化导出结果失败", "Failed to serialize the export result", "エクスポート結果のシリアライズに失敗しました"),
+    ("启动 ffmpeg 失败", "Failed to start ffmpeg", "ffmpeg を起動できませんでした"),
+    ("无法读取 ffmpeg 输出", "Cannot read ffmpeg output", "ffmpeg の出力を読み取れません"),
+    ("等待 ffmpeg 结束失败", "Failed to wait for ffmpeg", "ffmpeg の終了待ちに失敗しました"),
+    ("ffmpeg 编码失败", "ffmpeg encoding failed", "ffmpeg のエンコードに失敗しました"),
+    // 动态壁纸
+    ("历史图片不足两张，无法生成动态壁纸", "At least two captures are needed for a live wallpaper", "ライブ壁紙には少なくとも 2 枚の履歴画像が必要です"),
+    ("创建动态壁纸目录失败", "Failed to create the live wallpaper directory", "ライブ壁紙用フォルダを作成できませんでした"),
+    ("保存动态壁纸帧失败", "Failed to save live wallpaper frame", "ライブ壁紙のフレームを保存できませんでした"),
+    ("保存过渡帧失败", "Failed to save transition frame", "トランジションフレームを保存できませんでした"),
+    ("读取动态壁纸帧失败", "Failed to read a live wallpaper frame", "ライブ壁紙のフレームを読み込めませんでした"),
+    ("动态壁纸任务异常退出", "Live wallpaper task exited unexpectedly", "ライブ壁紙タスクが異常終了しました"),
+    ("读取动态壁纸目录失败", "Failed to read the live wallpaper directory", "ライブ壁紙用フォルダを読み込めませんでした"),
+    ("当前桌面不支持幻灯片壁纸", "This desktop does not support slideshow wallpapers", "このデスクトップはスライドショー壁紙に対応していません"),
+    // 设置壁纸
+    ("创建显示器壁纸目录失败", "Failed to create the monitor wallpaper directory", "モニター用壁紙フォルダを作成できませんでした"),
+    ("保存裁剪后图片失败", "Failed to save the cropped image", "切り抜いた画像を保存できませんでした"),
+    ("壁纸文件不存在", "Wallpaper file does not exist", "壁紙ファイルが存在しません"),
+    ("请检查文件路径是否正确", "Please check the file path", "ファイルパスを確認してください"),
+    ("设置壁纸失败", "Failed to set wallpaper", "壁紙を設定できませんでした"),
+    ("文件路径: {}", "File path: {}", "ファイルパス: {}"),
+    ("macOS 设置失败", "Failed to set the macOS wallpaper", "macOS の壁紙を設定できませんでした"),
+    ("KDE 设置失败", "Failed to set the KDE wallpaper", "KDE の壁紙を設定できませんでした"),
+    ("KDE 幻灯片设置失败", "Failed to set the KDE slideshow", "KDE のスライドショーを設定できませんでした"),
+    ("GNOME 设置失败", "Failed to set the GNOME wallpaper", "GNOME の壁紙を設定できませんでした"),
+    ("写入 GNOME 幻灯片失败", "Failed to write the GNOME slideshow", "GNOME のスライドショーを書き込めませんでした"),
+    ("写入 GNOME 背景 XML 失败", "Failed to write the GNOME background XML", "GNOME の背景 XML を書き込めませんでした"),
+];
+
+/// 查找当前语言的翻译，目录中没有时返回 None
+pub fn lookup(msgid: &str) -> Option<&'static str> {
+    let locale = current();
+    CATALOG
+        .iter()
+        .find(|(zh, _, _)| *zh == msgid)
+        .map(|&(zh, en, ja)| match locale {
+            Locale::ZhCn => zh,
+            Locale::En => en,
+            Locale::Ja => ja,
+        })
+}
+
+/// 翻译一条文案，找不到时原样返回
+pub fn tr(msgid: &str) -> &str {
+    lookup(msgid).unwrap_or(msgid)
+}
+
+/// 翻译带 `{}` 占位符的文案并按顺序填入参数
+pub fn trf(msgid: &str, args: &[&dyn fmt::Display]) -> String {
+    let mut out = String::new();
+    let mut args = args.iter();
+    let mut parts = tr(msgid).split("{}").peekable();
+    while let Some(part) = parts.next() {
+        out.push_str(part);
+        if parts.peek().is_some()
+            && let Some(arg) = args.next()
+        {
+            out.push_str(&arg.to_string());
+        }
+    }
+    out
+}
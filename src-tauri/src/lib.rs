@@ -1,27 +1,127 @@
-use chrono::{Datelike, Timelike, Utc};
-use image::{self, GenericImageView};
-use reqwest::blocking::Client;
-use serde::{Serialize, Deserialize};
+use chrono::{DateTime, Datelike, Timelike, Utc};
+use serde::Serialize;
 use serde_json::to_string;
 use std::fs;
 use std::path::{Path, PathBuf};
-use tauri::{AppHandle, Manager};
+use tauri::{AppHandle, Emitter, Manager};
 use tauri::tray::{TrayIconBuilder};
-use tauri::menu::{Menu, MenuItem, MenuItemBuilder, SubmenuBuilder, MenuBuilder};
+use tauri::menu::{CheckMenuItem, Menu, MenuItem, MenuItemBuilder, SubmenuBuilder, MenuBuilder};
+use tauri_plugin_store::StoreExt;
 use tauri_plugin_opener::OpenerExt;
+use std::time::Instant;
+use tracing::{Instrument, debug, error, info, info_span, warn};
+mod atomic;
+mod download;
+mod error;
+mod history;
+mod i18n;
+mod live;
+mod location;
+mod logging;
+mod map_overlay;
+mod overlay;
+mod pipeline;
+mod places;
+mod profiles;
+mod progress;
+mod projection;
+mod retention;
+mod secrets;
+mod settings;
+mod timelapse;
+mod video;
 mod wallpaper;
+mod watchdog;
+mod weather;
+mod weather_cache;
+mod weather_format;
+
+use download::{CancelToken, DownloadConfig, DownloadState};
+use error::{EarthError, EarthResult};
+use history::HistoryIndex;
+use i18n::Locale;
+use live::{LiveConfig, LiveState};
+use location::{Framing, LocationState, ManualLocation, ResolvedLocation};
+use map_overlay::MapOverlayConfig;
+use overlay::{OverlayInfo, TextOverlayConfig, TextOverlays};
+use pipeline::{Pipeline, RunId, RunInfo, UPDATE_KEY};
+use places::{MarkerTarget, Place, PlaceMarkerConfig, PlaceMarkers};
+use profiles::{FileFormat, Profiles, SettingChange};
+use progress::{ProgressReporter, UpdateStage};
+use retention::{ActiveWallpaper, ActiveWallpapers, RetentionPolicy, RetentionState};
+use settings::Settings;
+use std::collections::HashMap;
+use std::sync::Mutex;
+use timelapse::TimelapseRequest;
+use video::VideoRequest;
+use weather::{CurrentWeather, Forecast, ProviderKind, WeatherLocation, WeatherState};
+use weather_format::Units;
+
+/// 延时动画和视频导出共用的取消标记
+#[derive(Default)]
+struct TimelapseState {
+    cancel: Mutex<CancelToken>,
+}
 
 // Learn more about Tauri commands at https://v2.tauri.app/develop/calling-rust/
 #[tauri::command]
 fn greet(name: &str) -> String {
-    println!("Backend was called with an argument: {}", name);
+    debug!(name, "Backend was called with an argument");
     format!("Hello, {}! You've been greeted from Rust!", name)
 }
 
 #[tauri::command]
-fn update_earth_image(app: tauri::AppHandle) -> Result<String, String> {
-    // Calculate time for Himawari-8 image (using UTC)
-    let now = Utc::now() - chrono::Duration::minutes(30);
+async fn update_earth_image(app: tauri::AppHandle) -> EarthResult<String> {
+    // 下载可能被限速或分摊到整个发布周期，放到阻塞线程池里执行，避免卡住主线程
+    tauri::async_runtime::spawn_blocking(move || fetch_earth_image(&app, true))
+        .await
+        .map_err(|e| EarthError::internal("下载任务异常退出", e))?
+}
+
+/// 取消正在运行的下载或设置壁纸流程，run_id 为空时取消当前流程，返回被取消的编号
+#[tauri::command]
+fn cancel_update(state: tauri::State<'_, Pipeline>, run_id: Option<RunId>) -> Option<RunId> {
+    state.cancel(run_id)
+}
+
+/// 当前正在运行的流程
+#[tauri::command]
+fn get_current_run(state: tauri::State<'_, Pipeline>) -> Option<RunInfo> {
+    state.current()
+}
+
+/// 卫星名称和产品编号，写入历史索引
+const SATELLITE: &str = "Himawari-9";
+const PRODUCT: &str = "D531106";
+
+/// 下载最新的地球图像；已有下载在运行时合并等待它的结果
+///
+/// trickle 为 false 时立即下载全部瓦片，唤醒后的补充更新使用。
+fn fetch_earth_image(app: &AppHandle, trickle: bool) -> EarthResult<String> {
+    app.state::<Pipeline>().run(UPDATE_KEY, |run_id, cancel| {
+        // Calculate time for Himawari-8 image (using UTC)
+        let now = Utc::now() - chrono::Duration::minutes(30);
+        let reporter = ProgressReporter::new(app, run_id, "update");
+        let result = fetch_capture(app, now, trickle, cancel, &|stage| reporter.stage(stage))
+            .inspect_err(|e| error!(error = %e, "更新地球图像失败"));
+        reporter.finish(&result);
+        result
+    })
+}
+
+/// 下载指定时刻的地球图像，拼接、加黑边并写入历史索引，每个阶段开始时调用 on_stage
+///
+/// trickle 为 false 时忽略涓流设置，用于补下载历史拍摄：否则补一天的延时动画也要下载一整天。
+fn fetch_capture(
+    app: &AppHandle,
+    now: DateTime<Utc>,
+    trickle: bool,
+    cancel: &CancelToken,
+    on_stage: &dyn Fn(UpdateStage),
+) -> EarthResult<String> {
+    let slot_id = now.format("%Y%m%d_%H%M").to_string();
+    let _span = info_span!("update", slot = %slot_id).entered();
+    on_stage(UpdateStage::ResolvingTimestamp { slot: slot_id });
 
     let year = now.year().to_string();
     let month = format!("{:02}", now.month());
@@ -33,93 +133,117 @@ fn update_earth_image(app: tauri::AppHandle) -> Result<String, String> {
     let app_data_dir = app
         .path()
         .app_local_data_dir()
-        .map_err(|e| format!("无法获取应用本地数据目录: {}", e))?;
+        .map_err(|e| EarthError::internal("无法获取应用本地数据目录", e))?;
     let mut base_path = PathBuf::from(app_data_dir);
     base_path.push("immediate_earth");
-    fs::create_dir_all(&base_path).map_err(|e| format!("无法创建基础目录: {}", e))?;
+    fs::create_dir_all(&base_path).map_err(|e| EarthError::io(&base_path, "无法创建基础目录", e))?;
 
     // Create tiles directory
     let mut tiles_path = base_path.clone();
     tiles_path.push("tiles");
     fs::create_dir_all(&tiles_path)
-        .map_err(|e| format!("Failed to create tiles directory: {}", e))?;
+        .map_err(|e| EarthError::io(&tiles_path, "创建瓦片目录失败", e))?;
 
     // Image parameters
-    let multiple = 4; // 4x4 grid for 2200x2200 resolution
+    let multiple = load_settings(app).level; // 每边瓦片数，默认 4x4 即 2200x2200
     let tile_size = 550;
     let total_size = multiple * tile_size;
 
     // Create empty image
-    let mut earth = image::ImageBuffer::new(total_size as u32, total_size as u32);
-    let client = Client::new();
+    let mut earth = image::ImageBuffer::new(total_size, total_size);
+    let client = download::build_client()?;
+
+    // 读取当前的限速与涓流设置
+    let state = app.state::<DownloadState>();
+    let config = state.config.lock().unwrap().clone();
+    let slot = download::tile_slot(&config, multiple * multiple).filter(|_| trickle);
+    on_stage(UpdateStage::Downloading { total: (multiple * multiple) as usize });
 
     for i in 0..multiple {
         for j in 0..multiple {
+            let started = Instant::now();
             let url = format!(
-                "https://himawari.asia/img/D531106/{}d/550/{}/{}/{}/{}{}00_{}_{}.png",
-                multiple, year, month, day, hour, minute, i, j
+                "https://himawari.asia/img/{}/{}d/550/{}/{}/{}/{}{}00_{}_{}.png",
+                PRODUCT, multiple, year, month, day, hour, minute, i, j
             );
             // 日志记录url
-            println!("Downloading tile: {}", url);
-            let response = client.get(&url)
-                .header("User-Agent", "Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/91.0.4472.124 Safari/537.36")
-                .send()
-                .map_err(|e| format!("Failed to download tile: {}", e))?;
-
-            let tile_data = response
-                .bytes()
-                .map_err(|e| format!("Failed to read tile data: {}", e))?;
+            debug!(%url, "Downloading tile");
+            let tile_data = download::download_tile(&client, &url, &state.throttle, cancel)?;
             if tile_data.len() == 2834 {
-                return Err(format!("本次爬取失败，tile({},{})大小异常", i, j));
+                return Err(EarthError::tile(i, j, url, i18n::trf("本次爬取失败，tile({},{})大小异常", &[&i, &j])));
             }
+            on_stage(UpdateStage::TileDownloaded {
+                x: i,
+                y: j,
+                bytes: tile_data.len(),
+                done: (i * multiple + j + 1) as usize,
+                total: (multiple * multiple) as usize,
+            });
             let tile = image::load_from_memory(&tile_data)
-                .map_err(|e| format!("Failed to decode tile: {}", e))?
+                .map_err(|e| EarthError::tile(i, j, url.as_str(), i18n::trf("瓦片解码失败: {}", &[&e])))?
                 .to_rgba8();
 
             // Save individual tile
             let tile_path = tiles_path.join(format!("tile_{}_{}.png", i, j));
-            fs::write(&tile_path, &tile_data).map_err(|e| format!("Failed to save tile: {}", e))?;
+            atomic::write_bytes(&tile_path, &tile_data, "保存瓦片失败")?;
 
             // Paste tile into position
             let x = i * tile_size;
             let y = j * tile_size;
             image::imageops::replace(&mut earth, &tile, x as i64, y as i64);
+
+            // 涓流模式：最后一个瓦片之外，等满时间片再下载下一个
+            if i * multiple + j + 1 < multiple * multiple {
+                download::wait_for_slot(slot, started, cancel)?;
+            }
         }
     }
 
-    // Save merged image
+    // 瓦片已全部拼进大图，保存不带叠加层的原图
+    on_stage(UpdateStage::Stitching);
     let img_filename = format!("earth_{}{}{}_{}{}.png", year, month, day, hour, minute);
     let mut merged_img_path = base_path.clone();
     merged_img_path.push(&img_filename);
-    earth
-        .save(&merged_img_path)
-        .map_err(|e| format!("Failed to save merged image: {}", e))?;
+    atomic::save_image(&merged_img_path, "保存拼接大图失败", |tmp, format| {
+        earth.save_with_format(tmp, format)
+    })?;
 
     // 1. 生成 black 后缀路径
     let black_img_path = add_suffix_to_filename(&merged_img_path, "_black");
 
-    // 2. 读取原始大图，加黑边
-    let path = Path::new(&merged_img_path);
-    let img = image::open(path)
-        .map_err(|e| format!("读取拼接后大图失败: {}", e))?;
-    let (orig_img_width, orig_img_height) = img.dimensions();
-    let img_width = orig_img_width;
-    let img_height = orig_img_height;
+    // 2. 原图已保存，叠加层直接画在内存中的大图上，只进入黑边图和壁纸
+    draw_capture_overlays(app, &mut earth, on_stage)?;
 
-    // black_border 基于原始宽度
-    let black_border = (orig_img_width as f32 / 10.0).round() as u32;
+    // 3. 加黑边并保存
+    on_stage(UpdateStage::Padding);
+    save_black_image(&earth, &black_img_path)?;
 
-    let new_w = img_width + black_border * 2;
-    let new_h = img_height + black_border * 2;
-    let mut canvas = image::RgbaImage::from_pixel(new_w, new_h, image::Rgba([0, 0, 0, 255]));
-    image::imageops::replace(&mut canvas, &img.to_rgba8(), black_border as i64, black_border as i64);
-
-    // 3. 保存 black 图
-    canvas.save(&black_img_path)
-        .map_err(|e| format!("保存加黑边大图失败: {}", e))?;
-    println!("【地球大图】加黑边后图片已保存: {}", black_img_path.to_string_lossy());
+    // 4. 写入历史索引
+    let size = [&merged_img_path, &black_img_path]
+        .iter()
+        .filter_map(|p| fs::metadata(p).ok())
+        .map(|m| m.len())
+        .sum();
+    let timestamp = now
+        .with_minute((now.minute() / 10) * 10)
+        .and_then(|t| t.with_second(0))
+        .and_then(|t| t.with_nanosecond(0))
+        .unwrap_or(now);
+    let mut index = HistoryIndex::load(&base_path);
+    index.record(history::Capture {
+        id: format!("{}{}{}_{}{}", year, month, day, hour, minute),
+        satellite: SATELLITE.to_string(),
+        product: PRODUCT.to_string(),
+        level: multiple,
+        timestamp,
+        merged_image: merged_img_path.to_string_lossy().into_owned(),
+        black_image: black_img_path.to_string_lossy().into_owned(),
+        size,
+        checksum: history::checksum(&merged_img_path)?,
+    });
+    index.save(&base_path)?;
 
-    // 4. 返回 black 图路径
+    // 5. 返回 black 图路径
     #[derive(Serialize)]
     struct ImagePaths {
         tiles_dir: String,
@@ -131,7 +255,82 @@ fn update_earth_image(app: tauri::AppHandle) -> Result<String, String> {
         merged_image: merged_img_path.to_string_lossy().into_owned(),
         black_image: black_img_path.to_string_lossy().into_owned(),
     };
-    to_string(&image_paths).map_err(|e| format!("Failed to serialize paths: {}", e))
+    to_string(&image_paths).map_err(|e| EarthError::internal("序列化图片路径失败", e))
+}
+
+/// 按当前设置在拼接大图上画经纬网、海岸线和地点标注，有内容要画时先发出 Overlaying
+///
+/// 调用方传入的是原图保存后的大图：历史记录、校验和与延时动画用的原图保持干净。
+fn draw_capture_overlays(
+    app: &AppHandle,
+    img: &mut image::RgbaImage,
+    on_stage: &dyn Fn(UpdateStage),
+) -> EarthResult<()> {
+    let settings = load_settings(app);
+    let map_config = settings.map_overlay.clone();
+    let places = place_markers(&settings).filter(|p| p.config.target == MarkerTarget::Capture);
+    if map_config.enabled || places.is_some() {
+        on_stage(UpdateStage::Overlaying);
+    }
+    if map_config.enabled {
+        let geo_dir = app
+            .path()
+            .resource_dir()
+            .map_err(|e| EarthError::internal("无法获取资源目录", e))?
+            .join("geo");
+        map_overlay::draw(img, &map_config, &geo_dir)?;
+    }
+    if let Some(places) = places {
+        let size = img.width().min(img.height()) as f64;
+        places.draw(img, 1.0, |(u, v)| (u * size, v * size))?;
+    }
+    Ok(())
+}
+
+/// 黑边图被删除时用原图按当前的叠加层设置重新生成，两者都不在才算历史图片已被删除
+fn ensure_black_image(app: &AppHandle, capture: &history::Capture) -> EarthResult<()> {
+    let black_path = Path::new(&capture.black_image);
+    if black_path.exists() {
+        return Ok(());
+    }
+    let merged_path = Path::new(&capture.merged_image);
+    if !merged_path.exists() {
+        return Err(EarthError::io(black_path, "历史图片已被删除", &capture.id));
+    }
+    let mut img = image::open(merged_path)
+        .map_err(|e| EarthError::image(merged_path, "读取拼接后大图失败", e))?
+        .to_rgba8();
+    draw_capture_overlays(app, &mut img, &|_| {})?;
+    save_black_image(&img, black_path)?;
+    info!(id = %capture.id, "已用原图重新生成黑边图");
+    Ok(())
+}
+
+/// 给拼接大图四周加黑边并保存，黑边宽度为原图宽度乘以 projection::PADDING
+fn save_black_image(img: &image::RgbaImage, black_img_path: &Path) -> EarthResult<()> {
+    let (img_width, img_height) = img.dimensions();
+    let black_border = (img_width as f64 * projection::PADDING).round() as u32;
+    let mut canvas = image::RgbaImage::from_pixel(
+        img_width + black_border * 2,
+        img_height + black_border * 2,
+        image::Rgba([0, 0, 0, 255]),
+    );
+    image::imageops::replace(&mut canvas, img, black_border as i64, black_border as i64);
+    atomic::save_image(black_img_path, "保存加黑边大图失败", |tmp, format| {
+        canvas.save_with_format(tmp, format)
+    })?;
+    info!(path = %black_img_path.display(), "加黑边后图片已保存");
+    Ok(())
+}
+
+/// 补下载某个时刻的拍摄，与其他下载排队执行，避免同时写 tiles/
+///
+/// 本次的取消标记派生自调用方的 cancel，调用方取消或通过 cancel_update 取消本次都会停下。
+fn download_capture(app: &AppHandle, time: DateTime<Utc>, cancel: &CancelToken) -> EarthResult<()> {
+    let key = format!("capture:{}", time.format("%Y%m%d_%H%M"));
+    app.state::<Pipeline>()
+        .run_linked(&key, cancel, |_, cancel| fetch_capture(app, time, false, cancel, &|_| {}))
+        .map(|_| ())
 }
 
 /// 辅助函数：为文件名添加后缀，保留原扩展名
@@ -148,110 +347,796 @@ pub fn add_suffix_to_filename(path: &Path, suffix: &str) -> PathBuf {
     new_path
 } 
 
-#[tauri::command]
-fn get_image_dir(app: tauri::AppHandle) -> Result<String, String> {
+/// 图片保存目录：应用本地数据目录下的 immediate_earth
+fn image_base_dir(app: &AppHandle) -> EarthResult<PathBuf> {
     let app_data_dir = app
         .path()
         .app_local_data_dir()
-        .map_err(|e| format!("无法获取应用本地数据目录: {}", e))?;
-    let mut base_path = std::path::PathBuf::from(app_data_dir);
-    base_path.push("immediate_earth");
+        .map_err(|e| EarthError::internal("无法获取应用本地数据目录", e))?;
+    Ok(app_data_dir.join("immediate_earth"))
+}
+
+#[tauri::command]
+fn get_image_dir(app: tauri::AppHandle) -> EarthResult<String> {
+    let base_path = image_base_dir(&app)?;
     Ok(base_path.to_string_lossy().to_string())
 }
 
-/// 只保留今天的图片，其余全部删除
+/// 各显示器当前的壁纸，壁纸文件名每次设置都会变化，前端据此读取预览
 #[tauri::command]
-fn clean_old_images(app: tauri::AppHandle) -> Result<(), String> {
-    use chrono::Datelike;
-    // 获取图片保存目录
-    let app_data_dir = app
+fn get_active_wallpapers(app: AppHandle) -> EarthResult<ActiveWallpapers> {
+    let base_path = image_base_dir(&app)?;
+    Ok(ActiveWallpapers::load(&base_path))
+}
+
+/// 最近的日志，默认 200 行，便于用户反馈问题时附上
+#[tauri::command]
+fn get_recent_logs(app: AppHandle, lines: Option<usize>) -> EarthResult<Vec<String>> {
+    let log_dir = app
         .path()
-        .app_local_data_dir()
-        .map_err(|e| format!("无法获取应用本地数据目录: {}", e))?;
-    let mut base_path = std::path::PathBuf::from(app_data_dir);
-    base_path.push("immediate_earth");
+        .app_log_dir()
+        .map_err(|e| EarthError::internal("无法获取日志目录", e))?;
+    logging::recent_lines(&log_dir, lines.unwrap_or(200).min(5000))
+}
 
-    // 获取今天日期字符串，格式：yyyyMMdd
-    let now = chrono::Utc::now();
-    let today_str = format!("{:04}{:02}{:02}", now.year(), now.month(), now.day());
-
-    // 遍历 immediate_earth 目录下所有文件
-    if let Ok(entries) = std::fs::read_dir(&base_path) {
-        for entry in entries.flatten() {
-            let path = entry.path();
-            // 跳过目录（如 tiles）
-            if path.is_dir() { continue; }
-            // 只处理 png 文件
-            if let Some(ext) = path.extension() {
-                if ext != "png" { continue; }
-            } else {
-                continue;
-            }
-            // 文件名中包含今天日期则保留，否则删除
-            let fname = path.file_name().unwrap_or_default().to_string_lossy();
-            if !fname.contains(&today_str) {
-                let _ = std::fs::remove_file(&path);
-                println!("【前端定时清理】已删除旧图片: {}", path.to_string_lossy());
+/// 获取当前的下载限速配置
+#[tauri::command]
+fn get_download_config(state: tauri::State<'_, DownloadState>) -> DownloadConfig {
+    state.config.lock().unwrap().clone()
+}
+
+/// 修改下载限速配置并保存，对正在进行的下载立即生效；限速按 KB/s 向上取整保存
+#[tauri::command]
+fn set_download_config(app: AppHandle, config: DownloadConfig) -> EarthResult<()> {
+    info!(rate_limit_bps = ?config.rate_limit_bps, trickle = config.trickle, "下载设置已更新");
+    update_settings_with(&app, |settings| {
+        settings.rate_limit_kb = config.rate_limit_bps.map_or(0, |bps| bps.div_ceil(1024));
+        settings.trickle_download = config.trickle;
+    })?;
+    Ok(())
+}
+
+/// 按保留策略计算需要删除的图片，当前壁纸及其来源大图始终保留
+fn plan_image_cleanup(app: &AppHandle) -> EarthResult<Vec<retention::CleanupItem>> {
+    let base_path = image_base_dir(app)?;
+    let policy = app.state::<RetentionState>().policy.lock().unwrap().clone();
+    let protected = ActiveWallpapers::load(&base_path).protected_paths();
+    Ok(retention::plan_cleanup(&base_path, &policy, &protected, Utc::now()))
+}
+
+/// 按保留策略清理旧图片，返回已删除的文件列表
+#[tauri::command]
+fn clean_old_images(app: tauri::AppHandle) -> EarthResult<String> {
+    let deleted = retention::execute(plan_image_cleanup(&app)?);
+    // 同步去掉历史索引中已删除的记录
+    let base_path = image_base_dir(&app)?;
+    let mut index = HistoryIndex::load(&base_path);
+    if index.prune_missing() {
+        index.save(&base_path)?;
+    }
+    serde_json::to_string(&deleted).map_err(|e| EarthError::internal("序列化清理结果失败", e))
+}
+
+/// 预览清理：只列出将被删除的文件，不做删除
+#[tauri::command]
+fn preview_clean_old_images(app: tauri::AppHandle) -> EarthResult<String> {
+    let plan = plan_image_cleanup(&app)?;
+    serde_json::to_string(&plan).map_err(|e| EarthError::internal("序列化清理结果失败", e))
+}
+
+/// 获取图片保留策略
+#[tauri::command]
+fn get_retention_policy(state: tauri::State<'_, RetentionState>) -> RetentionPolicy {
+    state.policy.lock().unwrap().clone()
+}
+
+/// 修改图片保留策略并保存
+#[tauri::command]
+fn set_retention_policy(app: AppHandle, policy: RetentionPolicy) -> EarthResult<()> {
+    info!(?policy, "清理设置已更新");
+    update_settings_with(&app, |settings| settings.retention = policy)?;
+    Ok(())
+}
+
+/// 列出历史拍摄记录，可按时间范围（RFC 3339）筛选
+#[tauri::command]
+fn list_captures(app: tauri::AppHandle, from: Option<String>, to: Option<String>) -> EarthResult<String> {
+    let base_path = image_base_dir(&app)?;
+    let mut index = HistoryIndex::load(&base_path);
+    if index.prune_missing() {
+        index.save(&base_path)?;
+    }
+    let captures = index.filter(history::parse_time(from)?, history::parse_time(to)?);
+    serde_json::to_string(&captures).map_err(|e| EarthError::internal("序列化历史记录失败", e))
+}
+
+/// 把某次历史拍摄重新设置为壁纸，走与最新图片相同的裁剪流程
+#[tauri::command]
+async fn apply_capture(
+    app: AppHandle,
+    id: String,
+    platform: String,
+    monitor_indexes: Option<Vec<usize>>,
+) -> EarthResult<String> {
+    let base_path = image_base_dir(&app)?;
+    let index = HistoryIndex::load(&base_path);
+    let capture = index
+        .get(&id)
+        .ok_or_else(|| EarthError::invalid(i18n::trf("历史记录不存在: {}", &[&id])))?;
+    let black_image = capture.black_image.clone();
+    if !Path::new(&black_image).exists() {
+        let (handle, capture) = (app.clone(), capture.clone());
+        tauri::async_runtime::spawn_blocking(move || ensure_black_image(&handle, &capture))
+            .await
+            .map_err(|e| EarthError::internal("生成黑边图任务异常退出", e))??;
+    }
+    info!(%id, "重新应用历史壁纸");
+    set_wallpaper_for_all_monitors(app, black_image, Some(platform), monitor_indexes).await
+}
+
+/// 导出延时动画，缺失的拍摄会先补下载，进度通过 timelapse-progress 事件上报
+#[tauri::command]
+async fn export_timelapse(app: AppHandle, request: TimelapseRequest) -> EarthResult<String> {
+    let cancel = CancelToken::default();
+    *app.state::<TimelapseState>().cancel.lock().unwrap() = cancel.clone();
+    tauri::async_runtime::spawn_blocking(move || {
+        let base_path = image_base_dir(&app)?;
+        timelapse::export(
+            &base_path,
+            &request,
+            &cancel,
+            |time| download_capture(&app, time, &cancel),
+            |progress| {
+                let _ = app.emit("timelapse-progress", progress);
+            },
+        )
+    })
+    .await
+    .map_err(|e| EarthError::internal("导出任务异常退出", e))?
+}
+
+/// 导出延时视频：先渲染 PNG 序列帧，PATH 中有 ffmpeg 时再合成 MP4/WebM
+#[tauri::command]
+async fn export_video(app: AppHandle, request: VideoRequest) -> EarthResult<String> {
+    let cancel = CancelToken::default();
+    *app.state::<TimelapseState>().cancel.lock().unwrap() = cancel.clone();
+    tauri::async_runtime::spawn_blocking(move || {
+        let base_path = image_base_dir(&app)?;
+        let result = video::export(
+            &base_path,
+            &request,
+            &cancel,
+            |time| download_capture(&app, time, &cancel),
+            |progress| {
+                let _ = app.emit("timelapse-progress", progress);
+            },
+        )?;
+        serde_json::to_string(&result).map_err(|e| EarthError::internal("序列化导出结果失败", e))
+    })
+    .await
+    .map_err(|e| EarthError::internal("导出任务异常退出", e))?
+}
+
+/// 取消正在进行的延时动画或视频导出
+#[tauri::command]
+fn cancel_timelapse(state: tauri::State<'_, TimelapseState>) {
+    state.cancel.lock().unwrap().cancel();
+}
+
+/// 开启动态壁纸：轮播最近 N 张地球图，返回每个显示器的帧数
+#[tauri::command]
+async fn start_live_wallpaper(app: AppHandle, config: LiveConfig) -> EarthResult<usize> {
+    let state = app.state::<LiveState>();
+    state.stop();
+    let cancel = CancelToken::default();
+    *state.cancel.lock().unwrap() = Some(cancel.clone());
+
+    let monitor_count = app.available_monitors()
+        .map_err(|e| EarthError::monitor(None, i18n::trf("获取显示器信息失败: {}", &[&e])))?
+        .len();
+    let monitor_indexes = match &config.monitor_indexes {
+        Some(idxs) if !idxs.is_empty() => idxs.iter().copied().filter(|&i| i < monitor_count).collect(),
+        _ => (0..monitor_count).collect(),
+    };
+    tauri::async_runtime::spawn_blocking(move || {
+        let base_path = image_base_dir(&app)?;
+        let framing = location_framing(&app);
+        live::start(&app, &base_path, config, &framing, monitor_indexes, cancel)
+    })
+    .await
+    .map_err(|e| EarthError::internal("动态壁纸任务异常退出", e))?
+}
+
+/// 停止动态壁纸，保留当前显示的那一帧
+#[tauri::command]
+fn stop_live_wallpaper(state: tauri::State<'_, LiveState>) {
+    state.stop();
+}
+
+/// 设置文件名，与前端 plugin-store 共用
+const SETTINGS_FILE: &str = "settings.json";
+const TRAY_ID: &str = "main";
+const DOCS_MENU_ID: &str = "Tauri Doc";
+
+/// 读取设置，旧版本的设置在这里迁移并写回；设置文件打不开时返回默认值
+fn load_settings(app: &AppHandle) -> Settings {
+    let Ok(store) = app.store(SETTINGS_FILE) else {
+        return Settings::default();
+    };
+    let (settings, migrated) = Settings::from_entries(store.entries().into_iter().collect());
+    if migrated && let Err(e) = write_settings(app, &settings) {
+        warn!(error = %e, "保存迁移后的设置失败");
+    }
+    settings
+}
+
+/// 把设置写入 settings.json，值为 null 的键删除，不认识的键保留
+fn write_settings(app: &AppHandle, settings: &Settings) -> EarthResult<()> {
+    let store = app
+        .store(SETTINGS_FILE)
+        .map_err(|e| EarthError::internal("读取设置失败", e))?;
+    for key in settings::RETIRED_KEYS {
+        store.delete(key);
+    }
+    for (key, value) in settings.to_entries()? {
+        if value.is_null() {
+            store.delete(&key);
+        } else {
+            store.set(key, value);
+        }
+    }
+    store.save().map_err(|e| EarthError::internal("保存设置失败", e))
+}
+
+/// 修改设置：校验并保存，同步到运行中的状态，并通知前端 settings-changed
+fn update_settings_with(app: &AppHandle, change: impl FnOnce(&mut Settings)) -> EarthResult<Settings> {
+    let previous = load_settings(app);
+    let mut settings = previous.clone();
+    change(&mut settings);
+    commit_settings(app, &previous, settings)
+}
+
+fn commit_settings(app: &AppHandle, previous: &Settings, settings: Settings) -> EarthResult<Settings> {
+    settings.validate()?;
+    write_settings(app, &settings)?;
+    apply_settings(app, Some(previous), &settings)?;
+    let _ = app.emit("settings-changed", &settings);
+    Ok(settings)
+}
+
+/// 把设置同步到下载、清理、定位和界面语言等运行中的状态，previous 为 None 表示启动时
+fn apply_settings(app: &AppHandle, previous: Option<&Settings>, settings: &Settings) -> tauri::Result<()> {
+    app.state::<DownloadState>().update(settings.download_config());
+    *app.state::<RetentionState>().policy.lock().unwrap() = settings.retention.clone();
+    if previous.is_some_and(|p| {
+        p.manual_location != settings.manual_location || p.use_system_location != settings.use_system_location
+    }) {
+        app.state::<LocationState>().invalidate();
+    }
+    if previous.is_none_or(|p| p.locale != settings.locale) {
+        // 未保存语言时跟随系统
+        let locale = settings.locale.unwrap_or_else(Locale::detect);
+        i18n::set(locale);
+        info!(locale = locale.tag(), "界面语言");
+        if previous.is_some() {
+            let _ = app.emit("locale-changed", locale);
+        }
+    }
+    if previous.is_some_and(|p| p.locale != settings.locale || p.auto_set_wallpaper != settings.auto_set_wallpaper) {
+        rebuild_menus(app)?;
+    }
+    Ok(())
+}
+
+/// 读取全部设置
+#[tauri::command]
+fn get_settings(app: AppHandle) -> Settings {
+    load_settings(&app)
+}
+
+/// 修改设置，patch 中只需包含要改的顶层字段；校验失败时不做任何修改，返回修改后的全部设置
+#[tauri::command]
+fn update_settings(app: AppHandle, patch: serde_json::Value) -> EarthResult<Settings> {
+    let previous = load_settings(&app);
+    let settings = previous.merged(patch)?;
+    let settings = commit_settings(&app, &previous, settings)?;
+    info!("设置已更新");
+    Ok(settings)
+}
+
+/// 托盘菜单
+fn build_tray_menu(app: &AppHandle) -> tauri::Result<Menu<tauri::Wry>> {
+    let update_i = MenuItem::with_id(app, "update_now", i18n::tr("立即更新壁纸"), true, None::<&str>)?;
+    let auto_i = CheckMenuItem::with_id(
+        app,
+        "toggle_auto",
+        i18n::tr("自动设置壁纸"),
+        true,
+        load_settings(app).auto_set_wallpaper,
+        None::<&str>,
+    )?;
+    let quit_i = MenuItem::with_id(app, "quit", i18n::tr("退出"), true, None::<&str>)?;
+    let show_i = MenuItem::with_id(app, "show", i18n::tr("显示窗口"), true, None::<&str>)?;
+    let menu = Menu::with_items(app, &[&show_i, &update_i, &auto_i])?;
+    // 有设置方案时加一个子菜单，勾选最近切换到的方案
+    let saved = image_base_dir(app)
+        .and_then(|base| Profiles::load(&base))
+        .inspect_err(|e| warn!(error = %e, "读取设置方案失败"))
+        .unwrap_or_default();
+    if !saved.profiles.is_empty() {
+        let mut submenu = SubmenuBuilder::with_id(app, "profiles", i18n::tr("设置方案"));
+        for name in saved.profiles.keys() {
+            let active = saved.active.as_deref() == Some(name.as_str());
+            let item = CheckMenuItem::with_id(app, format!("{}{}", PROFILE_MENU_PREFIX, name), name, true, active, None::<&str>)?;
+            submenu = submenu.item(&item);
+        }
+        menu.append(&submenu.build()?)?;
+    }
+    menu.append(&quit_i)?;
+    Ok(menu)
+}
+
+/// 托盘菜单中设置方案菜单项的 id 前缀，后接方案名
+const PROFILE_MENU_PREFIX: &str = "profile:";
+
+/// 不经过前端下载最新图像，并按设置中的显示器设置壁纸；托盘菜单和唤醒后的补充更新使用
+fn update_and_apply(app: &AppHandle, trickle: bool) -> EarthResult<String> {
+    let paths = fetch_earth_image(app, trickle)?;
+    let _ = app.emit("earth-image-updated", &paths);
+    let black_image = serde_json::from_str::<serde_json::Value>(&paths)
+        .ok()
+        .and_then(|value| value["black_image"].as_str().map(str::to_string))
+        .ok_or_else(|| EarthError::invalid("没有可用的地球图像"))?;
+    let monitor_indexes = load_settings(app).target_monitors();
+    apply_wallpaper_blocking(app, black_image, tauri_plugin_os::platform().to_string(), monitor_indexes)
+}
+
+/// 窗口菜单
+fn build_window_menu(app: &AppHandle) -> tauri::Result<Menu<tauri::Wry>> {
+    let cust_menu = MenuItemBuilder::with_id(DOCS_MENU_ID, i18n::tr("Tauri 文档")).build(app)?;
+    let main_menu = SubmenuBuilder::with_id(app, "main_menu", i18n::tr("主菜单"))
+        .items(&[&cust_menu])
+        .build()?;
+    MenuBuilder::new(app).items(&[&main_menu]).build()
+}
+
+/// 切换语言后重建托盘和窗口菜单
+fn rebuild_menus(app: &AppHandle) -> tauri::Result<()> {
+    if let Some(tray) = app.tray_by_id(TRAY_ID) {
+        tray.set_menu(Some(build_tray_menu(app)?))?;
+    }
+    app.set_menu(build_window_menu(app)?)?;
+    Ok(())
+}
+
+/// 获取当前界面语言
+#[tauri::command]
+fn get_locale() -> Locale {
+    i18n::current()
+}
+
+/// 切换界面语言并保存，传 null 表示跟随系统；菜单立即重建，并通知前端 locale-changed
+#[tauri::command]
+fn set_locale(app: AppHandle, locale: Option<Locale>) -> EarthResult<Locale> {
+    update_settings_with(&app, |settings| settings.locale = locale)?;
+    let locale = i18n::current();
+    info!(locale = locale.tag(), "界面语言已切换");
+    Ok(locale)
+}
+
+/// 当前天气缓存 10 分钟，预报缓存 1 小时
+const WEATHER_TTL_MINUTES: i64 = 10;
+const FORECAST_TTL_MINUTES: i64 = 60;
+
+fn weather_cache_file(app: &AppHandle) -> EarthResult<PathBuf> {
+    let dir = app
+        .path()
+        .app_cache_dir()
+        .map_err(|e| EarthError::internal("无法获取缓存目录", e))?;
+    Ok(dir.join(weather_cache::CACHE_FILE))
+}
+
+/// 缓存键：数据源、数据类型、界面语言（描述文字随语言变化）和地点
+fn weather_cache_key(provider: ProviderKind, what: &str, location: &WeatherLocation) -> String {
+    format!(
+        "{:?}:{}:{}:{}",
+        provider,
+        what,
+        i18n::current().tag(),
+        location.cache_key()
+    )
+}
+
+/// 前端没有指定地点时按当前位置的经纬度查询
+fn weather_location(app: &AppHandle, location: Option<WeatherLocation>) -> EarthResult<WeatherLocation> {
+    match location {
+        Some(location) => Ok(location),
+        None => current_location(app)
+            .map(|l| WeatherLocation::Coordinates {
+                latitude: l.latitude,
+                longitude: l.longitude,
+            })
+            .ok_or_else(|| EarthError::invalid("无法确定当前位置")),
+    }
+}
+
+/// 旧版本把 API Key 明文存在 settings.json，启动时移到系统钥匙串
+fn migrate_weather_keys(app: &AppHandle) {
+    let Ok(store) = app.store(SETTINGS_FILE) else {
+        return;
+    };
+    let mut migrated = false;
+    for (store_key, provider) in [
+        ("openweather_key", ProviderKind::OpenWeatherMap),
+        ("qweather_key", ProviderKind::QWeather),
+    ] {
+        let Some(value) = store.get(store_key) else {
+            continue;
+        };
+        let key = value.as_str().unwrap_or_default();
+        match secrets::set_api_key(provider, Some(key)) {
+            Ok(()) => {
+                store.delete(store_key);
+                migrated = true;
+                info!(provider = ?provider, "天气 API Key 已移入钥匙串");
             }
+            Err(e) => warn!(provider = ?provider, error = %e, "迁移天气 API Key 失败"),
         }
     }
+    if migrated && let Err(e) = store.save() {
+        warn!(error = %e, "保存设置失败");
+    }
+}
+
+/// 保存或清除数据源的 API Key，key 为空时清除
+#[tauri::command]
+fn set_weather_key(provider: ProviderKind, key: Option<String>) -> EarthResult<()> {
+    secrets::set_api_key(provider, key.as_deref())?;
+    info!(provider = ?provider, saved = key.is_some_and(|key| !key.trim().is_empty()), "天气 API Key 已更新");
     Ok(())
 }
 
-/// 通过 OpenWeather API 获取天气信息，key 由前端传递
+/// 是否已保存该数据源的 API Key，前端据此显示状态，不返回 key 本身
 #[tauri::command]
-fn get_weather(city: String, key: String) -> Result<String, String> {
-    // 直接用前端传来的 key
-    let url = format!(
-        "https://api.openweathermap.org/data/2.5/weather?q={}&appid={}&units=metric&lang=zh_cn",
-        city, key
-    );
-    let client = Client::new();
-    let resp = client.get(&url)
-        .send()
-        .map_err(|e| format!("请求OpenWeather失败: {}", e))?
-        .text()
-        .map_err(|e| format!("读取OpenWeather响应失败: {}", e))?;
-
-    #[derive(Deserialize)]
-    struct WeatherResp {
-        weather: Vec<WeatherDesc>,
-        main: WeatherMain,
-        name: String,
+fn has_weather_key(provider: ProviderKind) -> EarthResult<bool> {
+    Ok(secrets::api_key(provider)?.is_some())
+}
+
+/// 按设置解析当前位置：manual_location 优先，use_system_location 未关闭时尝试系统定位，最后按时区和系统地区推测
+fn current_location(app: &AppHandle) -> Option<ResolvedLocation> {
+    let settings = load_settings(app);
+    app.state::<LocationState>()
+        .resolve(settings.manual_location, settings.use_system_location)
+}
+
+/// 壁纸取景设置：frame_location 以所在位置为中心裁剪，show_location_marker 画位置标记，
+/// 地点标注设为画在壁纸上时一并带上
+fn location_framing(app: &AppHandle) -> Framing {
+    let settings = load_settings(app);
+    let (center, marker) = (settings.frame_location, settings.show_location_marker);
+    Framing {
+        location: (center || marker).then(|| current_location(app)).flatten(),
+        center,
+        marker,
+        places: place_markers(&settings).filter(|p| p.config.target == MarkerTarget::Wallpaper),
     }
-    #[derive(Deserialize)]
-    struct WeatherDesc {
-        description: String,
+}
+
+/// 解析当前位置，refresh 为 true 时忽略缓存重新定位；无法确定时返回 null
+#[tauri::command]
+fn resolve_location(app: AppHandle, refresh: Option<bool>) -> Option<ResolvedLocation> {
+    if refresh.unwrap_or(false) {
+        app.state::<LocationState>().invalidate();
     }
-    #[derive(Deserialize)]
-    struct WeatherMain {
-        temp: f32,
-        humidity: u8,
+    current_location(&app)
+}
+
+/// 保存或清除手动位置，location 为空时改回自动定位
+#[tauri::command]
+fn set_manual_location(app: AppHandle, location: Option<ManualLocation>) -> EarthResult<Option<ResolvedLocation>> {
+    update_settings_with(&app, |settings| settings.manual_location = location)?;
+    Ok(current_location(&app))
+}
+
+/// 按设置中的数据源获取当前天气，key 从钥匙串读取，Open-Meteo 不需要 key
+///
+/// location 为空时使用自动解析的当前位置。
+#[tauri::command]
+fn get_weather(
+    app: AppHandle,
+    state: tauri::State<'_, WeatherState>,
+    location: Option<WeatherLocation>,
+) -> EarthResult<CurrentWeather> {
+    current_weather(&app, &state, location)
+}
+
+fn current_weather(
+    app: &AppHandle,
+    state: &WeatherState,
+    location: Option<WeatherLocation>,
+) -> EarthResult<CurrentWeather> {
+    let location = weather_location(app, location)?;
+    let kind = load_settings(app).weather_provider;
+    let cache_key = weather_cache_key(kind, "current", &location);
+    let ttl = chrono::Duration::minutes(WEATHER_TTL_MINUTES);
+    state.cache.get_or_fetch(&weather_cache_file(app)?, &cache_key, ttl, || {
+        let key = secrets::api_key(kind)?;
+        let weather = kind.provider().current(state.client()?, &location, key.as_deref())?;
+        debug!(provider = ?weather.provider, city = %weather.city, "天气已更新");
+        Ok(weather)
+    })
+}
+
+/// 未来 24 小时和若干天的预报，days 默认 3，最多 MAX_FORECAST_DAYS
+#[tauri::command]
+fn get_weather_forecast(
+    app: AppHandle,
+    state: tauri::State<'_, WeatherState>,
+    location: Option<WeatherLocation>,
+    days: Option<u8>,
+) -> EarthResult<Forecast> {
+    let location = weather_location(&app, location)?;
+    let kind = load_settings(&app).weather_provider;
+    let days = days.unwrap_or(3).clamp(1, weather::MAX_FORECAST_DAYS);
+    let cache_key = weather_cache_key(kind, &format!("forecast{}", days), &location);
+    let ttl = chrono::Duration::minutes(FORECAST_TTL_MINUTES);
+    state.cache.get_or_fetch(&weather_cache_file(&app)?, &cache_key, ttl, || {
+        let key = secrets::api_key(kind)?;
+        let forecast = kind.provider().forecast(state.client()?, &location, key.as_deref(), days)?;
+        debug!(provider = ?forecast.provider, city = %forecast.city, days, "天气预报已更新");
+        Ok(forecast)
+    })
+}
+
+/// 准备文字叠加的内容，没有显示器开启时返回 None
+///
+/// 天气按设置中的 weather_location 查询（自动定位时为当前位置），走与界面相同的缓存；获取失败时不显示天气行。
+fn load_text_overlays(app: &AppHandle, image_path: &str) -> Option<TextOverlays> {
+    let settings = load_settings(app);
+    let configs = settings.text_overlays;
+    if !configs.values().any(|config| config.enabled) {
+        return None;
     }
+    let weather = configs
+        .values()
+        .any(|config| config.enabled && config.show_weather)
+        .then(|| {
+            current_weather(app, &app.state::<WeatherState>(), settings.weather_location)
+                .inspect_err(|e| warn!(error = %e, "获取叠加文字的天气失败"))
+                .ok()
+        })
+        .flatten()
+        .map(|weather| weather_format::summary(&weather, settings.weather_units));
+    let capture_time = image_base_dir(app).ok().and_then(|base| {
+        HistoryIndex::load(&base)
+            .captures
+            .iter()
+            .find(|c| c.black_image == image_path || c.merged_image == image_path)
+            .map(|c| c.timestamp)
+    });
+    Some(TextOverlays {
+        configs,
+        info: OverlayInfo {
+            weather,
+            local_time: chrono::Local::now(),
+            capture_time,
+            satellite: SATELLITE.to_string(),
+        },
+    })
+}
 
-    let weather: WeatherResp = serde_json::from_str(&resp)
-        .map_err(|e| format!("解析OpenWeather响应失败: {}", e))?;
-    if !weather.weather.is_empty() {
-        let desc = &weather.weather[0].description;
-        let temp = weather.main.temp;
-        let humidity = weather.main.humidity;
-        let city = &weather.name;
-        Ok(format!(
-            "{} {}°C 湿度{}% ({})",
-            desc, temp.round(), humidity, city
-        ))
-    } else {
-        Err("未获取到天气信息".to_string())
+#[tauri::command]
+fn get_map_overlay(app: AppHandle) -> MapOverlayConfig {
+    load_settings(&app).map_overlay
+}
+
+/// 保存经纬网和海岸线叠加设置，下次更新地球图像时生效
+#[tauri::command]
+fn set_map_overlay(app: AppHandle, config: MapOverlayConfig) -> EarthResult<()> {
+    update_settings_with(&app, |settings| settings.map_overlay = config)?;
+    Ok(())
+}
+
+/// 要绘制的地点标注，未开启或没有地点时返回 None
+fn place_markers(settings: &Settings) -> Option<PlaceMarkers> {
+    (settings.place_markers.enabled && !settings.places.is_empty()).then(|| PlaceMarkers {
+        config: settings.place_markers.clone(),
+        places: settings.places.clone(),
+    })
+}
+
+/// 标注的地点及其是否位于卫星可见的半球
+#[derive(Serialize)]
+struct PlaceInfo {
+    #[serde(flatten)]
+    place: Place,
+    visible: bool,
+}
+
+#[tauri::command]
+fn get_places(app: AppHandle) -> Vec<PlaceInfo> {
+    load_settings(&app)
+        .places
+        .into_iter()
+        .map(|place| PlaceInfo {
+            visible: place.visible(),
+            place,
+        })
+        .collect()
+}
+
+/// 新增或修改地点，id 为 0 时新增；返回保存后的地点
+#[tauri::command]
+fn save_place(app: AppHandle, place: Place) -> EarthResult<Place> {
+    place.validate()?;
+    let previous = load_settings(&app);
+    let mut settings = previous.clone();
+    let saved = places::upsert(&mut settings.places, place);
+    commit_settings(&app, &previous, settings)?;
+    info!(id = saved.id, visible = saved.visible(), "地点已保存");
+    Ok(saved)
+}
+
+#[tauri::command]
+fn remove_place(app: AppHandle, id: u32) -> EarthResult<()> {
+    update_settings_with(&app, |settings| settings.places.retain(|place| place.id != id))?;
+    Ok(())
+}
+
+#[tauri::command]
+fn get_place_markers(app: AppHandle) -> PlaceMarkerConfig {
+    load_settings(&app).place_markers
+}
+
+/// 保存地点标注的显示设置，画进大图的下次更新地球图像时生效，画在壁纸上的下次设置壁纸时生效
+#[tauri::command]
+fn set_place_markers(app: AppHandle, config: PlaceMarkerConfig) -> EarthResult<()> {
+    update_settings_with(&app, |settings| settings.place_markers = config)?;
+    Ok(())
+}
+
+/// 导出全部设置，path 为空时放在图片目录的 exports 子目录；format 为空时按扩展名选择，默认 JSON
+///
+/// 天气 API Key 在钥匙串中，不会被导出。返回导出文件的路径。
+#[tauri::command]
+fn export_settings(app: AppHandle, path: Option<String>, format: Option<FileFormat>) -> EarthResult<String> {
+    let path = match path {
+        Some(path) => PathBuf::from(path),
+        None => {
+            let dir = image_base_dir(&app)?.join("exports");
+            fs::create_dir_all(&dir).map_err(|e| EarthError::io(&dir, "创建导出目录失败", e))?;
+            let ext = format.unwrap_or(FileFormat::Json).extension();
+            dir.join(format!("settings_{}.{}", Utc::now().format("%Y%m%d_%H%M%S"), ext))
+        }
+    };
+    let format = format.unwrap_or_else(|| FileFormat::from_path(&path));
+    let body = profiles::export(&load_settings(&app), format, Utc::now())?;
+    atomic::write_bytes(&path, body, "导出设置失败")?;
+    info!(path = %path.display(), "设置已导出");
+    Ok(path.to_string_lossy().into_owned())
+}
+
+/// 校验要导入的设置文件，列出与当前设置不同的字段，不做修改
+#[tauri::command]
+fn preview_settings_import(app: AppHandle, path: String) -> EarthResult<Vec<SettingChange>> {
+    let incoming = profiles::read(Path::new(&path))?;
+    profiles::diff(&load_settings(&app), &incoming)
+}
+
+/// 导入设置文件，替换当前的全部设置；文件中没有的字段恢复为默认值
+#[tauri::command]
+fn import_settings(app: AppHandle, path: String) -> EarthResult<Settings> {
+    let incoming = profiles::read(Path::new(&path))?;
+    let previous = load_settings(&app);
+    let settings = commit_settings(&app, &previous, incoming)?;
+    info!(path = %path, "设置已导入");
+    Ok(settings)
+}
+
+/// 方案名列表和最近切换到的方案
+#[derive(Serialize)]
+struct ProfileList {
+    names: Vec<String>,
+    active: Option<String>,
+}
+
+#[tauri::command]
+fn list_profiles(app: AppHandle) -> EarthResult<ProfileList> {
+    let saved = Profiles::load(&image_base_dir(&app)?)?;
+    Ok(ProfileList {
+        names: saved.profiles.keys().cloned().collect(),
+        active: saved.active,
+    })
+}
+
+/// 把当前设置保存为方案，同名方案直接覆盖，返回保存的方案名
+#[tauri::command]
+fn save_profile(app: AppHandle, name: String) -> EarthResult<String> {
+    let base = image_base_dir(&app)?;
+    let mut saved = Profiles::load(&base)?;
+    let name = saved.insert(&name, load_settings(&app))?;
+    saved.active = Some(name.clone());
+    saved.save(&base)?;
+    rebuild_menus(&app)?;
+    info!(profile = %name, "设置方案已保存");
+    Ok(name)
+}
+
+#[tauri::command]
+fn delete_profile(app: AppHandle, name: String) -> EarthResult<()> {
+    let base = image_base_dir(&app)?;
+    let mut saved = Profiles::load(&base)?;
+    saved.remove(&name);
+    saved.save(&base)?;
+    rebuild_menus(&app)?;
+    Ok(())
+}
+
+/// 列出切换到该方案会改动的设置
+#[tauri::command]
+fn preview_profile(app: AppHandle, name: String) -> EarthResult<Vec<SettingChange>> {
+    let saved = Profiles::load(&image_base_dir(&app)?)?;
+    profiles::diff(&load_settings(&app), saved.get(&name)?)
+}
+
+/// 用方案替换当前设置，托盘菜单也会调用
+fn switch_to_profile(app: &AppHandle, name: &str) -> EarthResult<Settings> {
+    let base = image_base_dir(app)?;
+    let mut saved = Profiles::load(&base)?;
+    let incoming = saved.get(name)?.clone();
+    let previous = load_settings(app);
+    let settings = commit_settings(app, &previous, incoming)?;
+    saved.active = Some(name.to_string());
+    saved.save(&base)?;
+    rebuild_menus(app)?;
+    info!(profile = %name, "已切换设置方案");
+    Ok(settings)
+}
+
+#[tauri::command]
+fn switch_profile(app: AppHandle, name: String) -> EarthResult<Settings> {
+    switch_to_profile(&app, &name)
+}
+
+/// 读取各显示器的文字叠加设置，未设置的显示器不在结果中
+#[tauri::command]
+fn get_text_overlays(app: AppHandle) -> HashMap<usize, TextOverlayConfig> {
+    load_settings(&app).text_overlays
+}
+
+/// 保存某个显示器的文字叠加设置，下次设置壁纸时生效
+#[tauri::command]
+fn set_text_overlay(app: AppHandle, monitor_index: usize, config: TextOverlayConfig) -> EarthResult<()> {
+    update_settings_with(&app, |settings| {
+        settings.text_overlays.insert(monitor_index, config);
+    })?;
+    Ok(())
+}
+
+/// 格式化后的天气文字
+#[derive(Serialize)]
+struct WeatherText {
+    summary: String,
+    details: String,
+}
+
+/// 按单位格式化天气，units 为空时使用设置中的 weather_units
+#[tauri::command]
+fn format_weather(app: AppHandle, weather: CurrentWeather, units: Option<Units>) -> WeatherText {
+    let units = units.unwrap_or_else(|| load_settings(&app).weather_units);
+    WeatherText {
+        summary: weather_format::summary(&weather, units),
+        details: weather_format::details(&weather, units),
     }
 }
 
 // 新增：获取所有显示器信息的命令
 #[tauri::command]
-fn get_all_monitors(app: AppHandle) -> Result<String, String> {
+fn get_all_monitors(app: AppHandle) -> EarthResult<String> {
     let monitors = app.available_monitors()
-        .map_err(|e| format!("获取显示器信息失败: {}", e))?;
+        .map_err(|e| EarthError::monitor(None, i18n::trf("获取显示器信息失败: {}", &[&e])))?;
     let primary = app.primary_monitor()
-        .map_err(|e| format!("获取主屏幕失败: {}", e))?;
+        .map_err(|e| EarthError::monitor(None, i18n::trf("获取主屏幕失败: {}", &[&e])))?;
     #[derive(Serialize)]
     struct MonitorInfo {
         name: String,
@@ -280,29 +1165,75 @@ fn get_all_monitors(app: AppHandle) -> Result<String, String> {
         }
     }).collect();
     serde_json::to_string(&monitor_infos)
-        .map_err(|e| format!("序列化显示器信息失败: {}", e))
+        .map_err(|e| EarthError::internal("序列化显示器信息失败", e))
 }
 
 // 修改：为所有显示器设置壁纸
+///
+/// platform 为空时按当前系统；monitor_indexes 为空时使用设置中的显示器。
 #[tauri::command]
 async fn set_wallpaper_for_all_monitors(
     app: AppHandle,
     image_path: String,
+    platform: Option<String>,
+    monitor_indexes: Option<Vec<usize>>,
+) -> EarthResult<String> {
+    let platform = platform.unwrap_or_else(|| tauri_plugin_os::platform().to_string());
+    tauri::async_runtime::spawn_blocking(move || {
+        let monitor_indexes = monitor_indexes.or_else(|| load_settings(&app).target_monitors());
+        apply_wallpaper_blocking(&app, image_path, platform, monitor_indexes)
+    })
+    .await
+    .map_err(|e| EarthError::internal("设置壁纸任务异常退出", e))?
+}
+
+/// 在当前线程按显示器设置壁纸，与其他设置壁纸流程排队执行
+fn apply_wallpaper_blocking(
+    app: &AppHandle,
+    image_path: String,
     platform: String,
     monitor_indexes: Option<Vec<usize>>,
-) -> Result<String, String> {
+) -> EarthResult<String> {
+    let key = format!("apply:{}:{:?}", image_path, monitor_indexes);
+    app.state::<Pipeline>().run(&key, |run_id, cancel| {
+        let reporter = ProgressReporter::new(app, run_id, "apply");
+        // 天气请求用的是阻塞客户端，要在 block_on 之外完成
+        let text = load_text_overlays(app, &image_path);
+        let result = tauri::async_runtime::block_on(apply_wallpaper(
+            app,
+            &image_path,
+            &platform,
+            monitor_indexes,
+            text.as_ref(),
+            cancel,
+            &reporter,
+        ));
+        reporter.finish(&result);
+        result
+    })
+}
+
+/// 按显示器裁剪并设置壁纸，记录当前壁纸，返回裁剪后的图片路径
+async fn apply_wallpaper(
+    app: &AppHandle,
+    image_path: &str,
+    platform: &str,
+    monitor_indexes: Option<Vec<usize>>,
+    text: Option<&TextOverlays>,
+    cancel: &CancelToken,
+    reporter: &ProgressReporter,
+) -> EarthResult<String> {
     let monitors = app.available_monitors()
-        .map_err(|e| format!("获取显示器信息失败: {}", e))?;
-    // 打印所有屏幕详细信息
-    println!("【所有屏幕信息】");
+        .map_err(|e| EarthError::monitor(None, i18n::trf("获取显示器信息失败: {}", &[&e])))?;
+    // 记录所有屏幕详细信息
     for (idx, m) in monitors.iter().enumerate() {
-        println!(
-            "屏幕{}: name={:?}, position=({},{}) size=({}x{}) scale_factor={}",
-            idx,
-            m.name(),
-            m.position().x, m.position().y,
-            m.size().width, m.size().height,
-            m.scale_factor()
+        debug!(
+            index = idx,
+            name = ?m.name(),
+            position = ?(m.position().x, m.position().y),
+            size = ?(m.size().width, m.size().height),
+            scale_factor = m.scale_factor(),
+            "屏幕信息"
         );
     }
     // 处理要设置的屏幕索引
@@ -315,20 +1246,45 @@ async fn set_wallpaper_for_all_monitors(
     } else {
         (0..monitors.len()).collect()
     };
+    let base_path = image_base_dir(app)?;
+    let mut active = ActiveWallpapers::load(&base_path);
+    let framing = location_framing(app);
     let mut results = Vec::new();
-    for &index in &target_indexes {
+    let total = target_indexes.len();
+    // GNOME 所有显示器共用一个 picture-uri，设置多个显示器时渐变会互相覆盖，只在单个显示器时渐变
+    let fade = total == 1;
+    for (done, &index) in target_indexes.iter().enumerate() {
+        cancel.check()?;
         if let Some(monitor) = monitors.get(index) {
-            println!("【设置壁纸】正在处理显示器 {}: {}x{}", 
-                index, monitor.size().width, monitor.size().height);
-            let cropped_path = wallpaper::crop_image_for_monitor(&app, &image_path, index).await?;
-            wallpaper::set_wallpaper_for_monitor(cropped_path.clone(), platform.clone(), index).await?;
+            let span = info_span!("monitor", index, width = monitor.size().width, height = monitor.size().height);
+            let cropped_path = async {
+                info!("正在设置壁纸");
+                reporter.stage(UpdateStage::Cropping { monitor: index, done, total });
+                let cropped_path = wallpaper::crop_image_for_monitor(app, image_path, index, &framing, text).await?;
+                reporter.stage(UpdateStage::Applying { monitor: index, done, total });
+                wallpaper::set_wallpaper_for_monitor(cropped_path.clone(), platform.to_string(), index, fade).await?;
+                Ok::<_, EarthError>(cropped_path)
+            }
+            .instrument(span)
+            .await?;
+            // 记录当前壁纸，清理旧图片时跳过
+            let previous = active.monitors.insert(index, ActiveWallpaper {
+                wallpaper: cropped_path.clone(),
+                source: image_path.to_string(),
+            });
+            active.save(&base_path)?;
+            // 新壁纸已设置成功，再删除更早的壁纸文件
+            wallpaper::remove_stale_wallpapers(
+                Path::new(&cropped_path),
+                previous.as_ref().map(|p| Path::new(&p.wallpaper)),
+            );
             results.push(cropped_path);
         } else {
-            println!("警告：索引{}的屏幕不存在，跳过", index);
+            warn!(index, "屏幕不存在，跳过");
         }
     }
     Ok(serde_json::to_string(&results)
-        .map_err(|e| format!("序列化结果失败: {}", e))?)
+        .map_err(|e| EarthError::internal("序列化结果失败", e))?)
 }
 
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
@@ -339,12 +1295,32 @@ pub fn run() {
         .plugin(tauri_plugin_fs::init())
         .plugin(tauri_plugin_opener::init())
         .plugin(tauri_plugin_store::Builder::default().build())
+        .manage(DownloadState::default())
+        .manage(RetentionState::default())
+        .manage(TimelapseState::default())
+        .manage(LiveState::default())
+        .manage(Pipeline::default())
+        .manage(WeatherState::default())
+        .manage(LocationState::default())
         .setup(|app| {
+            if let Ok(log_dir) = app.path().app_log_dir() {
+                logging::init(&log_dir);
+            }
+            let settings = load_settings(app.handle());
+            apply_settings(app.handle(), None, &settings)?;
+            migrate_weather_keys(app.handle());
+
+            // 清理上次崩溃时写到一半的临时文件
+            if let Ok(base) = image_base_dir(app.handle()) {
+                let removed = atomic::remove_stale_temp_files(&base);
+                if removed > 0 {
+                    info!(removed, "已清理残留临时文件");
+                }
+            }
+
             // 系统托盘
-            let quit_i = MenuItem::with_id(app, "quit", "退出", true, None::<&str>)?;
-            let show_i = MenuItem::with_id(app, "show", "显示窗口", true, None::<&str>)?;
-            let menu = Menu::with_items(app, &[&show_i, &quit_i])?;
-            let _tray = TrayIconBuilder::new()
+            let menu = build_tray_menu(app.handle())?;
+            let _tray = TrayIconBuilder::with_id(TRAY_ID)
                 .icon(app.default_window_icon().unwrap().clone())
                 .menu(&menu)
                 .on_menu_event(|app, event| match event.id.as_ref() {
@@ -354,12 +1330,31 @@ pub fn run() {
                             let _ = window.set_focus();
                         }
                     }
+                    "update_now" => {
+                        let app = app.clone();
+                        tauri::async_runtime::spawn_blocking(move || {
+                            if let Err(e) = update_and_apply(&app, true) {
+                                error!(error = %e, "托盘更新壁纸失败");
+                            }
+                        });
+                    }
+                    "toggle_auto" => {
+                        let enabled = !load_settings(app).auto_set_wallpaper;
+                        if let Err(e) = update_settings_with(app, |settings| settings.auto_set_wallpaper = enabled) {
+                            warn!(error = %e, "切换自动设置壁纸失败");
+                        }
+                    }
+                    id if id.starts_with(PROFILE_MENU_PREFIX) => {
+                        if let Err(e) = switch_to_profile(app, &id[PROFILE_MENU_PREFIX.len()..]) {
+                            warn!(error = %e, "切换设置方案失败");
+                        }
+                    }
                     "quit" => {
-                        println!("quit menu item was clicked");
+                        info!("quit menu item was clicked");
                         app.exit(0);
                     }
                     _ => {
-                        println!("menu item {:?} not handled", event.id);
+                        debug!(id = ?event.id, "menu item not handled");
                     }
                 })
                 .build(app)?;
@@ -376,7 +1371,7 @@ pub fn run() {
                     }
                     "quit" => app.exit(0),
                     _ => {
-                        println!("menu item {:?} not handled", event.id);
+                        debug!(id = ?event.id, "menu item not handled");
                     }
                 }
             });
@@ -397,23 +1392,32 @@ pub fn run() {
             });
 
 
-            // 窗口菜单
-            let cust_menu = MenuItemBuilder::with_id("Tauri Doc", "Tauri Doc").build(app)?;
-            let main_menu = SubmenuBuilder::with_id(app, "main_menu", "主菜单")
-                .items(&[&cust_menu])
-                .build()?;
-            let menu = MenuBuilder::new(app)
-                .items(&[&main_menu])
-                .build()?;
-            app.set_menu(menu)?;
+            // 窗口菜单，切换语言时会重建，按 id 匹配
+            app.set_menu(build_window_menu(app.handle())?)?;
             app.on_menu_event(move |app, event| {
-                if event.id() == cust_menu.id() {
+                if event.id() == DOCS_MENU_ID {
                     let _ = app.opener().open_url("https://github.com/tauri-apps/tauri", None::<&str>);
                 }
             });
 
 
 
+            // 监听系统睡眠唤醒，唤醒后补充更新一次地球图像
+            // 开启自动设置壁纸时直接在后端设置，窗口关闭到托盘时也能更新
+            // 补充更新不走涓流下载，睡眠期间已经错过了发布周期
+            watchdog::spawn(app.handle().clone(), |app| {
+                let result = if load_settings(app).auto_set_wallpaper {
+                    update_and_apply(app, false)
+                } else {
+                    fetch_earth_image(app, false).inspect(|paths| {
+                        let _ = app.emit("earth-image-updated", paths);
+                    })
+                };
+                if let Err(e) = result {
+                    error!(error = %e, "补充更新失败");
+                }
+            });
+
             #[cfg(debug_assertions)] // 仅在开发模式下打开 devtools
             {
                 let window = app.get_webview_window("main").unwrap();
@@ -424,9 +1428,52 @@ pub fn run() {
         .invoke_handler(tauri::generate_handler![
             greet,
             update_earth_image,
+            cancel_update,
+            get_current_run,
             get_image_dir,
+            get_active_wallpapers,
+            get_recent_logs,
+            get_download_config,
+            set_download_config,
             clean_old_images,
+            preview_clean_old_images,
+            get_retention_policy,
+            set_retention_policy,
+            list_captures,
+            apply_capture,
+            export_timelapse,
+            export_video,
+            cancel_timelapse,
+            start_live_wallpaper,
+            stop_live_wallpaper,
+            get_settings,
+            update_settings,
+            export_settings,
+            preview_settings_import,
+            import_settings,
+            list_profiles,
+            save_profile,
+            delete_profile,
+            preview_profile,
+            switch_profile,
+            get_locale,
+            set_locale,
             get_weather,
+            get_weather_forecast,
+            set_weather_key,
+            has_weather_key,
+            resolve_location,
+            set_manual_location,
+            get_text_overlays,
+            set_text_overlay,
+            get_map_overlay,
+            set_map_overlay,
+            get_places,
+            save_place,
+            remove_place,
+            get_place_markers,
+            set_place_markers,
+            format_weather,
             get_all_monitors,
             set_wallpaper_for_all_monitors
         ])
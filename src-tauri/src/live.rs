@@ -0,0 +1,231 @@
+use image::RgbaImage;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::thread;
+use std::time::Duration;
+use tauri::AppHandle;
+use tracing::{info, warn};
+
+use crate::atomic;
+use crate::download::CancelToken;
+use crate::error::{EarthError, EarthResult};
+use crate::history::HistoryIndex;
+use crate::location::Framing;
+use crate::wallpaper;
+
+/// 过渡帧每帧停留的时间
+const TRANSITION_FRAME: Duration = Duration::from_secs(1);
+
+/// 动态壁纸配置
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LiveConfig {
+    /// 轮播最近多少张地球图
+    pub frames: usize,
+    /// 每张图停留的秒数
+    pub interval_secs: u64,
+    /// 相邻两张图之间插入的渐变过渡帧数，0 表示直接切换
+    pub crossfade_steps: u32,
+    pub platform: String,
+    pub monitor_indexes: Option<Vec<usize>>,
+}
+
+/// 正在运行的动态壁纸的取消标记
+#[derive(Default)]
+pub struct LiveState {
+    pub cancel: Mutex<Option<CancelToken>>,
+}
+
+impl LiveState {
+    /// 停止正在运行的动态壁纸
+    pub fn stop(&self) {
+        if let Some(cancel) = self.cancel.lock().unwrap().take() {
+            cancel.cancel();
+        }
+    }
+}
+
+/// 某个显示器预渲染好的帧，按播放顺序排列
+pub struct MonitorFrames {
+    pub monitor_index: usize,
+    pub dir: PathBuf,
+    /// (路径, 是否为过渡帧)
+    pub frames: Vec<(PathBuf, bool)>,
+}
+
+/// 两张同尺寸图片按比例 t 线性混合
+pub fn blend(a: &RgbaImage, b: &RgbaImage, t: f32) -> RgbaImage {
+    // 按 1/256 的定点权重逐字节混合，比逐像素取值快得多
+    let w = (t.clamp(0.0, 1.0) * 256.0).round() as u32;
+    let data = a
+        .as_raw()
+        .iter()
+        .zip(b.as_raw())
+        .map(|(&pa, &pb)| ((pa as u32 * (256 - w) + pb as u32 * w + 128) >> 8) as u8)
+        .collect();
+    RgbaImage::from_raw(a.width(), a.height(), data).unwrap_or_else(|| a.clone())
+}
+
+/// 生成 from 到 to 之间的过渡帧并写入磁盘，尺寸不同时不生成
+fn write_transitions(
+    dir: &Path,
+    k: usize,
+    from: &RgbaImage,
+    to: &RgbaImage,
+    steps: u32,
+    frames: &mut Vec<(PathBuf, bool)>,
+) -> EarthResult<()> {
+    if from.dimensions() != to.dimensions() {
+        return Ok(());
+    }
+    for s in 1..=steps {
+        let t = s as f32 / (steps + 1) as f32;
+        let path = dir.join(format!("frame_{:03}_{:02}.png", k, s));
+        let frame = blend(from, to, t);
+        atomic::save_image(&path, "保存过渡帧失败", |tmp, format| {
+            frame.save_with_format(tmp, format)
+        })?;
+        frames.push((path, true));
+    }
+    Ok(())
+}
+
+/// 为每个显示器裁剪最近 N 张图，并按需生成循环播放所需的渐变过渡帧
+///
+/// 帧文件名按播放顺序排序，桌面自带的幻灯片按文件名排序即可正确播放。
+/// 4K 下每帧约 33 MB，关键帧逐张渲染后立即写盘，内存中只保留相邻的两张。
+pub fn prerender(
+    app: &AppHandle,
+    base: &Path,
+    config: &LiveConfig,
+    framing: &Framing,
+    monitor_indexes: &[usize],
+    crossfade: bool,
+) -> EarthResult<Vec<MonitorFrames>> {
+    let mut index = HistoryIndex::load(base);
+    index.prune_missing();
+    let sources: Vec<String> = index
+        .captures
+        .iter()
+        .rev()
+        .take(config.frames.max(1))
+        .rev()
+        .filter_map(|c| match crate::ensure_black_image(app, c) {
+            Ok(()) => Some(c.black_image.clone()),
+            Err(e) => {
+                warn!(id = %c.id, error = %e, "黑边图缺失且无法重新生成，跳过");
+                None
+            }
+        })
+        .collect();
+    if sources.len() < 2 {
+        return Err(EarthError::invalid("历史图片不足两张，无法生成动态壁纸"));
+    }
+
+    let steps = if crossfade { config.crossfade_steps } else { 0 };
+    let mut result = Vec::new();
+    for &monitor_index in monitor_indexes {
+        let dir = wallpaper::create_monitor_wallpaper_dir(app, monitor_index)?.join("live");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).map_err(|e| EarthError::io(&dir, "创建动态壁纸目录失败", e))?;
+
+        let mut frames = Vec::new();
+        let mut previous: Option<RgbaImage> = None;
+        for (k, source) in sources.iter().enumerate() {
+            let (img, _, _) = wallpaper::render_for_monitor(app, source, monitor_index, framing)?;
+            let key = img.to_rgba8();
+            drop(img);
+            if let Some(previous) = previous.take() {
+                write_transitions(&dir, k - 1, &previous, &key, steps, &mut frames)?;
+            }
+            let path = dir.join(format!("frame_{:03}_00.png", k));
+            atomic::save_image(&path, "保存动态壁纸帧失败", |tmp, format| {
+                key.save_with_format(tmp, format)
+            })?;
+            frames.push((path, false));
+            previous = Some(key);
+        }
+        // 最后一张向第一张过渡，形成循环；第一张从磁盘读回，不常驻内存
+        if let Some(last) = previous.take()
+            && steps > 0
+        {
+            let first_path = &frames[0].0;
+            let first = image::open(first_path)
+                .map_err(|e| EarthError::image(first_path, "读取动态壁纸帧失败", e))?
+                .to_rgba8();
+            write_transitions(&dir, sources.len() - 1, &last, &first, steps, &mut frames)?;
+        }
+        info!(monitor = monitor_index, frames = frames.len(), "动态壁纸帧已生成");
+        result.push(MonitorFrames {
+            monitor_index,
+            dir,
+            frames,
+        });
+    }
+    Ok(result)
+}
+
+/// 启动动态壁纸：支持幻灯片的桌面交给桌面播放，其余由后台线程定时切换
+pub fn start(
+    app: &AppHandle,
+    base: &Path,
+    config: LiveConfig,
+    framing: &Framing,
+    monitor_indexes: Vec<usize>,
+    cancel: CancelToken,
+) -> EarthResult<usize> {
+    // 先试探桌面是否支持原生幻灯片，支持时不需要生成过渡帧
+    let native = wallpaper::supports_native_slideshow(&config.platform);
+    let rendered = prerender(app, base, &config, framing, &monitor_indexes, !native)?;
+
+    if native {
+        for monitor in &rendered {
+            wallpaper::set_slideshow_for_monitor(&monitor.dir, config.interval_secs, monitor.monitor_index)?;
+        }
+        return Ok(rendered.first().map_or(0, |m| m.frames.len()));
+    }
+
+    let frame_count = rendered.first().map_or(0, |m| m.frames.len());
+    let hold = Duration::from_secs(config.interval_secs.max(1));
+    let platform = config.platform.clone();
+    thread::spawn(move || {
+        let mut f = 0;
+        while !cancel.is_cancelled() && frame_count > 0 {
+            let mut transition = false;
+            for monitor in &rendered {
+                if let Some((path, is_transition)) = monitor.frames.get(f % monitor.frames.len()) {
+                    transition = *is_transition;
+                    let path = path.to_string_lossy().into_owned();
+                    if let Err(e) = tauri::async_runtime::block_on(wallpaper::set_wallpaper_for_monitor(
+                        path,
+                        platform.clone(),
+                        monitor.monitor_index,
+                        false,
+                    )) {
+                        warn!(monitor = monitor.monitor_index, error = %e, "动态壁纸切换失败");
+                    }
+                }
+            }
+            cancel.sleep(if transition { TRANSITION_FRAME } else { hold });
+            f = (f + 1) % frame_count;
+        }
+        info!("动态壁纸已停止");
+    });
+    Ok(frame_count)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::Rgba;
+
+    #[test]
+    fn blend_interpolates_each_channel() {
+        let a = RgbaImage::from_pixel(2, 1, Rgba([0, 100, 200, 255]));
+        let b = RgbaImage::from_pixel(2, 1, Rgba([255, 100, 0, 255]));
+        assert_eq!(blend(&a, &b, 0.0), a);
+        assert_eq!(blend(&a, &b, 1.0), b);
+        assert_eq!(blend(&a, &b, 0.5).get_pixel(1, 0), &Rgba([128, 100, 100, 255]));
+    }
+}
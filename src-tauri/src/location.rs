@@ -0,0 +1,478 @@
+use serde::{Deserialize, Serialize};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tracing::debug;
+
+use crate::error::{EarthError, EarthResult};
+use crate::places::PlaceMarkers;
+
+/// 系统定位和时区推测的结果缓存一小时，手动位置不缓存
+const CACHE_TTL: Duration = Duration::from_secs(60 * 60);
+
+/// 位置来源，按解析优先级排列
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LocationSource {
+    /// 设置中手动填写的经纬度
+    Manual,
+    /// 操作系统的定位服务
+    System,
+    /// 按系统时区推测，只精确到时区所在城市
+    Timezone,
+    /// 按系统地区设置推测，只精确到国家或地区的首都
+    Region,
+}
+
+/// 手动设置的位置，保存在 settings.json 的 manual_location 字段
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct ManualLocation {
+    pub latitude: f64,
+    pub longitude: f64,
+}
+
+impl ManualLocation {
+    pub fn validate(&self) -> EarthResult<()> {
+        if !(-90.0..=90.0).contains(&self.latitude) || !(-180.0..=180.0).contains(&self.longitude) {
+            return Err(EarthError::invalid("经纬度超出范围"));
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ResolvedLocation {
+    pub latitude: f64,
+    pub longitude: f64,
+    pub source: LocationSource,
+    /// 按时区或地区推测时为推测的城市名，其余来源为空
+    pub name: Option<String>,
+}
+
+/// 壁纸按位置取景和标注的设置
+#[derive(Debug, Clone, Default)]
+pub struct Framing {
+    pub location: Option<ResolvedLocation>,
+    /// 裁剪窗口以所在位置为中心
+    pub center: bool,
+    /// 在所在位置画「你在这里」标记
+    pub marker: bool,
+    /// 要画在壁纸上的地点标注
+    pub places: Option<PlaceMarkers>,
+}
+
+/// 自动解析的位置缓存
+#[derive(Default)]
+pub struct LocationState {
+    cached: Mutex<Option<(Instant, ResolvedLocation)>>,
+}
+
+impl LocationState {
+    /// 依次尝试手动位置、系统定位（use_system 为 true 时）、时区推测和地区推测，都失败时返回 None
+    pub fn resolve(&self, manual: Option<ManualLocation>, use_system: bool) -> Option<ResolvedLocation> {
+        self.resolve_with(manual, use_system, system_location, offline_guess)
+    }
+
+    fn resolve_with(
+        &self,
+        manual: Option<ManualLocation>,
+        use_system: bool,
+        system: impl FnOnce() -> Option<(f64, f64)>,
+        guess: impl FnOnce() -> Option<ResolvedLocation>,
+    ) -> Option<ResolvedLocation> {
+        if let Some(manual) = manual.filter(|m| m.validate().is_ok()) {
+            return Some(ResolvedLocation {
+                latitude: manual.latitude,
+                longitude: manual.longitude,
+                source: LocationSource::Manual,
+                name: None,
+            });
+        }
+
+        if let Some((at, location)) = self.cached.lock().unwrap().as_ref()
+            && at.elapsed() < CACHE_TTL
+            && (use_system || location.source != LocationSource::System)
+        {
+            return Some(location.clone());
+        }
+        // 系统定位最多等 10 秒，期间不持有锁；并发的调用可能各定位一次，结果相同
+        let resolved = use_system
+            .then(system)
+            .flatten()
+            .map(|(latitude, longitude)| ResolvedLocation {
+                latitude,
+                longitude,
+                source: LocationSource::System,
+                name: None,
+            })
+            .or_else(guess);
+        debug!(location = ?resolved, "已解析当前位置");
+        *self.cached.lock().unwrap() = resolved.clone().map(|location| (Instant::now(), location));
+        resolved
+    }
+
+    /// 设置改变或用户要求重新定位时丢弃缓存
+    pub fn invalidate(&self) {
+        *self.cached.lock().unwrap() = None;
+    }
+}
+
+/// Windows 定位服务，用户在系统设置里关闭定位时返回 None
+#[cfg(target_os = "windows")]
+fn system_location() -> Option<(f64, f64)> {
+    use windows::Devices::Geolocation::{GeolocationAccessStatus, Geolocator};
+    use windows::Foundation::TimeSpan;
+
+    let locate = || -> windows::core::Result<Option<(f64, f64)>> {
+        if Geolocator::RequestAccessAsync()?.get()? != GeolocationAccessStatus::Allowed {
+            return Ok(None);
+        }
+        // 接受一小时内的缓存位置，最多等 10 秒
+        let position = Geolocator::new()?
+            .GetGeopositionAsyncWithAgeAndTimeout(
+                TimeSpan::from(CACHE_TTL),
+                TimeSpan::from(Duration::from_secs(10)),
+            )?
+            .get()?;
+        let point = position.Coordinate()?.Point()?.Position()?;
+        Ok(Some((point.Latitude, point.Longitude)))
+    };
+    locate().unwrap_or_else(|e| {
+        debug!(error = %e, "系统定位失败");
+        None
+    })
+}
+
+/// macOS 的 CoreLocation 需要签名和授权弹窗，Linux 的 GeoClue 需要桌面会话配合，暂不支持
+#[cfg(not(target_os = "windows"))]
+fn system_location() -> Option<(f64, f64)> {
+    None
+}
+
+fn system_timezone() -> Option<String> {
+    iana_time_zone::get_timezone()
+        .inspect_err(|e| debug!(error = %e, "读取系统时区失败"))
+        .ok()
+}
+
+/// 不联网的位置推测：先按系统时区，时区不在表中时按系统地区设置
+fn offline_guess() -> Option<ResolvedLocation> {
+    system_timezone()
+        .and_then(|tz| timezone_guess(&tz))
+        .or_else(|| tauri_plugin_os::locale().and_then(|locale| region_guess(&locale)))
+}
+
+/// 按时区推测位置，取时区代表城市的坐标，不在表中的时区返回 None
+pub fn timezone_guess(timezone: &str) -> Option<ResolvedLocation> {
+    let &(_, latitude, longitude) = TIMEZONES.iter().find(|(tz, _, _)| *tz == timezone)?;
+    let name = timezone.rsplit('/').next().unwrap_or(timezone).replace('_', " ");
+    Some(ResolvedLocation {
+        latitude,
+        longitude,
+        source: LocationSource::Timezone,
+        name: Some(name),
+    })
+}
+
+/// 按 zh-CN、en_US.UTF-8、zh-Hans-TW 这样的地区设置推测位置，取国家或地区首都的坐标
+pub fn region_guess(locale: &str) -> Option<ResolvedLocation> {
+    let tag = locale.split(['.', '@']).next().unwrap_or(locale);
+    let region = tag
+        .split(['-', '_'])
+        .skip(1)
+        .find(|part| part.len() == 2 && part.chars().all(|c| c.is_ascii_alphabetic()))?
+        .to_ascii_uppercase();
+    let &(_, latitude, longitude, name) = REGIONS.iter().find(|(code, _, _, _)| *code == region)?;
+    Some(ResolvedLocation {
+        latitude,
+        longitude,
+        source: LocationSource::Region,
+        name: Some(name.to_string()),
+    })
+}
+
+/// 常见 IANA 时区及其代表城市的纬度、经度
+const TIMEZONES: &[(&str, f64, f64)] = &[
+    // 东亚
+    ("Asia/Shanghai", 31.23, 121.47),
+    ("Asia/Chongqing", 29.56, 106.55),
+    ("Asia/Harbin", 45.80, 126.53),
+    ("Asia/Urumqi", 43.83, 87.62),
+    ("Asia/Hong_Kong", 22.32, 114.17),
+    ("Asia/Macau", 22.20, 113.55),
+    ("Asia/Taipei", 25.03, 121.57),
+    ("Asia/Tokyo", 35.68, 139.69),
+    ("Asia/Seoul", 37.57, 126.98),
+    ("Asia/Pyongyang", 39.03, 125.75),
+    ("Asia/Ulaanbaatar", 47.89, 106.91),
+    // 东南亚
+    ("Asia/Singapore", 1.35, 103.82),
+    ("Asia/Kuala_Lumpur", 3.139, 101.69),
+    ("Asia/Bangkok", 13.75, 100.50),
+    ("Asia/Ho_Chi_Minh", 10.82, 106.63),
+    ("Asia/Saigon", 10.82, 106.63),
+    ("Asia/Phnom_Penh", 11.56, 104.92),
+    ("Asia/Vientiane", 17.98, 102.63),
+    ("Asia/Yangon", 16.87, 96.20),
+    ("Asia/Jakarta", -6.21, 106.85),
+    ("Asia/Makassar", -5.15, 119.43),
+    ("Asia/Jayapura", -2.53, 140.72),
+    ("Asia/Manila", 14.60, 120.98),
+    ("Asia/Brunei", 4.89, 114.94),
+    ("Asia/Dili", -8.56, 125.56),
+    // 南亚、中亚、西亚
+    ("Asia/Kolkata", 22.57, 88.36),
+    ("Asia/Calcutta", 22.57, 88.36),
+    ("Asia/Dhaka", 23.81, 90.41),
+    ("Asia/Kathmandu", 27.72, 85.32),
+    ("Asia/Colombo", 6.93, 79.86),
+    ("Asia/Karachi", 24.86, 67.01),
+    ("Asia/Kabul", 34.56, 69.21),
+    ("Asia/Tashkent", 41.30, 69.24),
+    ("Asia/Almaty", 43.24, 76.89),
+    ("Asia/Tehran", 35.69, 51.39),
+    ("Asia/Dubai", 25.20, 55.27),
+    ("Asia/Riyadh", 24.71, 46.68),
+    ("Asia/Jerusalem", 31.77, 35.21),
+    // 俄罗斯亚洲部分
+    ("Asia/Yekaterinburg", 56.84, 60.61),
+    ("Asia/Novosibirsk", 55.01, 82.93),
+    ("Asia/Krasnoyarsk", 56.01, 92.85),
+    ("Asia/Irkutsk", 52.29, 104.28),
+    ("Asia/Yakutsk", 62.03, 129.73),
+    ("Asia/Vladivostok", 43.12, 131.89),
+    ("Asia/Magadan", 59.56, 150.80),
+    ("Asia/Kamchatka", 53.02, 158.65),
+    // 大洋洲
+    ("Australia/Sydney", -33.87, 151.21),
+    ("Australia/Melbourne", -37.81, 144.96),
+    ("Australia/Brisbane", -27.47, 153.03),
+    ("Australia/Adelaide", -34.93, 138.60),
+    ("Australia/Darwin", -12.46, 130.84),
+    ("Australia/Perth", -31.95, 115.86),
+    ("Australia/Hobart", -42.88, 147.33),
+    ("Pacific/Auckland", -36.85, 174.76),
+    ("Pacific/Port_Moresby", -9.44, 147.18),
+    ("Pacific/Guam", 13.44, 144.79),
+    ("Pacific/Noumea", -22.28, 166.46),
+    ("Pacific/Fiji", -18.14, 178.44),
+    ("Pacific/Honolulu", 21.31, -157.86),
+    // 欧洲
+    ("Europe/London", 51.51, -0.13),
+    ("Europe/Paris", 48.86, 2.35),
+    ("Europe/Berlin", 52.52, 13.40),
+    ("Europe/Madrid", 40.42, -3.70),
+    ("Europe/Rome", 41.90, 12.50),
+    ("Europe/Amsterdam", 52.37, 4.90),
+    ("Europe/Stockholm", 59.33, 18.07),
+    ("Europe/Warsaw", 52.23, 21.01),
+    ("Europe/Kyiv", 50.45, 30.52),
+    ("Europe/Kiev", 50.45, 30.52),
+    ("Europe/Istanbul", 41.01, 28.98),
+    ("Europe/Moscow", 55.76, 37.62),
+    // 非洲
+    ("Africa/Cairo", 30.04, 31.24),
+    ("Africa/Lagos", 6.52, 3.38),
+    ("Africa/Nairobi", -1.29, 36.82),
+    ("Africa/Johannesburg", -26.20, 28.05),
+    // 美洲
+    ("America/New_York", 40.71, -74.01),
+    ("America/Toronto", 43.65, -79.38),
+    ("America/Chicago", 41.88, -87.63),
+    ("America/Denver", 39.74, -104.99),
+    ("America/Los_Angeles", 34.05, -118.24),
+    ("America/Vancouver", 49.28, -123.12),
+    ("America/Anchorage", 61.22, -149.90),
+    ("America/Mexico_City", 19.43, -99.13),
+    ("America/Bogota", 4.71, -74.07),
+    ("America/Lima", -12.05, -77.04),
+    ("America/Santiago", -33.45, -70.67),
+    ("America/Sao_Paulo", -23.55, -46.63),
+    ("America/Argentina/Buenos_Aires", -34.60, -58.38),
+];
+
+/// 常见国家和地区的 ISO 3166 代码及其首都的纬度、经度
+const REGIONS: &[(&str, f64, f64, &str)] = &[
+    // 亚洲
+    ("CN", 39.90, 116.41, "Beijing"),
+    ("HK", 22.32, 114.17, "Hong Kong"),
+    ("MO", 22.20, 113.55, "Macau"),
+    ("TW", 25.03, 121.57, "Taipei"),
+    ("JP", 35.68, 139.69, "Tokyo"),
+    ("KR", 37.57, 126.98, "Seoul"),
+    ("MN", 47.89, 106.91, "Ulaanbaatar"),
+    ("SG", 1.35, 103.82, "Singapore"),
+    ("MY", 3.139, 101.69, "Kuala Lumpur"),
+    ("TH", 13.75, 100.50, "Bangkok"),
+    ("VN", 21.03, 105.85, "Hanoi"),
+    ("ID", -6.21, 106.85, "Jakarta"),
+    ("PH", 14.60, 120.98, "Manila"),
+    ("IN", 28.61, 77.21, "New Delhi"),
+    ("PK", 33.68, 73.05, "Islamabad"),
+    ("BD", 23.81, 90.41, "Dhaka"),
+    ("IR", 35.69, 51.39, "Tehran"),
+    ("AE", 24.45, 54.38, "Abu Dhabi"),
+    ("SA", 24.71, 46.68, "Riyadh"),
+    ("IL", 31.77, 35.21, "Jerusalem"),
+    ("TR", 39.93, 32.86, "Ankara"),
+    // 大洋洲
+    ("AU", -35.28, 149.13, "Canberra"),
+    ("NZ", -41.29, 174.78, "Wellington"),
+    // 欧洲
+    ("GB", 51.51, -0.13, "London"),
+    ("IE", 53.35, -6.26, "Dublin"),
+    ("FR", 48.86, 2.35, "Paris"),
+    ("DE", 52.52, 13.40, "Berlin"),
+    ("AT", 48.21, 16.37, "Vienna"),
+    ("CH", 46.95, 7.45, "Bern"),
+    ("NL", 52.37, 4.90, "Amsterdam"),
+    ("BE", 50.85, 4.35, "Brussels"),
+    ("ES", 40.42, -3.70, "Madrid"),
+    ("PT", 38.72, -9.14, "Lisbon"),
+    ("IT", 41.90, 12.50, "Rome"),
+    ("SE", 59.33, 18.07, "Stockholm"),
+    ("NO", 59.91, 10.75, "Oslo"),
+    ("DK", 55.68, 12.57, "Copenhagen"),
+    ("FI", 60.17, 24.94, "Helsinki"),
+    ("PL", 52.23, 21.01, "Warsaw"),
+    ("CZ", 50.08, 14.44, "Prague"),
+    ("UA", 50.45, 30.52, "Kyiv"),
+    ("RU", 55.76, 37.62, "Moscow"),
+    // 非洲
+    ("EG", 30.04, 31.24, "Cairo"),
+    ("NG", 9.08, 7.40, "Abuja"),
+    ("KE", -1.29, 36.82, "Nairobi"),
+    ("ZA", -25.75, 28.19, "Pretoria"),
+    // 美洲
+    ("US", 38.91, -77.04, "Washington"),
+    ("CA", 45.42, -75.70, "Ottawa"),
+    ("MX", 19.43, -99.13, "Mexico City"),
+    ("CO", 4.71, -74.07, "Bogota"),
+    ("PE", -12.05, -77.04, "Lima"),
+    ("CL", -33.45, -70.67, "Santiago"),
+    ("BR", -15.79, -47.88, "Brasilia"),
+    ("AR", -34.60, -58.38, "Buenos Aires"),
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::Cell;
+
+    fn guessed(latitude: f64, longitude: f64) -> Option<ResolvedLocation> {
+        Some(ResolvedLocation {
+            latitude,
+            longitude,
+            source: LocationSource::Timezone,
+            name: None,
+        })
+    }
+
+    #[test]
+    fn timezone_guess_table() {
+        let cases = [
+            ("Asia/Shanghai", Some("Shanghai")),
+            ("Asia/Hong_Kong", Some("Hong Kong")),
+            ("America/Argentina/Buenos_Aires", Some("Buenos Aires")),
+            ("Europe/Kiev", Some("Kiev")),
+            ("Etc/UTC", None),
+            ("asia/shanghai", None),
+            ("", None),
+        ];
+        for (timezone, name) in cases {
+            let guess = timezone_guess(timezone);
+            assert_eq!(guess.as_ref().and_then(|g| g.name.as_deref()), name, "{}", timezone);
+            assert!(guess.is_none_or(|g| g.source == LocationSource::Timezone));
+        }
+    }
+
+    #[test]
+    fn region_guess_table() {
+        let cases = [
+            ("zh-CN", Some("Beijing")),
+            ("en_US.UTF-8", Some("Washington")),
+            ("zh-Hans-TW", Some("Taipei")),
+            ("de_de@euro", Some("Berlin")),
+            ("ja-JP-u-ca-japanese", Some("Tokyo")),
+            ("en", None),
+            ("C", None),
+            ("POSIX", None),
+            ("xx-ZZ", None),
+        ];
+        for (locale, name) in cases {
+            let guess = region_guess(locale);
+            assert_eq!(guess.as_ref().and_then(|g| g.name.as_deref()), name, "{}", locale);
+            assert!(guess.is_none_or(|g| g.source == LocationSource::Region));
+        }
+    }
+
+    #[test]
+    fn tables_have_valid_coordinates() {
+        let timezones = TIMEZONES.iter().map(|&(_, lat, lon)| (lat, lon));
+        let regions = REGIONS.iter().map(|&(_, lat, lon, _)| (lat, lon));
+        for (latitude, longitude) in timezones.chain(regions) {
+            assert!(ManualLocation { latitude, longitude }.validate().is_ok());
+        }
+    }
+
+    #[test]
+    fn manual_location_wins() {
+        let state = LocationState::default();
+        let manual = ManualLocation {
+            latitude: 35.68,
+            longitude: 139.69,
+        };
+        let resolved = state
+            .resolve_with(Some(manual), true, || panic!("不应定位"), || panic!("不应推测"))
+            .unwrap();
+        assert_eq!(resolved.source, LocationSource::Manual);
+        assert_eq!((resolved.latitude, resolved.longitude), (35.68, 139.69));
+    }
+
+    #[test]
+    fn invalid_manual_location_falls_back_to_system() {
+        let state = LocationState::default();
+        let manual = ManualLocation {
+            latitude: 91.0,
+            longitude: 0.0,
+        };
+        let resolved = state
+            .resolve_with(Some(manual), true, || Some((1.0, 2.0)), || panic!("不应推测"))
+            .unwrap();
+        assert_eq!(resolved.source, LocationSource::System);
+    }
+
+    #[test]
+    fn guess_when_system_is_disabled_or_unavailable() {
+        let state = LocationState::default();
+        let resolved = state.resolve_with(None, false, || panic!("不应定位"), || guessed(3.0, 4.0));
+        assert_eq!(resolved, guessed(3.0, 4.0));
+
+        let state = LocationState::default();
+        let resolved = state.resolve_with(None, true, || None, || guessed(3.0, 4.0));
+        assert_eq!(resolved, guessed(3.0, 4.0));
+
+        let state = LocationState::default();
+        assert_eq!(state.resolve_with(None, true, || None, || None), None);
+    }
+
+    #[test]
+    fn cached_result_is_reused_until_invalidated() {
+        let state = LocationState::default();
+        let calls = Cell::new(0);
+        let system = || {
+            calls.set(calls.get() + 1);
+            Some((1.0, 2.0))
+        };
+        state.resolve_with(None, true, system, || None);
+        state.resolve_with(None, true, system, || None);
+        assert_eq!(calls.get(), 1);
+
+        // 关闭系统定位后不再用缓存的系统定位结果
+        let resolved = state.resolve_with(None, false, system, || guessed(3.0, 4.0));
+        assert_eq!(resolved, guessed(3.0, 4.0));
+
+        state.invalidate();
+        state.resolve_with(None, true, system, || None);
+        assert_eq!(calls.get(), 2);
+    }
+}
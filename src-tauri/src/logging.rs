@@ -0,0 +1,94 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use tracing_appender::rolling::{RollingFileAppender, Rotation};
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::util::SubscriberInitExt;
+use tracing_subscriber::{EnvFilter, fmt};
+
+use crate::error::{EarthError, EarthResult};
+
+/// 日志文件名前缀，完整文件名形如 immediate_earth.2025-06-20.log
+const LOG_PREFIX: &str = "immediate_earth";
+const LOG_SUFFIX: &str = "log";
+
+/// 最多保留的日志文件数，按天滚动即保留最近一周
+const MAX_LOG_FILES: usize = 7;
+
+/// 默认级别，可用 RUST_LOG 环境变量覆盖，如 RUST_LOG=tauri_app_lib=debug
+const DEFAULT_FILTER: &str = "info";
+
+/// 初始化日志：写入日志目录下按天滚动的文件，同时输出到控制台
+///
+/// release 版在 Windows 上没有控制台，文件是唯一能看到日志的地方。
+/// 日志目录不可写时退回到只输出控制台。
+pub fn init(log_dir: &Path) {
+    let filter = EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new(DEFAULT_FILTER));
+    // 两个输出层共用 span 字段的格式化结果，控制台带颜色会把转义符写进文件
+    let console = fmt::layer().with_ansi(false);
+
+    let file = fs::create_dir_all(log_dir)
+        .map_err(|e| e.to_string())
+        .and_then(|_| {
+            RollingFileAppender::builder()
+                .rotation(Rotation::DAILY)
+                .filename_prefix(LOG_PREFIX)
+                .filename_suffix(LOG_SUFFIX)
+                .max_log_files(MAX_LOG_FILES)
+                .build(log_dir)
+                .map_err(|e| e.to_string())
+        });
+
+    match file {
+        Ok(appender) => {
+            let file_layer = fmt::layer().with_ansi(false).with_writer(appender);
+            let _ = tracing_subscriber::registry()
+                .with(filter)
+                .with(console)
+                .with(file_layer)
+                .try_init();
+            tracing::info!(dir = %log_dir.display(), "日志已初始化");
+        }
+        Err(e) => {
+            let _ = tracing_subscriber::registry().with(filter).with(console).try_init();
+            tracing::warn!(dir = %log_dir.display(), error = %e, "无法写入日志文件，只输出到控制台");
+        }
+    }
+}
+
+/// 日志目录下的日志文件，按日期从旧到新排列
+fn log_files(log_dir: &Path) -> EarthResult<Vec<PathBuf>> {
+    let mut files: Vec<PathBuf> = fs::read_dir(log_dir)
+        .map_err(|e| EarthError::io(log_dir, "读取日志目录失败", e))?
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| {
+            path.file_name()
+                .and_then(|name| name.to_str())
+                .is_some_and(|name| name.starts_with(LOG_PREFIX) && name.ends_with(LOG_SUFFIX))
+        })
+        .collect();
+    // 文件名中的日期是 YYYY-MM-DD，按名字排序即按时间排序
+    files.sort();
+    Ok(files)
+}
+
+/// 读取最近的 n 行日志，按时间从旧到新排列；当天不够时继续往前一天的文件里找
+pub fn recent_lines(log_dir: &Path, n: usize) -> EarthResult<Vec<String>> {
+    let mut lines = Vec::new();
+    for path in log_files(log_dir)?.iter().rev() {
+        if lines.len() >= n {
+            break;
+        }
+        let content = fs::read_to_string(path).map_err(|e| EarthError::io(path, "读取日志文件失败", e))?;
+        let mut older: Vec<String> = content
+            .lines()
+            .rev()
+            .take(n - lines.len())
+            .map(str::to_string)
+            .collect();
+        older.reverse();
+        older.append(&mut lines);
+        lines = older;
+    }
+    Ok(lines)
+}
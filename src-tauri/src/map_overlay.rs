@@ -0,0 +1,263 @@
+use image::RgbaImage;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, OnceLock};
+use tracing::{debug, warn};
+
+use crate::error::{EarthError, EarthResult};
+use crate::overlay::{self, LineMask};
+use crate::projection;
+
+/// 随应用打包的 Natural Earth 1:110m 矢量数据，位于资源目录的 geo 子目录
+pub const COASTLINE_FILE: &str = "ne_110m_coastline.geojson";
+pub const BORDER_FILE: &str = "ne_110m_admin_0_boundary_lines_land.geojson";
+
+/// 沿经线、纬线每隔多少度取一个点，足够让曲线在 2200 像素的大图上看起来平滑
+const SAMPLE_STEP: f64 = 1.0;
+
+/// 经纬网和海岸线叠加设置，保存在 settings.json 的 map_overlay 字段
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MapOverlayConfig {
+    pub enabled: bool,
+    pub graticule: bool,
+    /// 经纬线间隔，单位度
+    pub graticule_step: f64,
+    pub coastlines: bool,
+    pub borders: bool,
+    /// #RRGGBB
+    pub color: String,
+    /// 线宽，单位为拼接大图的像素
+    pub line_width: f64,
+    /// 0 到 1
+    pub opacity: f64,
+}
+
+impl Default for MapOverlayConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            graticule: true,
+            graticule_step: 10.0,
+            coastlines: true,
+            borders: false,
+            color: "#FFD700".to_string(),
+            line_width: 1.5,
+            opacity: 0.6,
+        }
+    }
+}
+
+impl MapOverlayConfig {
+    pub fn validate(&self) -> EarthResult<()> {
+        if !(1.0..=45.0).contains(&self.graticule_step) {
+            return Err(EarthError::invalid("经纬线间隔需在 1 到 45 度之间"));
+        }
+        if !(0.5..=10.0).contains(&self.line_width) {
+            return Err(EarthError::invalid("线宽需在 0.5 到 10 之间"));
+        }
+        if !(0.0..=1.0).contains(&self.opacity) {
+            return Err(EarthError::invalid("不透明度需在 0 到 1 之间"));
+        }
+        overlay::parse_color(&self.color).map(|_| ())
+    }
+}
+
+/// 一条折线，点为 (经度, 纬度)
+type Polyline = Vec<(f64, f64)>;
+
+/// 经纬网：经线从南纬 80 到北纬 80（两极附近经线汇聚，画满会糊成一团），纬线绕地球一周
+fn graticule(step: f64) -> Vec<Polyline> {
+    let mut lines = Vec::new();
+    let samples = |from: f64, to: f64| {
+        let n = ((to - from) / SAMPLE_STEP).round() as i32;
+        (0..=n).map(move |i| from + i as f64 * SAMPLE_STEP)
+    };
+    let mut lon = -180.0;
+    while lon < 180.0 {
+        lines.push(samples(-80.0, 80.0).map(|lat| (lon, lat)).collect());
+        lon += step;
+    }
+    let mut lat = -90.0 + step;
+    while lat < 90.0 {
+        lines.push(samples(-180.0, 180.0).map(|lon| (lon, lat)).collect());
+        lat += step;
+    }
+    lines
+}
+
+/// 读取 GeoJSON 中的所有线：LineString、MultiLineString 以及多边形的边界
+pub fn parse_geojson(body: &str) -> EarthResult<Vec<Polyline>> {
+    let root: Value = serde_json::from_str(body).map_err(|e| EarthError::internal("解析地图数据失败", e))?;
+    let mut lines = Vec::new();
+    let features = root["features"].as_array().map(Vec::as_slice).unwrap_or_default();
+    for geometry in features.iter().map(|f| &f["geometry"]) {
+        let coords = &geometry["coordinates"];
+        let rings: Vec<&Value> = match geometry["type"].as_str() {
+            Some("LineString") => vec![coords],
+            Some("MultiLineString") | Some("Polygon") => coords.as_array().into_iter().flatten().collect(),
+            Some("MultiPolygon") => coords
+                .as_array()
+                .into_iter()
+                .flatten()
+                .flat_map(|polygon| polygon.as_array().into_iter().flatten())
+                .collect(),
+            _ => continue,
+        };
+        for ring in rings {
+            let line: Polyline = ring
+                .as_array()
+                .into_iter()
+                .flatten()
+                .filter_map(|p| Some((p[0].as_f64()?, p[1].as_f64()?)))
+                .collect();
+            if line.len() >= 2 {
+                lines.push(line);
+            }
+        }
+    }
+    Ok(lines)
+}
+
+/// 读取资源目录中的矢量数据，文件不存在时返回空并记录日志
+///
+/// 解析结果按路径缓存，每次更新壁纸不必重新读取和解析 GeoJSON；读取失败不缓存。
+fn load_lines(dir: &Path, file: &str) -> Arc<Vec<Polyline>> {
+    static CACHE: OnceLock<Mutex<HashMap<PathBuf, Arc<Vec<Polyline>>>>> = OnceLock::new();
+    let path = dir.join(file);
+    let cache = CACHE.get_or_init(Default::default);
+    if let Some(lines) = cache.lock().unwrap().get(&path) {
+        return lines.clone();
+    }
+    let body = match fs::read_to_string(&path) {
+        Ok(body) => body,
+        Err(e) => {
+            warn!(path = %path.display(), error = %e, "地图数据不存在，跳过");
+            return Arc::default();
+        }
+    };
+    match parse_geojson(&body) {
+        Ok(lines) => {
+            let lines = Arc::new(lines);
+            cache.lock().unwrap().insert(path, lines.clone());
+            lines
+        }
+        Err(e) => {
+            warn!(path = %path.display(), error = %e, "地图数据无法解析，跳过");
+            Arc::default()
+        }
+    }
+}
+
+/// 把折线投影到全圆盘图上画进蒙版，位于地球背面的部分断开不画
+fn stroke_lines(mask: &mut LineMask, lines: &[Polyline], size: u32, line_width: f64) {
+    let size_f = size as f64;
+    for line in lines {
+        let mut last: Option<(f64, f64)> = None;
+        for &(lon, lat) in line {
+            let point = projection::to_disk(lat, lon).map(|(u, v)| (u * size_f, v * size_f));
+            if let (Some(a), Some(b)) = (last, point) {
+                mask.stroke(a, b, line_width);
+            }
+            last = point;
+        }
+    }
+}
+
+/// 在拼接好的全圆盘大图（不含黑边）上画经纬网、海岸线和国界
+///
+/// geo_dir 是打包的 Natural Earth 数据所在目录；数据缺失时只画经纬网。
+pub fn draw(img: &mut RgbaImage, config: &MapOverlayConfig, geo_dir: &Path) -> EarthResult<()> {
+    let color = overlay::parse_color(&config.color)?;
+    let size = img.width().min(img.height());
+    let mut mask = LineMask::new(img.width(), img.height());
+    if config.graticule {
+        stroke_lines(&mut mask, &graticule(config.graticule_step), size, config.line_width);
+    }
+    if config.coastlines {
+        stroke_lines(&mut mask, &load_lines(geo_dir, COASTLINE_FILE), size, config.line_width);
+    }
+    if config.borders {
+        // 国界比海岸线细一些，避免与海岸线混淆
+        stroke_lines(&mut mask, &load_lines(geo_dir, BORDER_FILE), size, config.line_width * 0.75);
+    }
+    mask.composite(img, color, config.opacity);
+    debug!(
+        graticule = config.graticule,
+        coastlines = config.coastlines,
+        borders = config.borders,
+        "已绘制地图叠加层"
+    );
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn graticule_lines_and_samples() {
+        let lines = graticule(10.0);
+        // 36 条经线，纬线从南纬 80 到北纬 80 共 17 条
+        assert_eq!(lines.len(), 36 + 17);
+        let meridian = &lines[0];
+        assert_eq!(meridian.first(), Some(&(-180.0, -80.0)));
+        assert_eq!(meridian.last(), Some(&(-180.0, 80.0)));
+        assert_eq!(meridian.len(), 161);
+        let parallel = &lines[36];
+        assert_eq!(parallel.first(), Some(&(-180.0, -80.0)));
+        assert_eq!(parallel.last(), Some(&(180.0, -80.0)));
+        assert_eq!(parallel.len(), 361);
+        // 间隔不能整除 180 时不画到极点和 180 度经线之外
+        let lines = graticule(45.0);
+        assert_eq!(lines.len(), 8 + 3);
+        assert!(lines.iter().flatten().all(|&(lon, lat)| (-180.0..=180.0).contains(&lon) && (-90.0..90.0).contains(&lat)));
+    }
+
+    #[test]
+    fn parse_geojson_reads_lines_and_polygon_rings() {
+        let body = r#"{
+            "type": "FeatureCollection",
+            "features": [
+                {"geometry": {"type": "LineString", "coordinates": [[0, 0], [1, 1]]}},
+                {"geometry": {"type": "MultiLineString", "coordinates": [[[2, 2], [3, 3]], [[4, 4], [5, 5], [6, 6]]]}},
+                {"geometry": {"type": "Polygon", "coordinates": [[[0, 0], [1, 0], [1, 1], [0, 0]]]}},
+                {"geometry": {"type": "MultiPolygon", "coordinates": [[[[7, 7], [8, 8]]], [[[9, 9], [10, 10]], [[11, 11], [12, 12]]]]}},
+                {"geometry": {"type": "Point", "coordinates": [0, 0]}},
+                {"geometry": {"type": "LineString", "coordinates": [[0, 0]]}},
+                {"geometry": {"type": "LineString", "coordinates": [[0, 0], ["x", 1], [2.5, -3.5]]}},
+                {"geometry": null}
+            ]
+        }"#;
+        let lines = parse_geojson(body).unwrap();
+        let lengths: Vec<usize> = lines.iter().map(Vec::len).collect();
+        // 点和只有一个点的线跳过，坐标无效的点丢掉
+        assert_eq!(lengths, [2, 2, 3, 4, 2, 2, 2, 2]);
+        assert_eq!(lines[0], [(0.0, 0.0), (1.0, 1.0)]);
+        assert_eq!(lines[7], [(0.0, 0.0), (2.5, -3.5)]);
+    }
+
+    #[test]
+    fn parse_geojson_errors_and_empty_collections() {
+        assert!(parse_geojson("{").is_err());
+        assert!(parse_geojson(r#"{"type": "FeatureCollection", "features": []}"#).unwrap().is_empty());
+        assert!(parse_geojson(r#"{"type": "Feature"}"#).unwrap().is_empty());
+    }
+
+    #[test]
+    fn parsed_lines_are_cached_by_path() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = "lines.geojson";
+        assert!(load_lines(dir.path(), file).is_empty());
+        let body = r#"{"features": [{"geometry": {"type": "LineString", "coordinates": [[0, 0], [1, 1]]}}]}"#;
+        fs::write(dir.path().join(file), body).unwrap();
+        // 之前读取失败没有缓存，文件出现后能读到
+        let first = load_lines(dir.path(), file);
+        assert_eq!(first.len(), 1);
+        fs::remove_file(dir.path().join(file)).unwrap();
+        assert!(Arc::ptr_eq(&first, &load_lines(dir.path(), file)));
+    }
+}
@@ -0,0 +1,522 @@
+use ab_glyph::{Font, FontArc, FontVec, Glyph, PxScale, ScaleFont, point};
+use chrono::{DateTime, Local, Utc};
+use image::{Rgba, RgbaImage};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::sync::OnceLock;
+use tracing::debug;
+
+use crate::error::{EarthError, EarthResult};
+
+/// 随应用打包的字体，覆盖拉丁、希腊、西里尔字母和常用符号
+const BUNDLED_FONT: &[u8] = include_bytes!("../fonts/DejaVuSans.ttf");
+
+/// 随应用打包的中日文字体：文泉驿微米黑的子集，含 GB 2312 一级汉字、JIS 第一水准汉字、假名和全角标点
+const BUNDLED_CJK_FONT: &[u8] = include_bytes!("../fonts/WenQuanYiMicroHei-subset.ttf");
+
+/// 两个打包字体里都没有的字（生僻字等）从这些系统字体中找，按顺序取第一个存在的
+const FALLBACK_FONTS: &[&str] = &[
+    "C:\\Windows\\Fonts\\msyh.ttc",
+    "C:\\Windows\\Fonts\\YuGothM.ttc",
+    "/System/Library/Fonts/PingFang.ttc",
+    "/System/Library/Fonts/Hiragino Sans GB.ttc",
+    "/usr/share/fonts/opentype/noto/NotoSansCJK-Regular.ttc",
+    "/usr/share/fonts/noto-cjk/NotoSansCJK-Regular.ttc",
+    "/usr/share/fonts/google-noto-cjk/NotoSansCJK-Regular.ttc",
+    "/usr/share/fonts/truetype/wqy/wqy-microhei.ttc",
+];
+
+/// 按 alpha 把颜色混合到像素上，坐标越界时忽略
+fn blend_pixel(img: &mut RgbaImage, x: i64, y: i64, color: Rgba<u8>, alpha: f64) {
+    if x < 0 || y < 0 || x >= img.width() as i64 || y >= img.height() as i64 || alpha <= 0.0 {
+        return;
+    }
+    let alpha = alpha.min(1.0) * color[3] as f64 / 255.0;
+    let pixel = img.get_pixel_mut(x as u32, y as u32);
+    for i in 0..3 {
+        pixel[i] = (pixel[i] as f64 * (1.0 - alpha) + color[i] as f64 * alpha).round() as u8;
+    }
+}
+
+/// 线条的覆盖度蒙版：先把所有线段画进来再一次性混合，线段相接处不会因重复混合而变深
+///
+/// 覆盖度按 0 到 255 存成一个字节，最高级别的大图也只占与像素数相同的内存。
+pub struct LineMask {
+    width: u32,
+    height: u32,
+    coverage: Vec<u8>,
+}
+
+impl LineMask {
+    pub fn new(width: u32, height: u32) -> Self {
+        Self {
+            width,
+            height,
+            coverage: vec![0; width as usize * height as usize],
+        }
+    }
+
+    /// 画一条线段，line_width 为线宽（像素），边缘做 1 像素抗锯齿
+    pub fn stroke(&mut self, (x0, y0): (f64, f64), (x1, y1): (f64, f64), line_width: f64) {
+        let half = line_width / 2.0;
+        let reach = half + 1.0;
+        let min_x = (x0.min(x1) - reach).floor().max(0.0) as u32;
+        let min_y = (y0.min(y1) - reach).floor().max(0.0) as u32;
+        let max_x = ((x0.max(x1) + reach).ceil().max(0.0) as u32).min(self.width);
+        let max_y = ((y0.max(y1) + reach).ceil().max(0.0) as u32).min(self.height);
+        let (dx, dy) = (x1 - x0, y1 - y0);
+        let len2 = dx * dx + dy * dy;
+        for y in min_y..max_y {
+            for x in min_x..max_x {
+                let (px, py) = (x as f64 + 0.5, y as f64 + 0.5);
+                let t = if len2 > 0.0 {
+                    (((px - x0) * dx + (py - y0) * dy) / len2).clamp(0.0, 1.0)
+                } else {
+                    0.0
+                };
+                let d = ((px - x0 - t * dx).powi(2) + (py - y0 - t * dy).powi(2)).sqrt();
+                let c = ((half - d + 0.5).clamp(0.0, 1.0) * 255.0).round() as u8;
+                let cell = &mut self.coverage[y as usize * self.width as usize + x as usize];
+                *cell = (*cell).max(c);
+            }
+        }
+    }
+
+    /// 按覆盖度把颜色混合到图上，opacity 为整体不透明度
+    pub fn composite(&self, img: &mut RgbaImage, color: Rgba<u8>, opacity: f64) {
+        for (i, &c) in self.coverage.iter().enumerate() {
+            if c > 0 {
+                let x = (i % self.width as usize) as i64;
+                let y = (i / self.width as usize) as i64;
+                blend_pixel(img, x, y, color, c as f64 / 255.0 * opacity);
+            }
+        }
+    }
+}
+
+/// 「你在这里」标记：白色圆环包着红点，外圈一圈暗色描边，在云和海面上都看得清
+pub fn draw_location_marker(img: &mut RgbaImage, cx: f64, cy: f64, radius: f64) {
+    let ring = (radius * 0.25).max(1.5);
+    let outline = Rgba([0, 0, 0, 160]);
+    let white = Rgba([255, 255, 255, 255]);
+    let red = Rgba([230, 40, 40, 255]);
+    let reach = (radius + ring * 2.0).ceil() as i64;
+    for dy in -reach..=reach {
+        for dx in -reach..=reach {
+            let x = cx.round() as i64 + dx;
+            let y = cy.round() as i64 + dy;
+            let d = ((x as f64 - cx).powi(2) + (y as f64 - cy).powi(2)).sqrt();
+            // 每一层按到边缘的距离做 1 像素的抗锯齿
+            let coverage = |inner: f64, outer: f64| (d - inner + 0.5).clamp(0.0, 1.0) * (outer - d + 0.5).clamp(0.0, 1.0);
+            blend_pixel(img, x, y, outline, coverage(radius - ring * 1.5, radius + ring));
+            blend_pixel(img, x, y, white, coverage(radius - ring, radius));
+            blend_pixel(img, x, y, red, coverage(f64::NEG_INFINITY, radius * 0.45));
+        }
+    }
+}
+
+/// 标注地点的圆点：彩色实心圆外加一圈暗色描边
+pub fn draw_dot(img: &mut RgbaImage, cx: f64, cy: f64, radius: f64, color: Rgba<u8>) {
+    let outline = Rgba([0, 0, 0, 160]);
+    let border = (radius * 0.35).max(1.0);
+    let reach = (radius + border + 1.0).ceil() as i64;
+    for dy in -reach..=reach {
+        for dx in -reach..=reach {
+            let x = cx.round() as i64 + dx;
+            let y = cy.round() as i64 + dy;
+            let d = ((x as f64 - cx).powi(2) + (y as f64 - cy).powi(2)).sqrt();
+            blend_pixel(img, x, y, outline, (radius + border - d + 0.5).clamp(0.0, 1.0));
+            blend_pixel(img, x, y, color, (radius - d + 0.5).clamp(0.0, 1.0));
+        }
+    }
+}
+
+/// 在 (x, y) 右侧画一行带阴影的标签，文字垂直居中于 y；右侧放不下时改画在左侧
+pub fn draw_label(img: &mut RgbaImage, text: &str, x: f64, y: f64, gap: f64, px: f32, color: Rgba<u8>) {
+    let scale = PxScale::from(px);
+    let metrics = bundled_font().as_scaled(scale);
+    let width = line_width(text, scale);
+    let right = x as f32 + gap as f32;
+    let left = if right + width <= img.width() as f32 {
+        right
+    } else {
+        x as f32 - gap as f32 - width
+    };
+    let baseline = y as f32 + (metrics.ascent() + metrics.descent()) / 2.0;
+    let offset = (px / 14.0).max(1.0);
+    draw_line(img, text, left + offset, baseline + offset, scale, Rgba([0, 0, 0, 160]));
+    draw_line(img, text, left, baseline, scale, color);
+}
+
+/// 文字在壁纸上的位置
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TextPosition {
+    TopLeft,
+    TopRight,
+    BottomLeft,
+    #[default]
+    BottomRight,
+}
+
+/// 单个显示器的文字叠加设置，保存在 settings.json 的 text_overlays 字段（按显示器索引）
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TextOverlayConfig {
+    pub enabled: bool,
+    pub position: TextPosition,
+    /// 字号，单位为显示器的物理像素
+    pub font_size: f32,
+    /// #RRGGBB 或 #RRGGBBAA
+    pub color: String,
+    pub shadow: bool,
+    pub show_weather: bool,
+    pub show_local_time: bool,
+    pub show_capture_time: bool,
+    pub show_satellite: bool,
+}
+
+impl Default for TextOverlayConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            position: TextPosition::BottomRight,
+            font_size: 28.0,
+            color: "#FFFFFF".to_string(),
+            shadow: true,
+            show_weather: true,
+            show_local_time: true,
+            show_capture_time: false,
+            show_satellite: false,
+        }
+    }
+}
+
+impl TextOverlayConfig {
+    pub fn validate(&self) -> EarthResult<()> {
+        if !(8.0..=200.0).contains(&self.font_size) {
+            return Err(EarthError::invalid("字号需在 8 到 200 之间"));
+        }
+        parse_color(&self.color).map(|_| ())
+    }
+}
+
+/// 解析 #RRGGBB / #RRGGBBAA
+pub fn parse_color(value: &str) -> EarthResult<Rgba<u8>> {
+    let hex = value.trim().trim_start_matches('#');
+    let channel = |i: usize| hex.get(i..i + 2).and_then(|c| u8::from_str_radix(c, 16).ok());
+    // from_str_radix 接受 +1 这样的写法，先确认全是十六进制数字
+    let hex_digits = hex.bytes().all(|b| b.is_ascii_hexdigit());
+    let color = match hex.len() {
+        _ if !hex_digits => None,
+        6 => channel(0).zip(channel(2)).zip(channel(4)).map(|((r, g), b)| Rgba([r, g, b, 255])),
+        8 => channel(0)
+            .zip(channel(2))
+            .zip(channel(4))
+            .zip(channel(6))
+            .map(|(((r, g), b), a)| Rgba([r, g, b, a])),
+        _ => None,
+    };
+    color.ok_or_else(|| EarthError::invalid("颜色格式应为 #RRGGBB 或 #RRGGBBAA"))
+}
+
+/// 叠加文字的内容，设置壁纸时生成一次，各显示器按自己的设置挑选
+#[derive(Debug, Clone)]
+pub struct OverlayInfo {
+    /// 已按界面语言和单位格式化的天气摘要，获取失败时为空
+    pub weather: Option<String>,
+    pub local_time: DateTime<Local>,
+    pub capture_time: Option<DateTime<Utc>>,
+    pub satellite: String,
+}
+
+impl OverlayInfo {
+    /// 按设置挑出要显示的行，顺序为天气、本地时间、拍摄时间和卫星
+    pub fn lines(&self, config: &TextOverlayConfig) -> Vec<String> {
+        let mut lines = Vec::new();
+        if config.show_weather
+            && let Some(weather) = &self.weather
+        {
+            lines.push(weather.clone());
+        }
+        if config.show_local_time {
+            lines.push(self.local_time.format("%Y-%m-%d %H:%M").to_string());
+        }
+        match (config.show_satellite, config.show_capture_time.then_some(self.capture_time).flatten()) {
+            (true, Some(time)) => lines.push(format!("{} {} UTC", self.satellite, time.format("%Y-%m-%d %H:%M"))),
+            (false, Some(time)) => lines.push(format!("{} UTC", time.format("%Y-%m-%d %H:%M"))),
+            (true, None) => lines.push(self.satellite.clone()),
+            (false, None) => {}
+        }
+        lines
+    }
+}
+
+/// 一次设置壁纸时各显示器的文字叠加设置和共用的内容
+#[derive(Debug, Clone)]
+pub struct TextOverlays {
+    pub configs: HashMap<usize, TextOverlayConfig>,
+    pub info: OverlayInfo,
+}
+
+impl TextOverlays {
+    /// 该显示器开启了文字叠加时返回设置和要画的行
+    pub fn for_monitor(&self, monitor_index: usize) -> Option<(&TextOverlayConfig, Vec<String>)> {
+        let config = self.configs.get(&monitor_index).filter(|config| config.enabled)?;
+        Some((config, self.info.lines(config)))
+    }
+}
+
+fn bundled_font() -> &'static FontArc {
+    static FONT: OnceLock<FontArc> = OnceLock::new();
+    FONT.get_or_init(|| FontArc::try_from_slice(BUNDLED_FONT).expect("bundled font is valid"))
+}
+
+fn bundled_cjk_font() -> &'static FontArc {
+    static FONT: OnceLock<FontArc> = OnceLock::new();
+    FONT.get_or_init(|| FontArc::try_from_slice(BUNDLED_CJK_FONT).expect("bundled CJK font is valid"))
+}
+
+fn fallback_font() -> Option<&'static FontArc> {
+    static FONT: OnceLock<Option<FontArc>> = OnceLock::new();
+    FONT.get_or_init(|| {
+        let font = FALLBACK_FONTS.iter().find_map(|path| {
+            let data = fs::read(path).ok()?;
+            FontVec::try_from_vec_and_index(data, 0).ok().map(FontArc::new)
+        });
+        if font.is_none() {
+            debug!("未找到可用的 CJK 系统字体，打包字体中没有的字将无法显示");
+        }
+        font
+    })
+    .as_ref()
+}
+
+/// 字符所用的字体：依次找打包的拉丁字体、打包的中日文字体和系统字体
+fn font_for(c: char) -> &'static FontArc {
+    let bundled = bundled_font();
+    [bundled, bundled_cjk_font()]
+        .into_iter()
+        .chain(fallback_font())
+        .find(|font| font.glyph_id(c).0 != 0)
+        .unwrap_or(bundled)
+}
+
+fn line_width(line: &str, scale: PxScale) -> f32 {
+    line.chars()
+        .map(|c| {
+            let font = font_for(c).as_scaled(scale);
+            font.h_advance(font.glyph_id(c))
+        })
+        .sum()
+}
+
+fn draw_line(img: &mut RgbaImage, line: &str, x: f32, baseline: f32, scale: PxScale, color: Rgba<u8>) {
+    let mut caret = x;
+    for c in line.chars() {
+        let font = font_for(c);
+        let scaled = font.as_scaled(scale);
+        let id = scaled.glyph_id(c);
+        let glyph: Glyph = id.with_scale_and_position(scale, point(caret, baseline));
+        caret += scaled.h_advance(id);
+        let Some(outlined) = font.outline_glyph(glyph) else {
+            continue;
+        };
+        let bounds = outlined.px_bounds();
+        outlined.draw(|gx, gy, coverage| {
+            let px = bounds.min.x as i64 + gx as i64;
+            let py = bounds.min.y as i64 + gy as i64;
+            blend_pixel(img, px, py, color, coverage as f64);
+        });
+    }
+}
+
+/// 把多行文字画到壁纸的一角
+///
+/// scale 是壁纸像素与显示器物理像素之比，字号和边距按它换算，壁纸被桌面拉伸后大小与设置一致。
+pub fn draw_text(img: &mut RgbaImage, lines: &[String], config: &TextOverlayConfig, scale: f64) -> EarthResult<()> {
+    if lines.is_empty() {
+        return Ok(());
+    }
+    let color = parse_color(&config.color)?;
+    let px = (config.font_size as f64 * scale).max(6.0) as f32;
+    let scale_px = PxScale::from(px);
+    let metrics = bundled_font().as_scaled(scale_px);
+    let line_height = metrics.height() + metrics.line_gap();
+    let margin = px * 1.2;
+    let shadow_offset = (px / 14.0).max(1.0);
+    let shadow = Rgba([0, 0, 0, (color[3] as u32 * 3 / 5) as u8]);
+
+    let block_height = line_height * lines.len() as f32;
+    let (width, height) = (img.width() as f32, img.height() as f32);
+    let top = match config.position {
+        TextPosition::TopLeft | TextPosition::TopRight => margin,
+        TextPosition::BottomLeft | TextPosition::BottomRight => height - margin - block_height,
+    };
+    for (i, line) in lines.iter().enumerate() {
+        let x = match config.position {
+            TextPosition::TopLeft | TextPosition::BottomLeft => margin,
+            TextPosition::TopRight | TextPosition::BottomRight => width - margin - line_width(line, scale_px),
+        };
+        let baseline = top + line_height * i as f32 + metrics.ascent();
+        if config.shadow {
+            draw_line(img, line, x + shadow_offset, baseline + shadow_offset, scale_px, shadow);
+        }
+        draw_line(img, line, x, baseline, scale_px, color);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn info() -> OverlayInfo {
+        OverlayInfo {
+            weather: Some("北京 晴 25°C".to_string()),
+            local_time: Local.with_ymd_and_hms(2025, 6, 20, 20, 5, 0).unwrap(),
+            capture_time: Some(Utc.with_ymd_and_hms(2025, 6, 20, 11, 50, 0).unwrap()),
+            satellite: "Himawari-9".to_string(),
+        }
+    }
+
+    /// 被画过的像素的包围盒 (min_x, min_y, max_x, max_y)
+    fn drawn_bounds(img: &RgbaImage) -> Option<(u32, u32, u32, u32)> {
+        img.enumerate_pixels()
+            .filter(|(_, _, p)| p[0] > 0)
+            .fold(None, |acc, (x, y, _)| match acc {
+                None => Some((x, y, x, y)),
+                Some((x0, y0, x1, y1)) => Some((x0.min(x), y0.min(y), x1.max(x), y1.max(y))),
+            })
+    }
+
+    #[test]
+    fn line_mask_does_not_darken_where_strokes_meet() {
+        let mut mask = LineMask::new(20, 10);
+        mask.stroke((2.0, 5.0), (10.0, 5.0), 2.0);
+        mask.stroke((10.0, 5.0), (18.0, 5.0), 2.0);
+        // 整个像素都在线内为 255，两条线段相接处不叠加
+        assert_eq!(mask.coverage[5 * 20 + 10], 255);
+        assert_eq!(mask.coverage[5 * 20 + 5], 255);
+        assert_eq!(mask.coverage[0], 0);
+
+        let mut img = RgbaImage::from_pixel(20, 10, Rgba([0, 0, 0, 255]));
+        mask.composite(&mut img, Rgba([200, 200, 200, 255]), 0.5);
+        assert_eq!(img.get_pixel(5, 5)[0], 100);
+        assert_eq!(img.get_pixel(10, 5)[0], 100);
+        assert_eq!(img.get_pixel(10, 0)[0], 0);
+    }
+
+    #[test]
+    fn parse_color_accepts_rgb_and_rgba() {
+        assert_eq!(parse_color("#FFFFFF").unwrap(), Rgba([255, 255, 255, 255]));
+        assert_eq!(parse_color("#12ab3C80").unwrap(), Rgba([0x12, 0xab, 0x3c, 0x80]));
+        // # 可省略，首尾空白忽略
+        assert_eq!(parse_color(" 000000 ").unwrap(), Rgba([0, 0, 0, 255]));
+    }
+
+    #[test]
+    fn parse_color_rejects_bad_values() {
+        for value in ["", "#FFF", "#FFFFF", "#FFFFFFF", "#GGGGGG", "#FFFFFF0Z", "#+1+1+1", "#数字"] {
+            assert!(parse_color(value).is_err(), "{}", value);
+        }
+    }
+
+    #[test]
+    fn validate_checks_font_size_and_color() {
+        let config = TextOverlayConfig::default();
+        assert!(config.validate().is_ok());
+        for font_size in [8.0, 200.0] {
+            assert!(TextOverlayConfig { font_size, ..config.clone() }.validate().is_ok());
+        }
+        for font_size in [7.9, 200.5, 0.0, f32::NAN] {
+            assert!(TextOverlayConfig { font_size, ..config.clone() }.validate().is_err());
+        }
+        let color = "white".to_string();
+        assert!(TextOverlayConfig { color, ..config }.validate().is_err());
+    }
+
+    #[test]
+    fn lines_follow_settings_in_order() {
+        let info = info();
+        let all = TextOverlayConfig {
+            show_capture_time: true,
+            show_satellite: true,
+            ..TextOverlayConfig::default()
+        };
+        assert_eq!(
+            info.lines(&all),
+            ["北京 晴 25°C", "2025-06-20 20:05", "Himawari-9 2025-06-20 11:50 UTC"]
+        );
+        let capture_only = TextOverlayConfig {
+            show_weather: false,
+            show_local_time: false,
+            show_capture_time: true,
+            ..TextOverlayConfig::default()
+        };
+        assert_eq!(info.lines(&capture_only), ["2025-06-20 11:50 UTC"]);
+        // 没有天气和拍摄时间时跳过这两行
+        let bare = OverlayInfo {
+            weather: None,
+            capture_time: None,
+            ..info
+        };
+        assert_eq!(bare.lines(&all), ["2025-06-20 20:05", "Himawari-9"]);
+    }
+
+    #[test]
+    fn disabled_monitor_has_no_overlay() {
+        let enabled = TextOverlayConfig {
+            enabled: true,
+            ..TextOverlayConfig::default()
+        };
+        let overlays = TextOverlays {
+            configs: HashMap::from([(0, enabled), (1, TextOverlayConfig::default())]),
+            info: info(),
+        };
+        assert_eq!(overlays.for_monitor(0).unwrap().1.len(), 2);
+        assert!(overlays.for_monitor(1).is_none());
+        assert!(overlays.for_monitor(2).is_none());
+    }
+
+    #[test]
+    fn cjk_text_uses_the_bundled_font() {
+        for c in ['晴', '雨', '曇', 'り', 'ア', '，'] {
+            assert_ne!(bundled_cjk_font().glyph_id(c).0, 0, "{}", c);
+            assert!(std::ptr::eq(font_for(c), bundled_cjk_font()), "{}", c);
+        }
+        assert!(std::ptr::eq(font_for('A'), bundled_font()));
+        assert!(line_width("晴", PxScale::from(20.0)) > 10.0);
+    }
+
+    #[test]
+    fn text_is_drawn_in_the_chosen_corner() {
+        let lines = vec!["晴 25°C".to_string(), "くもり".to_string()];
+        for position in [
+            TextPosition::TopLeft,
+            TextPosition::TopRight,
+            TextPosition::BottomLeft,
+            TextPosition::BottomRight,
+        ] {
+            let mut img = RgbaImage::from_pixel(400, 200, Rgba([0, 0, 0, 255]));
+            let config = TextOverlayConfig {
+                position,
+                font_size: 20.0,
+                shadow: false,
+                ..TextOverlayConfig::default()
+            };
+            draw_text(&mut img, &lines, &config, 1.0).unwrap();
+            let (x0, y0, x1, y1) = drawn_bounds(&img).expect("没有画出文字");
+            let left = matches!(position, TextPosition::TopLeft | TextPosition::BottomLeft);
+            let top = matches!(position, TextPosition::TopLeft | TextPosition::TopRight);
+            assert_eq!(x1 < 200, left, "{:?}", position);
+            assert_eq!(x0 >= 200, !left, "{:?}", position);
+            assert_eq!(y1 < 100, top, "{:?}", position);
+            assert_eq!(y0 >= 100, !top, "{:?}", position);
+            // 两行都画出来了，高度超过一行
+            assert!(y1 - y0 > 25, "{:?}", position);
+        }
+    }
+}
@@ -0,0 +1,292 @@
+use serde::Serialize;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Condvar, Mutex};
+use tracing::{info, info_span};
+
+use crate::download::CancelToken;
+use crate::error::{EarthError, EarthResult};
+
+/// 每次运行的编号，从 1 开始递增
+pub type RunId = u64;
+
+/// 定时下载最新地球图像的流程 key，睡眠或断网时只取消它
+pub const UPDATE_KEY: &str = "update";
+
+/// 正在运行的流程，供前端展示和取消
+#[derive(Debug, Clone, Serialize)]
+pub struct RunInfo {
+    pub id: RunId,
+    pub key: String,
+}
+
+/// 一次运行的结果，合并进来的调用方在这里等待
+#[derive(Default)]
+struct Outcome {
+    result: Mutex<Option<EarthResult<String>>>,
+    done: Condvar,
+}
+
+impl Outcome {
+    fn wait(&self) -> EarthResult<String> {
+        let mut result = self.result.lock().unwrap();
+        loop {
+            if let Some(result) = result.as_ref() {
+                return result.clone();
+            }
+            result = self.done.wait(result).unwrap();
+        }
+    }
+
+    fn set(&self, value: EarthResult<String>) {
+        *self.result.lock().unwrap() = Some(value);
+        self.done.notify_all();
+    }
+
+    fn is_set(&self) -> bool {
+        self.result.lock().unwrap().is_some()
+    }
+}
+
+/// 流程结束时清理运行状态；f panic 时也会执行，避免排队和合并等待的调用方永远阻塞
+struct Finish<'a> {
+    pipeline: &'a Pipeline,
+    outcome: Arc<Outcome>,
+}
+
+impl Drop for Finish<'_> {
+    fn drop(&mut self) {
+        if !self.outcome.is_set() {
+            self.outcome.set(Err(EarthError::internal("流程异常退出", "panic")));
+        }
+        *self.pipeline.running.lock().unwrap_or_else(|e| e.into_inner()) = None;
+        self.pipeline.idle.notify_all();
+    }
+}
+
+struct Running {
+    info: RunInfo,
+    cancel: CancelToken,
+    outcome: Arc<Outcome>,
+}
+
+/// 下载与设置壁纸的单飞保护
+///
+/// 同一时间只运行一个流程，避免两次运行同时写 tiles/ 等文件。
+/// 同一 key 的流程正在运行时，新的调用不再重新执行，而是等待并共享它的结果；
+/// key 不同时排队，等前一个结束后再运行。
+#[derive(Default)]
+pub struct Pipeline {
+    running: Mutex<Option<Running>>,
+    idle: Condvar,
+    next_id: AtomicU64,
+}
+
+impl Pipeline {
+    /// 运行一个流程，f 拿到本次的编号和取消标记；会阻塞，需在阻塞线程中调用
+    pub fn run<F>(&self, key: &str, f: F) -> EarthResult<String>
+    where
+        F: FnOnce(RunId, &CancelToken) -> EarthResult<String>,
+    {
+        self.run_linked(key, &CancelToken::default(), f)
+    }
+
+    /// 同 [`Pipeline::run`]，但本次的取消标记派生自 parent，parent 取消时本次也会停下
+    pub fn run_linked<F>(&self, key: &str, parent: &CancelToken, f: F) -> EarthResult<String>
+    where
+        F: FnOnce(RunId, &CancelToken) -> EarthResult<String>,
+    {
+        let mut running = self.running.lock().unwrap();
+        loop {
+            match running.as_ref() {
+                Some(current) if current.info.key == key => {
+                    let (id, outcome) = (current.info.id, current.outcome.clone());
+                    drop(running);
+                    info!(run = id, key, "相同流程正在运行，合并等待结果");
+                    return outcome.wait();
+                }
+                Some(_) => running = self.idle.wait(running).unwrap(),
+                None => break,
+            }
+        }
+
+        let id = self.next_id.fetch_add(1, Ordering::SeqCst) + 1;
+        let cancel = parent.child();
+        let outcome = Arc::new(Outcome::default());
+        *running = Some(Running {
+            info: RunInfo {
+                id,
+                key: key.to_string(),
+            },
+            cancel: cancel.clone(),
+            outcome: outcome.clone(),
+        });
+        drop(running);
+
+        let finish = Finish {
+            pipeline: self,
+            outcome: outcome.clone(),
+        };
+        let result = {
+            let _span = info_span!("run", id, key).entered();
+            f(id, &cancel)
+        };
+        outcome.set(result.clone());
+        drop(finish);
+        result
+    }
+
+    /// 取消正在运行的流程；指定 run_id 时只在编号一致时取消，返回被取消的编号
+    pub fn cancel(&self, run_id: Option<RunId>) -> Option<RunId> {
+        let running = self.running.lock().unwrap();
+        let current = running.as_ref()?;
+        if run_id.is_some_and(|id| id != current.info.id) {
+            return None;
+        }
+        current.cancel.cancel();
+        info!(run = current.info.id, key = %current.info.key, "流程已取消");
+        Some(current.info.id)
+    }
+
+    /// 只在正在运行的流程 key 一致时取消，返回被取消的编号
+    pub fn cancel_key(&self, key: &str) -> Option<RunId> {
+        let id = self.current().filter(|run| run.key == key)?.id;
+        self.cancel(Some(id))
+    }
+
+    pub fn current(&self) -> Option<RunInfo> {
+        self.running.lock().unwrap().as_ref().map(|r| r.info.clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::panic::{self, AssertUnwindSafe};
+    use std::sync::mpsc;
+    use std::thread;
+    use std::time::Duration;
+
+    /// 等到 pipeline 上有 key 对应的流程在运行
+    fn wait_running(pipeline: &Pipeline, key: &str) -> RunId {
+        loop {
+            if let Some(run) = pipeline.current().filter(|run| run.key == key) {
+                return run.id;
+            }
+            thread::sleep(Duration::from_millis(5));
+        }
+    }
+
+    #[test]
+    fn same_key_joins_running_result() {
+        let pipeline = Arc::new(Pipeline::default());
+        let (release, gate) = mpsc::channel::<()>();
+        let first = {
+            let pipeline = pipeline.clone();
+            thread::spawn(move || {
+                pipeline.run("update", |_, _| {
+                    gate.recv().unwrap();
+                    Ok("first".to_string())
+                })
+            })
+        };
+        wait_running(&pipeline, "update");
+        let joined = {
+            let pipeline = pipeline.clone();
+            thread::spawn(move || pipeline.run("update", |_, _| Ok("second".to_string())))
+        };
+        thread::sleep(Duration::from_millis(50));
+        release.send(()).unwrap();
+        assert_eq!(first.join().unwrap().unwrap(), "first");
+        assert_eq!(joined.join().unwrap().unwrap(), "first");
+    }
+
+    #[test]
+    fn different_key_waits_for_running_one() {
+        let pipeline = Arc::new(Pipeline::default());
+        let (release, gate) = mpsc::channel::<()>();
+        let first = {
+            let pipeline = pipeline.clone();
+            thread::spawn(move || {
+                pipeline.run("update", |_, _| {
+                    gate.recv().unwrap();
+                    Ok(String::new())
+                })
+            })
+        };
+        let first_id = wait_running(&pipeline, "update");
+        let queued = {
+            let pipeline = pipeline.clone();
+            thread::spawn(move || pipeline.run("apply", |id, _| Ok(id.to_string())))
+        };
+        thread::sleep(Duration::from_millis(50));
+        assert_eq!(pipeline.current().unwrap().key, "update");
+        release.send(()).unwrap();
+        first.join().unwrap().unwrap();
+        assert_eq!(queued.join().unwrap().unwrap(), (first_id + 1).to_string());
+        assert!(pipeline.current().is_none());
+    }
+
+    #[test]
+    fn panic_releases_waiters() {
+        let pipeline = Arc::new(Pipeline::default());
+        let (release, gate) = mpsc::channel::<()>();
+        let panicking = {
+            let pipeline = pipeline.clone();
+            thread::spawn(move || {
+                panic::catch_unwind(AssertUnwindSafe(|| {
+                    pipeline.run("update", |_, _| {
+                        gate.recv().unwrap();
+                        panic!("boom")
+                    })
+                }))
+            })
+        };
+        wait_running(&pipeline, "update");
+        let joined = {
+            let pipeline = pipeline.clone();
+            thread::spawn(move || pipeline.run("update", |_, _| Ok(String::new())))
+        };
+        let queued = {
+            let pipeline = pipeline.clone();
+            thread::spawn(move || pipeline.run("apply", |_, _| Ok("applied".to_string())))
+        };
+        thread::sleep(Duration::from_millis(50));
+        release.send(()).unwrap();
+        assert!(panicking.join().unwrap().is_err());
+        assert!(matches!(joined.join().unwrap(), Err(EarthError::Internal { .. })));
+        assert_eq!(queued.join().unwrap().unwrap(), "applied");
+        assert!(pipeline.current().is_none());
+    }
+
+    #[test]
+    fn linked_run_follows_parent_cancel() {
+        let pipeline = Pipeline::default();
+        let parent = CancelToken::default();
+        parent.cancel();
+        let result = pipeline.run_linked("capture", &parent, |_, cancel| {
+            cancel.check()?;
+            Ok(String::new())
+        });
+        assert!(matches!(result, Err(EarthError::Cancelled { .. })));
+    }
+
+    #[test]
+    fn cancel_key_skips_other_runs() {
+        let pipeline = Arc::new(Pipeline::default());
+        let (release, gate) = mpsc::channel::<()>();
+        let apply = {
+            let pipeline = pipeline.clone();
+            thread::spawn(move || {
+                pipeline.run("apply", |_, cancel| {
+                    gate.recv().unwrap();
+                    cancel.check()?;
+                    Ok(String::new())
+                })
+            })
+        };
+        wait_running(&pipeline, "apply");
+        assert_eq!(pipeline.cancel_key(UPDATE_KEY), None);
+        release.send(()).unwrap();
+        assert!(apply.join().unwrap().is_ok());
+    }
+}
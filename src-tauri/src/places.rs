@@ -0,0 +1,139 @@
+use image::{Rgba, RgbaImage};
+use serde::{Deserialize, Serialize};
+use tracing::debug;
+
+use crate::error::{EarthError, EarthResult};
+use crate::overlay;
+use crate::projection;
+
+/// 地点名称的最大字符数，太长的标签会挡住大片云图
+const MAX_NAME_CHARS: usize = 40;
+
+fn default_color() -> String {
+    "#FF5050".to_string()
+}
+
+/// 标注的地点，保存在 settings.json 的 places 字段
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Place {
+    /// 新增时为 0，保存时由后端分配
+    #[serde(default)]
+    pub id: u32,
+    pub name: String,
+    pub latitude: f64,
+    pub longitude: f64,
+    /// #RRGGBB
+    #[serde(default = "default_color")]
+    pub color: String,
+}
+
+impl Place {
+    pub fn validate(&self) -> EarthResult<()> {
+        if self.name.trim().is_empty() {
+            return Err(EarthError::invalid("地点名称不能为空"));
+        }
+        if self.name.chars().count() > MAX_NAME_CHARS {
+            return Err(EarthError::invalid("地点名称不能超过 40 个字符"));
+        }
+        if !(-90.0..=90.0).contains(&self.latitude) || !(-180.0..=180.0).contains(&self.longitude) {
+            return Err(EarthError::invalid("经纬度超出范围"));
+        }
+        overlay::parse_color(&self.color).map(|_| ())
+    }
+
+    /// 在卫星图上是否可见，位于地球背面时为 false
+    pub fn visible(&self) -> bool {
+        projection::to_disk(self.latitude, self.longitude).is_some()
+    }
+}
+
+/// 标注画在哪里
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MarkerTarget {
+    /// 下载时画进拼接大图，历史记录、延时视频和之后的壁纸都带着标注
+    Capture,
+    /// 设置壁纸时按显示器分辨率画在裁剪后的壁纸上，改动后重新设置壁纸即可生效
+    #[default]
+    Wallpaper,
+}
+
+/// 地点标注的显示设置，保存在 settings.json 的 place_markers 字段
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PlaceMarkerConfig {
+    pub enabled: bool,
+    pub target: MarkerTarget,
+    pub show_labels: bool,
+    /// 标签字号，画在壁纸上时为显示器物理像素，画进大图时为大图像素
+    pub label_size: f32,
+}
+
+impl Default for PlaceMarkerConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            target: MarkerTarget::Wallpaper,
+            show_labels: true,
+            label_size: 18.0,
+        }
+    }
+}
+
+impl PlaceMarkerConfig {
+    pub fn validate(&self) -> EarthResult<()> {
+        if !(8.0..=200.0).contains(&self.label_size) {
+            return Err(EarthError::invalid("字号需在 8 到 200 之间"));
+        }
+        Ok(())
+    }
+}
+
+/// 新地点分配比现有最大 id 大 1 的 id，已有 id 的按 id 替换；返回保存后的地点
+pub fn upsert(places: &mut Vec<Place>, mut place: Place) -> Place {
+    match places.iter_mut().find(|p| place.id != 0 && p.id == place.id) {
+        Some(existing) => *existing = place.clone(),
+        None => {
+            place.id = places.iter().map(|p| p.id).max().unwrap_or(0) + 1;
+            places.push(place.clone());
+        }
+    }
+    place
+}
+
+/// 一次绘制用到的设置和地点
+#[derive(Debug, Clone)]
+pub struct PlaceMarkers {
+    pub config: PlaceMarkerConfig,
+    pub places: Vec<Place>,
+}
+
+impl PlaceMarkers {
+    /// 把地点画到图上，位于地球背面的地点跳过
+    ///
+    /// to_pixel 把全圆盘归一化坐标换算为这张图上的像素坐标；scale 是图像像素与标签字号单位之比。
+    pub fn draw(
+        &self,
+        img: &mut RgbaImage,
+        scale: f64,
+        to_pixel: impl Fn((f64, f64)) -> (f64, f64),
+    ) -> EarthResult<()> {
+        let px = (self.config.label_size as f64 * scale).max(6.0);
+        let radius = (px * 0.3).max(2.0);
+        let mut drawn = 0;
+        for place in &self.places {
+            let Some(uv) = projection::to_disk(place.latitude, place.longitude) else {
+                continue;
+            };
+            let color = overlay::parse_color(&place.color)?;
+            let (x, y) = to_pixel(uv);
+            overlay::draw_dot(img, x, y, radius, color);
+            if self.config.show_labels {
+                overlay::draw_label(img, &place.name, x, y, radius * 2.0, px as f32, Rgba([255, 255, 255, 255]));
+            }
+            drawn += 1;
+        }
+        debug!(drawn, total = self.places.len(), "已绘制地点标注");
+        Ok(())
+    }
+}
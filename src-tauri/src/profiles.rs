@@ -0,0 +1,386 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+use tracing::warn;
+
+use crate::atomic;
+use crate::error::{EarthError, EarthResult};
+use crate::i18n;
+use crate::settings::Settings;
+
+/// 设置方案保存在图片目录的 profiles.json，与 settings.json 分开，切换方案不会改动方案本身
+const PROFILES_FILE: &str = "profiles.json";
+
+/// 方案名的最大字符数，托盘菜单里放得下
+const MAX_NAME_CHARS: usize = 32;
+
+/// 导出文件的格式，按扩展名选择：.toml 为 TOML，其余为 JSON
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FileFormat {
+    Json,
+    Toml,
+}
+
+impl FileFormat {
+    pub fn from_path(path: &Path) -> Self {
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some(ext) if ext.eq_ignore_ascii_case("toml") => FileFormat::Toml,
+            _ => FileFormat::Json,
+        }
+    }
+
+    pub fn extension(self) -> &'static str {
+        match self {
+            FileFormat::Json => "json",
+            FileFormat::Toml => "toml",
+        }
+    }
+}
+
+/// 导出文件的内容
+///
+/// 天气 API Key 保存在系统钥匙串中，不在设置里，因此不会被导出；导入后需在每台机器上重新填写。
+#[derive(Debug, Serialize, Deserialize)]
+struct ExportFile {
+    app: String,
+    exported_at: DateTime<Utc>,
+    settings: Value,
+}
+
+const EXPORT_APP: &str = "ImmediateEarth";
+
+/// TOML 没有 null，导出前去掉值为 null 的键（导入时缺少的字段按默认值即 None 处理）
+fn strip_nulls(value: Value) -> Value {
+    match value {
+        Value::Object(map) => Value::Object(
+            map.into_iter()
+                .filter(|(_, v)| !v.is_null())
+                .map(|(k, v)| (k, strip_nulls(v)))
+                .collect(),
+        ),
+        Value::Array(items) => Value::Array(items.into_iter().map(strip_nulls).collect()),
+        other => other,
+    }
+}
+
+/// 把设置序列化为导出文件的内容
+pub fn export(settings: &Settings, format: FileFormat, now: DateTime<Utc>) -> EarthResult<String> {
+    let file = ExportFile {
+        app: EXPORT_APP.to_string(),
+        exported_at: now,
+        settings: strip_nulls(Value::Object(settings.to_entries()?)),
+    };
+    match format {
+        FileFormat::Json => serde_json::to_string_pretty(&file).map_err(|e| EarthError::internal("序列化设置失败", e)),
+        FileFormat::Toml => toml::to_string_pretty(&file).map_err(|e| EarthError::internal("序列化设置失败", e)),
+    }
+}
+
+/// 解析导出文件并校验其中的设置
+pub fn parse(body: &str, format: FileFormat) -> EarthResult<Settings> {
+    let file: ExportFile = match format {
+        FileFormat::Json => serde_json::from_str(body).map_err(|e| EarthError::invalid(i18n::trf("设置文件格式错误: {}", &[&e]))),
+        FileFormat::Toml => toml::from_str(body).map_err(|e| EarthError::invalid(i18n::trf("设置文件格式错误: {}", &[&e]))),
+    }?;
+    if file.app != EXPORT_APP {
+        return Err(EarthError::invalid("不是本应用导出的设置文件"));
+    }
+    Settings::from_import(file.settings)
+}
+
+/// 读取并校验导出文件
+pub fn read(path: &Path) -> EarthResult<Settings> {
+    let body = fs::read_to_string(path).map_err(|e| EarthError::io(path, "读取设置文件失败", e))?;
+    parse(&body, FileFormat::from_path(path))
+}
+
+/// 导入或切换前后不同的一项设置，嵌套字段用点号连接，如 map_overlay.color
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct SettingChange {
+    pub key: String,
+    /// 不存在时为 null
+    pub current: Value,
+    pub incoming: Value,
+}
+
+/// 把嵌套对象展开为「点号路径 -> 值」，数组整体作为一个值比较
+fn flatten(prefix: &str, value: &Value, out: &mut BTreeMap<String, Value>) {
+    match value {
+        // 空对象没有字段，不产生条目
+        Value::Object(map) => {
+            for (key, value) in map {
+                let path = if prefix.is_empty() { key.clone() } else { format!("{}.{}", prefix, key) };
+                flatten(&path, value, out);
+            }
+        }
+        _ => {
+            out.insert(prefix.to_string(), value.clone());
+        }
+    }
+}
+
+/// 列出两份设置之间所有不同的字段，按路径排序
+pub fn diff(current: &Settings, incoming: &Settings) -> EarthResult<Vec<SettingChange>> {
+    let mut before = BTreeMap::new();
+    let mut after = BTreeMap::new();
+    flatten("", &Value::Object(current.to_entries()?), &mut before);
+    flatten("", &Value::Object(incoming.to_entries()?), &mut after);
+    let mut keys: Vec<&String> = before.keys().chain(after.keys()).collect();
+    keys.sort();
+    keys.dedup();
+    Ok(keys
+        .into_iter()
+        .filter(|key| key.as_str() != "schema_version")
+        .filter_map(|key| {
+            let current = before.get(key).cloned().unwrap_or(Value::Null);
+            let incoming = after.get(key).cloned().unwrap_or(Value::Null);
+            (current != incoming).then(|| SettingChange {
+                key: key.clone(),
+                current,
+                incoming,
+            })
+        })
+        .collect())
+}
+
+/// 命名的设置方案，如「公司双屏」「笔记本用电池」
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Profiles {
+    /// 最近一次切换到的方案，之后手动改过设置也不会清除
+    pub active: Option<String>,
+    pub profiles: BTreeMap<String, Settings>,
+}
+
+impl Profiles {
+    /// 读取保存的方案，文件不存在时为空；无法解析的文件改名备份后从空开始，
+    /// 避免下次保存时把原有方案覆盖掉
+    pub fn load(base: &Path) -> EarthResult<Self> {
+        let path = base.join(PROFILES_FILE);
+        let body = match fs::read_to_string(&path) {
+            Ok(body) => body,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Profiles::default()),
+            Err(e) => return Err(EarthError::io(&path, "读取设置方案失败", e)),
+        };
+        // 方案逐个按导入规则解析，旧版本的方案也能迁移，解析不了的方案跳过
+        let raw = match serde_json::from_str::<Map<String, Value>>(&body) {
+            Ok(raw) => raw,
+            Err(e) => {
+                let backup = base.join(format!("profiles-{}.json.bak", Utc::now().format("%Y%m%d-%H%M%S")));
+                fs::rename(&path, &backup).map_err(|e| EarthError::io(&path, "备份无法解析的设置方案失败", e))?;
+                warn!(backup = %backup.display(), error = %e, "设置方案文件无法解析，已备份");
+                return Ok(Profiles::default());
+            }
+        };
+        let profiles = raw
+            .get("profiles")
+            .and_then(Value::as_object)
+            .into_iter()
+            .flatten()
+            .filter_map(|(name, value)| {
+                Settings::from_import(value.clone())
+                    .inspect_err(|e| warn!(profile = %name, error = %e, "设置方案无法解析，跳过"))
+                    .ok()
+                    .map(|settings| (name.clone(), settings))
+            })
+            .collect();
+        let active = raw.get("active").and_then(Value::as_str).map(str::to_string);
+        Ok(Profiles { active, profiles })
+    }
+
+    pub fn save(&self, base: &Path) -> EarthResult<()> {
+        let path = base.join(PROFILES_FILE);
+        let json = serde_json::to_string_pretty(self).map_err(|e| EarthError::internal("序列化设置方案失败", e))?;
+        atomic::write_bytes(&path, json, "保存设置方案失败")
+    }
+
+    pub fn get(&self, name: &str) -> EarthResult<&Settings> {
+        self.profiles
+            .get(name)
+            .ok_or_else(|| EarthError::invalid(i18n::trf("设置方案不存在: {}", &[&name])))
+    }
+
+    /// 保存方案，同名方案直接覆盖；返回去掉首尾空白后的方案名
+    pub fn insert(&mut self, name: &str, settings: Settings) -> EarthResult<String> {
+        let name = name.trim();
+        if name.is_empty() || name.chars().count() > MAX_NAME_CHARS {
+            return Err(EarthError::invalid("方案名称需为 1 到 32 个字符"));
+        }
+        self.profiles.insert(name.to_string(), settings);
+        Ok(name.to_string())
+    }
+
+    pub fn remove(&mut self, name: &str) {
+        self.profiles.remove(name);
+        if self.active.as_deref() == Some(name) {
+            self.active = None;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::location::ManualLocation;
+    use crate::overlay::{TextOverlayConfig, TextPosition};
+    use crate::places::Place;
+    use crate::retention::RetentionPolicy;
+    use crate::weather::WeatherLocation;
+    use chrono::TimeZone;
+    use serde_json::json;
+
+    /// 尽量多的字段偏离默认值，包括嵌套对象、数组、按显示器索引的表和值为 None 的可选字段
+    fn customized() -> Settings {
+        let mut settings = Settings {
+            level: 8,
+            auto_set_wallpaper: true,
+            monitor_indexes: vec![0, 2],
+            rate_limit_kb: 512,
+            retention: RetentionPolicy {
+                keep_last: None,
+                keep_days: Some(7),
+                max_total_mb: Some(2048),
+            },
+            weather_location: Some(WeatherLocation::Coordinates {
+                latitude: 39.9,
+                longitude: 116.4,
+            }),
+            manual_location: Some(ManualLocation {
+                latitude: 35.68,
+                longitude: 139.69,
+            }),
+            places: vec![Place {
+                id: 1,
+                name: "東京".to_string(),
+                latitude: 35.68,
+                longitude: 139.69,
+                color: "#FF0000".to_string(),
+            }],
+            ..Settings::default()
+        };
+        settings.text_overlays.insert(
+            1,
+            TextOverlayConfig {
+                enabled: true,
+                position: TextPosition::TopLeft,
+                font_size: 32.5,
+                ..TextOverlayConfig::default()
+            },
+        );
+        settings.map_overlay.enabled = true;
+        settings.map_overlay.color = "#00FF00".to_string();
+        settings
+    }
+
+    fn now() -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2025, 6, 20, 12, 0, 0).unwrap()
+    }
+
+    #[test]
+    fn json_export_round_trips() {
+        for settings in [Settings::default(), customized()] {
+            let body = export(&settings, FileFormat::Json, now()).unwrap();
+            assert_eq!(parse(&body, FileFormat::Json).unwrap(), settings);
+        }
+    }
+
+    #[test]
+    fn toml_export_round_trips() {
+        for settings in [Settings::default(), customized()] {
+            let body = export(&settings, FileFormat::Toml, now()).unwrap();
+            assert_eq!(parse(&body, FileFormat::Toml).unwrap(), settings);
+        }
+    }
+
+    #[test]
+    fn parse_rejects_foreign_files() {
+        let body = json!({ "app": "Other", "exported_at": now(), "settings": {} }).to_string();
+        assert!(matches!(parse(&body, FileFormat::Json), Err(EarthError::InvalidInput { .. })));
+        assert!(parse("app = ", FileFormat::Toml).is_err());
+    }
+
+    #[test]
+    fn strip_nulls_recurses_into_objects_and_arrays() {
+        let stripped = strip_nulls(json!({
+            "a": null,
+            "b": { "c": null, "d": 1 },
+            "e": [{ "f": null, "g": 2 }, 3],
+        }));
+        assert_eq!(stripped, json!({ "b": { "d": 1 }, "e": [{ "g": 2 }, 3] }));
+    }
+
+    #[test]
+    fn diff_lists_nested_keys() {
+        let current = Settings::default();
+        let mut incoming = current.clone();
+        incoming.level = 8;
+        incoming.map_overlay.color = "#00FF00".to_string();
+        incoming.monitor_indexes = vec![1];
+        let changes = diff(&current, &incoming).unwrap();
+        let keys: Vec<&str> = changes.iter().map(|c| c.key.as_str()).collect();
+        // 数组整体比较，嵌套对象只列出变了的字段
+        assert_eq!(keys, ["level", "map_overlay.color", "monitor_indexes"]);
+        assert_eq!(changes[1].current, json!("#FFD700"));
+        assert_eq!(changes[1].incoming, json!("#00FF00"));
+        assert!(diff(&current, &current).unwrap().is_empty());
+    }
+
+    #[test]
+    fn diff_reports_added_optional_fields() {
+        let current = Settings::default();
+        let incoming = Settings {
+            weather_location: Some(WeatherLocation::CityId { id: "101010100".to_string() }),
+            ..current.clone()
+        };
+        let changes = diff(&current, &incoming).unwrap();
+        let keys: Vec<&str> = changes.iter().map(|c| c.key.as_str()).collect();
+        assert_eq!(keys, ["weather_location.id", "weather_location.type"]);
+        // 原来为 None，展开后不存在的路径记为 null
+        assert!(changes.iter().all(|c| c.current.is_null()));
+    }
+
+    #[test]
+    fn load_missing_file_is_empty_and_saved_profiles_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        assert!(Profiles::load(dir.path()).unwrap().profiles.is_empty());
+        let mut saved = Profiles::default();
+        saved.insert(" 公司双屏 ", customized()).unwrap();
+        saved.active = Some("公司双屏".into());
+        saved.save(dir.path()).unwrap();
+        let loaded = Profiles::load(dir.path()).unwrap();
+        assert_eq!(loaded.active.as_deref(), Some("公司双屏"));
+        assert_eq!(
+            loaded.get("公司双屏").unwrap().to_entries().unwrap(),
+            customized().to_entries().unwrap()
+        );
+    }
+
+    #[test]
+    fn unreadable_profiles_file_is_backed_up_before_starting_over() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(PROFILES_FILE);
+        fs::write(&path, "{\"profiles\": {").unwrap();
+        let loaded = Profiles::load(dir.path()).unwrap();
+        assert!(loaded.profiles.is_empty());
+        // 原文件改名保留，之后保存不会覆盖它
+        assert!(!path.exists());
+        let backups: Vec<_> = fs::read_dir(dir.path())
+            .unwrap()
+            .map(|entry| entry.unwrap().path())
+            .filter(|p| p.to_string_lossy().ends_with(".json.bak"))
+            .collect();
+        assert_eq!(backups.len(), 1);
+        assert_eq!(fs::read_to_string(&backups[0]).unwrap(), "{\"profiles\": {");
+    }
+
+    #[test]
+    fn load_reports_read_errors() {
+        let dir = tempfile::tempdir().unwrap();
+        // 同名目录读不出内容，不能当成没有方案
+        fs::create_dir(dir.path().join(PROFILES_FILE)).unwrap();
+        assert!(matches!(Profiles::load(dir.path()), Err(EarthError::Io { .. })));
+    }
+}
@@ -0,0 +1,180 @@
+use serde::Serialize;
+use std::sync::Mutex;
+use std::time::Instant;
+use tauri::{AppHandle, Emitter};
+use tracing::info;
+
+use crate::error::{EarthError, EarthResult};
+use crate::pipeline::RunId;
+
+/// 每个阶段开始时发出，payload 为 [`UpdateProgress`]
+pub const PROGRESS_EVENT: &str = "update-progress";
+/// 整个流程结束（成功或失败）时发出，payload 为 [`UpdateSummary`]
+pub const SUMMARY_EVENT: &str = "update-summary";
+
+/// 更新流程中的阶段，序列化时以 stage 字段区分
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "stage", rename_all = "snake_case")]
+pub enum UpdateStage {
+    /// 确定要下载的拍摄时刻，slot 格式 YYYYMMDD_HHMM
+    ResolvingTimestamp { slot: String },
+    /// 开始下载瓦片，在第一个请求发出前发出，total 为瓦片总数
+    Downloading { total: usize },
+    /// 第 (x, y) 块瓦片下载完成，done/total 为已完成的瓦片数
+    TileDownloaded {
+        x: u32,
+        y: u32,
+        bytes: usize,
+        done: usize,
+        total: usize,
+    },
+    /// 瓦片拼接成大图
+    Stitching,
+    /// 在拼接好的大图上画经纬网和海岸线
+    Overlaying,
+    /// 给大图加黑边
+    Padding,
+    /// 按显示器裁剪，done/total 为已处理的显示器数
+    Cropping { monitor: usize, done: usize, total: usize },
+    /// 设置显示器壁纸
+    Applying { monitor: usize, done: usize, total: usize },
+}
+
+impl UpdateStage {
+    /// 汇总耗时用的阶段名，同名阶段的耗时累加
+    pub fn name(&self) -> &'static str {
+        match self {
+            UpdateStage::ResolvingTimestamp { .. } => "resolving_timestamp",
+            UpdateStage::Downloading { .. } | UpdateStage::TileDownloaded { .. } => "download",
+            UpdateStage::Stitching => "stitching",
+            UpdateStage::Overlaying => "overlaying",
+            UpdateStage::Padding => "padding",
+            UpdateStage::Cropping { .. } => "cropping",
+            UpdateStage::Applying { .. } => "applying",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct UpdateProgress {
+    pub run_id: RunId,
+    /// update 表示下载地球图像，apply 表示设置壁纸
+    pub operation: &'static str,
+    #[serde(flatten)]
+    pub stage: UpdateStage,
+    /// 从流程开始到现在的毫秒数
+    pub elapsed_ms: u64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct StageTiming {
+    pub stage: &'static str,
+    pub ms: u64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct UpdateSummary {
+    pub run_id: RunId,
+    pub operation: &'static str,
+    pub success: bool,
+    pub error: Option<EarthError>,
+    pub total_ms: u64,
+    /// 各阶段耗时，按首次出现的顺序排列
+    pub stages: Vec<StageTiming>,
+    /// 本次下载的瓦片总字节数
+    pub bytes: u64,
+}
+
+struct Timings {
+    current: Option<(&'static str, Instant)>,
+    stages: Vec<StageTiming>,
+    bytes: u64,
+}
+
+impl Timings {
+    /// 结束当前阶段，把耗时累加到同名阶段上
+    fn close(&mut self, now: Instant) {
+        if let Some((name, since)) = self.current.take() {
+            let ms = now.duration_since(since).as_millis() as u64;
+            match self.stages.iter_mut().find(|t| t.stage == name) {
+                Some(timing) => timing.ms += ms,
+                None => self.stages.push(StageTiming { stage: name, ms }),
+            }
+        }
+    }
+}
+
+/// 记录一次流程的各阶段耗时，并把进度以事件的形式发给前端
+pub struct ProgressReporter {
+    app: AppHandle,
+    run_id: RunId,
+    operation: &'static str,
+    started: Instant,
+    timings: Mutex<Timings>,
+}
+
+impl ProgressReporter {
+    pub fn new(app: &AppHandle, run_id: RunId, operation: &'static str) -> Self {
+        ProgressReporter {
+            app: app.clone(),
+            run_id,
+            operation,
+            started: Instant::now(),
+            timings: Mutex::new(Timings {
+                current: None,
+                stages: Vec::new(),
+                bytes: 0,
+            }),
+        }
+    }
+
+    /// 进入某个阶段；与上一个阶段同名时继续计时
+    pub fn stage(&self, stage: UpdateStage) {
+        let now = Instant::now();
+        {
+            let mut timings = self.timings.lock().unwrap();
+            if let UpdateStage::TileDownloaded { bytes, .. } = &stage {
+                timings.bytes += *bytes as u64;
+            }
+            if timings.current.is_none_or(|(name, _)| name != stage.name()) {
+                timings.close(now);
+                timings.current = Some((stage.name(), now));
+            }
+        }
+        let _ = self.app.emit(
+            PROGRESS_EVENT,
+            UpdateProgress {
+                run_id: self.run_id,
+                operation: self.operation,
+                stage,
+                elapsed_ms: now.duration_since(self.started).as_millis() as u64,
+            },
+        );
+    }
+
+    /// 流程结束，发出汇总事件
+    pub fn finish<T>(&self, result: &EarthResult<T>) {
+        let now = Instant::now();
+        let mut timings = self.timings.lock().unwrap();
+        timings.close(now);
+        let summary = UpdateSummary {
+            run_id: self.run_id,
+            operation: self.operation,
+            success: result.is_ok(),
+            error: result.as_ref().err().cloned(),
+            total_ms: now.duration_since(self.started).as_millis() as u64,
+            stages: timings.stages.clone(),
+            bytes: timings.bytes,
+        };
+        info!(
+            run = summary.run_id,
+            operation = summary.operation,
+            success = summary.success,
+            total_ms = summary.total_ms,
+            bytes = summary.bytes,
+            stages = ?summary.stages,
+            "流程结束"
+        );
+        let _ = self.app.emit(SUMMARY_EVENT, summary);
+    }
+}
@@ -0,0 +1,97 @@
+/// Himawari 星下点经度
+const SUB_LONGITUDE: f64 = 140.7;
+
+/// 地球赤道半径、极半径和卫星到地心的距离，单位 km
+const EQUATOR_RADIUS: f64 = 6378.137;
+const POLAR_RADIUS: f64 = 6356.7523;
+const SATELLITE_DISTANCE: f64 = 42164.0;
+
+/// 2 km 分辨率全圆盘图（5500 像素）的列/行偏移和缩放系数，见 CGMS LRIT/HRIT 全球规范
+const FULL_DISK_PIXELS: f64 = 5500.0;
+const COFF: f64 = 2750.5;
+const CFAC: f64 = 20466275.0;
+
+/// 加黑边大图每侧黑边宽度与原图宽度之比
+pub const PADDING: f64 = 0.1;
+
+/// 经纬度投影到全圆盘图上的归一化坐标，(0, 0) 为左上角、(1, 1) 为右下角
+///
+/// 与瓦片拼接后的大图分辨率无关；位于地球背面、卫星看不到时返回 None。
+pub fn to_disk(latitude: f64, longitude: f64) -> Option<(f64, f64)> {
+    let a2 = EQUATOR_RADIUS * EQUATOR_RADIUS;
+    let b2 = POLAR_RADIUS * POLAR_RADIUS;
+    let lat = latitude.to_radians();
+    let dlon = (longitude - SUB_LONGITUDE).to_radians();
+
+    // 地理纬度换算为地心纬度，再求地表点的地心坐标
+    let c_lat = (b2 / a2 * lat.tan()).atan();
+    let rl = POLAR_RADIUS / (1.0 - (a2 - b2) / a2 * c_lat.cos().powi(2)).sqrt();
+    let px = rl * c_lat.cos() * dlon.cos();
+    let py = rl * c_lat.cos() * dlon.sin();
+    let pz = rl * c_lat.sin();
+
+    // 卫星指向地表点的向量与地表法线同向时才可见
+    let r1 = SATELLITE_DISTANCE - px;
+    if r1 * px / a2 - py * py / a2 - pz * pz / b2 <= 0.0 {
+        return None;
+    }
+    let rn = (r1 * r1 + py * py + pz * pz).sqrt();
+    let x = (py / r1).atan().to_degrees();
+    let y = (-pz / rn).asin().to_degrees();
+
+    let column = COFF + x * CFAC / 65536.0;
+    let line = COFF + y * CFAC / 65536.0;
+    Some(((column - 0.5) / FULL_DISK_PIXELS, (line - 0.5) / FULL_DISK_PIXELS))
+}
+
+/// 归一化坐标换算为加黑边大图（边长 width）上的像素坐标
+pub fn to_padded_pixel((u, v): (f64, f64), width: u32) -> (f64, f64) {
+    let disk = width as f64 / (1.0 + 2.0 * PADDING);
+    let border = disk * PADDING;
+    (border + u * disk, border + v * disk)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(actual: f64, expected: f64) {
+        assert!((actual - expected).abs() < 1e-9, "{} != {}", actual, expected);
+    }
+
+    #[test]
+    fn sub_satellite_point_is_disk_center() {
+        let (u, v) = to_disk(0.0, SUB_LONGITUDE).unwrap();
+        assert_close(u, 0.5);
+        assert_close(v, 0.5);
+    }
+
+    #[test]
+    fn far_side_is_hidden() {
+        assert_eq!(to_disk(0.0, SUB_LONGITUDE - 180.0), None);
+        assert_eq!(to_disk(0.0, SUB_LONGITUDE + 90.0), None);
+        assert_eq!(to_disk(90.0, SUB_LONGITUDE), None);
+    }
+
+    #[test]
+    fn north_is_up_and_east_is_right() {
+        let (u, v) = to_disk(35.0, SUB_LONGITUDE).unwrap();
+        assert_close(u, 0.5);
+        assert!(v < 0.5);
+        let (u, v) = to_disk(0.0, SUB_LONGITUDE + 30.0).unwrap();
+        assert!(u > 0.5);
+        assert_close(v, 0.5);
+        // 可见范围内的点都落在圆盘图内
+        let (u, v) = to_disk(-60.0, SUB_LONGITUDE - 60.0).unwrap();
+        assert!((0.0..=1.0).contains(&u) && (0.0..=1.0).contains(&v));
+    }
+
+    #[test]
+    fn padded_pixel_keeps_center_and_skips_border() {
+        let (x, y) = to_padded_pixel((0.5, 0.5), 1200);
+        assert_close(x, 600.0);
+        assert_close(y, 600.0);
+        let (x, _) = to_padded_pixel((0.0, 0.0), 1200);
+        assert_close(x, 100.0);
+    }
+}
@@ -0,0 +1,425 @@
+use chrono::{DateTime, Duration, NaiveDateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::SystemTime;
+use tracing::{info, warn};
+
+use crate::atomic;
+use crate::error::{EarthError, EarthResult};
+
+/// 记录当前壁纸的文件名，放在图片目录下
+const ACTIVE_FILE: &str = "active_wallpaper.json";
+
+/// 图片保留策略，各项为 None 表示不限制，任意一项超限即删除
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RetentionPolicy {
+    /// 最多保留最近的多少张地球图
+    pub keep_last: Option<usize>,
+    /// 最多保留多少天内的图片
+    pub keep_days: Option<u32>,
+    /// 图片目录最多占用多少 MB
+    pub max_total_mb: Option<u64>,
+}
+
+impl Default for RetentionPolicy {
+    fn default() -> Self {
+        RetentionPolicy {
+            keep_last: Some(48),
+            keep_days: Some(1),
+            max_total_mb: Some(500),
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct RetentionState {
+    pub policy: Mutex<RetentionPolicy>,
+}
+
+/// 当前正在使用的壁纸，按显示器索引记录
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ActiveWallpapers {
+    pub monitors: BTreeMap<usize, ActiveWallpaper>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ActiveWallpaper {
+    /// 裁剪后实际设置的壁纸
+    pub wallpaper: String,
+    /// 裁剪所用的地球大图
+    pub source: String,
+}
+
+impl ActiveWallpapers {
+    pub fn load(base: &Path) -> Self {
+        fs::read_to_string(base.join(ACTIVE_FILE))
+            .ok()
+            .and_then(|s| serde_json::from_str(&s).ok())
+            .unwrap_or_default()
+    }
+
+    pub fn save(&self, base: &Path) -> EarthResult<()> {
+        let path = base.join(ACTIVE_FILE);
+        let json = serde_json::to_string_pretty(self)
+            .map_err(|e| EarthError::internal("序列化当前壁纸记录失败", e))?;
+        atomic::write_bytes(&path, json, "保存当前壁纸记录失败")
+    }
+
+    /// 当前壁纸及其来源大图（含原图和黑边图）都不能删除
+    pub fn protected_paths(&self) -> HashSet<PathBuf> {
+        let mut paths = HashSet::new();
+        for active in self.monitors.values() {
+            paths.insert(PathBuf::from(&active.wallpaper));
+            let source = PathBuf::from(&active.source);
+            if let Some(stamp) = capture_stamp(&source) {
+                let dir = source.parent().unwrap_or(Path::new(""));
+                paths.insert(dir.join(format!("earth_{}.png", stamp)));
+                paths.insert(dir.join(format!("earth_{}_black.png", stamp)));
+            }
+            paths.insert(source);
+        }
+        paths
+    }
+}
+
+/// 一个待删除的文件
+#[derive(Debug, Clone, Serialize)]
+pub struct CleanupItem {
+    pub path: String,
+    pub size: u64,
+    pub reason: &'static str,
+}
+
+/// 目录中的一个文件，地球图按拍摄时间、其他文件按修改时间排序
+struct Entry {
+    path: PathBuf,
+    size: u64,
+    time: DateTime<Utc>,
+    /// 同一拍摄时间的原图和黑边图共享一个编号
+    capture: Option<String>,
+}
+
+/// 从 earth_YYYYMMDD_HHMM.png / earth_YYYYMMDD_HHMM_black.png 中取出时间戳部分
+pub fn capture_stamp(path: &Path) -> Option<String> {
+    let name = path.file_name()?.to_str()?;
+    let rest = name.strip_prefix("earth_")?.strip_suffix(".png")?;
+    let stamp = rest.strip_suffix("_black").unwrap_or(rest);
+    NaiveDateTime::parse_from_str(stamp, "%Y%m%d_%H%M").ok()?;
+    Some(stamp.to_string())
+}
+
+fn stamp_time(stamp: &str) -> Option<DateTime<Utc>> {
+    NaiveDateTime::parse_from_str(stamp, "%Y%m%d_%H%M")
+        .ok()
+        .map(|t| t.and_utc())
+}
+
+fn modified_time(meta: &fs::Metadata) -> DateTime<Utc> {
+    meta.modified().unwrap_or(SystemTime::UNIX_EPOCH).into()
+}
+
+/// 收集图片目录、tiles 目录和 monitor_* 目录下的全部图片
+fn collect_entries(base: &Path) -> Vec<Entry> {
+    let mut entries = Vec::new();
+    let mut dirs = vec![base.to_path_buf()];
+    if let Ok(read) = fs::read_dir(base) {
+        for entry in read.flatten() {
+            let path = entry.path();
+            let name = entry.file_name().to_string_lossy().into_owned();
+            if path.is_dir() && (name == "tiles" || name.starts_with("monitor_")) {
+                dirs.push(path);
+            }
+        }
+    }
+    for dir in dirs {
+        let Ok(read) = fs::read_dir(&dir) else { continue };
+        for entry in read.flatten() {
+            let path = entry.path();
+            if path.extension().is_none_or(|ext| ext != "png") {
+                continue;
+            }
+            let Ok(meta) = entry.metadata() else { continue };
+            if !meta.is_file() {
+                continue;
+            }
+            let capture = if dir == base { capture_stamp(&path) } else { None };
+            // 图片根目录下只管理地球图，不认识的文件一律不动
+            if dir == base && capture.is_none() {
+                continue;
+            }
+            let time = capture
+                .as_deref()
+                .and_then(stamp_time)
+                .unwrap_or_else(|| modified_time(&meta));
+            entries.push(Entry {
+                path,
+                size: meta.len(),
+                time,
+                capture,
+            });
+        }
+    }
+    entries
+}
+
+/// 按策略计算需要删除的文件，不做任何修改
+pub fn plan_cleanup(
+    base: &Path,
+    policy: &RetentionPolicy,
+    protected: &HashSet<PathBuf>,
+    now: DateTime<Utc>,
+) -> Vec<CleanupItem> {
+    let mut entries = collect_entries(base);
+    // 从新到旧排序
+    entries.sort_by_key(|e| std::cmp::Reverse(e.time));
+
+    // 最新一次拍摄始终保留，避免刚下载完就被删掉
+    let newest = entries.iter().find_map(|e| e.capture.clone());
+    let mut captures_seen: Vec<String> = Vec::new();
+    let mut total: u64 = entries.iter().map(|e| e.size).sum();
+    let mut plan: Vec<(usize, &'static str)> = Vec::new();
+
+    for (idx, entry) in entries.iter().enumerate() {
+        if let Some(capture) = &entry.capture
+            && !captures_seen.contains(capture)
+        {
+            captures_seen.push(capture.clone());
+        }
+        if protected.contains(&entry.path) || (entry.capture.is_some() && entry.capture == newest) {
+            continue;
+        }
+        let too_many = match (&entry.capture, policy.keep_last) {
+            (Some(_), Some(keep_last)) => captures_seen.len() > keep_last,
+            _ => false,
+        };
+        let too_old = policy
+            .keep_days
+            .is_some_and(|days| now - entry.time > Duration::days(days as i64));
+        if too_many {
+            plan.push((idx, "keep_last"));
+        } else if too_old {
+            plan.push((idx, "keep_days"));
+        }
+    }
+    for &(idx, _) in &plan {
+        total -= entries[idx].size;
+    }
+
+    // 仍超出配额时，从最旧的开始继续删除，同一拍摄的原图和黑边图一起删
+    if let Some(max_mb) = policy.max_total_mb {
+        let max_bytes = max_mb * 1024 * 1024;
+        let mut groups: Vec<Vec<usize>> = Vec::new();
+        let mut group_of: HashMap<&str, usize> = HashMap::new();
+        for (idx, entry) in entries.iter().enumerate() {
+            match entry.capture.as_deref() {
+                Some(capture) if group_of.contains_key(capture) => groups[group_of[capture]].push(idx),
+                Some(capture) => {
+                    group_of.insert(capture, groups.len());
+                    groups.push(vec![idx]);
+                }
+                None => groups.push(vec![idx]),
+            }
+        }
+        for group in groups.iter().rev() {
+            if total <= max_bytes {
+                break;
+            }
+            let keep = group.iter().any(|&idx| {
+                let entry = &entries[idx];
+                protected.contains(&entry.path) || (entry.capture.is_some() && entry.capture == newest)
+            });
+            if keep {
+                continue;
+            }
+            for &idx in group {
+                if !plan.iter().any(|&(i, _)| i == idx) {
+                    plan.push((idx, "max_total_mb"));
+                    total -= entries[idx].size;
+                }
+            }
+        }
+    }
+
+    plan.into_iter()
+        .map(|(idx, reason)| CleanupItem {
+            path: entries[idx].path.to_string_lossy().into_owned(),
+            size: entries[idx].size,
+            reason,
+        })
+        .collect()
+}
+
+/// 按计划删除文件，返回实际删除的文件
+pub fn execute(plan: Vec<CleanupItem>) -> Vec<CleanupItem> {
+    plan.into_iter()
+        .filter(|item| match fs::remove_file(&item.path) {
+            Ok(()) => {
+                info!(reason = item.reason, path = %item.path, size = item.size, "已删除旧图片");
+                true
+            }
+            Err(e) => {
+                warn!(path = %item.path, error = %e, "删除旧图片失败");
+                false
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    const MB: u64 = 1024 * 1024;
+
+    /// 创建指定大小的空洞文件
+    fn touch(path: &Path, size: u64) {
+        fs::File::create(path).unwrap().set_len(size).unwrap();
+    }
+
+    /// 2025-06-20 从 00:00 起每 10 分钟一次拍摄，各有原图和黑边图
+    fn captures(dir: &Path, count: usize, size: u64) -> Vec<String> {
+        (0..count)
+            .map(|i| {
+                let stamp = format!("20250620_{:02}{:02}", i / 6, i % 6 * 10);
+                touch(&dir.join(format!("earth_{}.png", stamp)), size);
+                touch(&dir.join(format!("earth_{}_black.png", stamp)), size);
+                stamp
+            })
+            .collect()
+    }
+
+    fn now() -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2025, 6, 20, 12, 0, 0).unwrap()
+    }
+
+    fn no_limits() -> RetentionPolicy {
+        RetentionPolicy {
+            keep_last: None,
+            keep_days: None,
+            max_total_mb: None,
+        }
+    }
+
+    /// 计划删除的文件名，按文件名排序
+    fn planned(plan: &[CleanupItem]) -> Vec<String> {
+        let mut names: Vec<String> = plan
+            .iter()
+            .map(|item| Path::new(&item.path).file_name().unwrap().to_string_lossy().into_owned())
+            .collect();
+        names.sort();
+        names
+    }
+
+    #[test]
+    fn keep_last_counts_captures_not_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let stamps = captures(dir.path(), 4, 10);
+        let policy = RetentionPolicy {
+            keep_last: Some(2),
+            ..no_limits()
+        };
+        let plan = plan_cleanup(dir.path(), &policy, &HashSet::new(), now());
+        assert!(plan.iter().all(|item| item.reason == "keep_last"));
+        assert_eq!(
+            planned(&plan),
+            [
+                format!("earth_{}.png", stamps[0]),
+                format!("earth_{}_black.png", stamps[0]),
+                format!("earth_{}.png", stamps[1]),
+                format!("earth_{}_black.png", stamps[1]),
+            ]
+        );
+    }
+
+    #[test]
+    fn keep_days_uses_capture_time_and_modified_time() {
+        let dir = tempfile::tempdir().unwrap();
+        let stamps = captures(dir.path(), 2, 10);
+        // 刚写入的裁剪图按修改时间算，不算过期
+        let monitor = dir.path().join("monitor_0");
+        fs::create_dir(&monitor).unwrap();
+        touch(&monitor.join("wallpaper.png"), 10);
+        let policy = RetentionPolicy {
+            keep_days: Some(1),
+            ..no_limits()
+        };
+        let later = now() + Duration::days(1) + Duration::minutes(5);
+        let plan = plan_cleanup(dir.path(), &policy, &HashSet::new(), later);
+        assert!(plan.iter().all(|item| item.reason == "keep_days"));
+        // 00:00 的已超过一天，00:10 的是最新一次拍摄
+        assert_eq!(
+            planned(&plan),
+            [format!("earth_{}.png", stamps[0]), format!("earth_{}_black.png", stamps[0])]
+        );
+    }
+
+    #[test]
+    fn size_cap_deletes_whole_captures_from_the_oldest() {
+        let dir = tempfile::tempdir().unwrap();
+        let stamps = captures(dir.path(), 4, MB);
+        // 共 8 MB，上限 5 MB：删掉最旧的两次拍摄后剩 4 MB
+        let policy = RetentionPolicy {
+            max_total_mb: Some(5),
+            ..no_limits()
+        };
+        let plan = plan_cleanup(dir.path(), &policy, &HashSet::new(), now());
+        assert!(plan.iter().all(|item| item.reason == "max_total_mb"));
+        assert_eq!(
+            planned(&plan),
+            [
+                format!("earth_{}.png", stamps[0]),
+                format!("earth_{}_black.png", stamps[0]),
+                format!("earth_{}.png", stamps[1]),
+                format!("earth_{}_black.png", stamps[1]),
+            ]
+        );
+    }
+
+    #[test]
+    fn current_wallpaper_and_newest_capture_are_never_deleted() {
+        let dir = tempfile::tempdir().unwrap();
+        let stamps = captures(dir.path(), 3, MB);
+        let monitor = dir.path().join("monitor_0");
+        fs::create_dir(&monitor).unwrap();
+        let wallpaper = monitor.join("wallpaper.png");
+        touch(&wallpaper, MB);
+        let mut active = ActiveWallpapers::default();
+        active.monitors.insert(
+            0,
+            ActiveWallpaper {
+                wallpaper: wallpaper.to_string_lossy().into_owned(),
+                source: dir.path().join(format!("earth_{}_black.png", stamps[0])).to_string_lossy().into_owned(),
+            },
+        );
+        let protected = active.protected_paths();
+        // 所有限制都收紧到删光为止
+        let policy = RetentionPolicy {
+            keep_last: Some(0),
+            keep_days: Some(0),
+            max_total_mb: Some(0),
+        };
+        let plan = plan_cleanup(dir.path(), &policy, &protected, now() + Duration::days(30));
+        // 只有中间那次拍摄可删：最旧的是当前壁纸来源，最新的刚下载
+        assert_eq!(
+            planned(&plan),
+            [format!("earth_{}.png", stamps[1]), format!("earth_{}_black.png", stamps[1])]
+        );
+    }
+
+    #[test]
+    fn unknown_files_in_the_image_root_are_left_alone() {
+        let dir = tempfile::tempdir().unwrap();
+        captures(dir.path(), 2, MB);
+        touch(&dir.path().join("notes.png"), 10 * MB);
+        let policy = RetentionPolicy {
+            max_total_mb: Some(0),
+            ..no_limits()
+        };
+        let plan = plan_cleanup(dir.path(), &policy, &HashSet::new(), now());
+        assert!(planned(&plan).iter().all(|name| name.starts_with("earth_")));
+    }
+}
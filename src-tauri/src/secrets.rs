@@ -0,0 +1,36 @@
+use keyring::Entry;
+
+use crate::error::{EarthError, EarthResult};
+use crate::weather::ProviderKind;
+
+/// 钥匙串中的服务名，与应用标识一致
+const SERVICE: &str = "com.ImmediateEarth.app";
+
+/// 天气 API Key 保存在系统钥匙串（macOS Keychain、Windows 凭据管理器、Secret Service）中，
+/// 不写入 settings.json，前端只能设置或清除，读不到明文
+fn entry(provider: ProviderKind) -> EarthResult<Entry> {
+    Entry::new(SERVICE, &format!("{}_api_key", provider.name())).map_err(|e| EarthError::internal("读取钥匙串失败", e))
+}
+
+/// 未保存时返回 None
+pub fn api_key(provider: ProviderKind) -> EarthResult<Option<String>> {
+    match entry(provider)?.get_password() {
+        Ok(key) => Ok(Some(key)),
+        Err(keyring::Error::NoEntry) => Ok(None),
+        Err(e) => Err(EarthError::internal("读取钥匙串失败", e)),
+    }
+}
+
+/// key 为空时删除已保存的 key
+pub fn set_api_key(provider: ProviderKind, key: Option<&str>) -> EarthResult<()> {
+    let entry = entry(provider)?;
+    match key.map(str::trim).filter(|key| !key.is_empty()) {
+        Some(key) => entry
+            .set_password(key)
+            .map_err(|e| EarthError::internal("保存到钥匙串失败", e)),
+        None => match entry.delete_credential() {
+            Ok(()) | Err(keyring::Error::NoEntry) => Ok(()),
+            Err(e) => Err(EarthError::internal("保存到钥匙串失败", e)),
+        },
+    }
+}
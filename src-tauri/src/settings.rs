@@ -0,0 +1,366 @@
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::collections::HashMap;
+use tracing::{info, warn};
+
+use crate::download::DownloadConfig;
+use crate::error::{EarthError, EarthResult};
+use crate::i18n::{self, Locale};
+use crate::location::ManualLocation;
+use crate::map_overlay::MapOverlayConfig;
+use crate::overlay::TextOverlayConfig;
+use crate::places::{Place, PlaceMarkerConfig};
+use crate::retention::RetentionPolicy;
+use crate::weather::{ProviderKind, WeatherLocation};
+use crate::weather_format::Units;
+
+/// 当前的设置格式版本，格式有不兼容的变化时加一，并在 migrate 中补上迁移
+pub const SCHEMA_VERSION: u32 = 1;
+
+/// 已迁移为新键、保存时要从 settings.json 删除的旧键
+pub const RETIRED_KEYS: &[&str] = &["autoSetWallpaperEnabled", "autoWallpaperMonitorIndexes", "weather_city"];
+
+/// 卫星图源站提供的分辨率档位：每边的瓦片数，每块瓦片 550 像素
+pub const LEVELS: &[u32] = &[1, 2, 4, 8, 16, 20];
+
+/// 后端读写的全部设置，每个字段对应 settings.json 中的一个顶层键
+///
+/// 前端直接写入 settings.json 的键也在这里，读取时单个字段格式不对只会回退为默认值。
+/// 天气 API Key 保存在钥匙串中，不在这里。
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    pub schema_version: u32,
+    /// 界面语言，None 表示跟随系统
+    pub locale: Option<Locale>,
+    /// 下载分辨率档位，见 LEVELS
+    pub level: u32,
+    /// 定时更新并自动设置壁纸
+    pub auto_set_wallpaper: bool,
+    /// 自动更新的间隔（分钟）
+    pub update_interval_minutes: u32,
+    /// 自动设置壁纸的显示器索引，为空表示所有显示器
+    pub monitor_indexes: Vec<usize>,
+    /// 下载限速，单位 KB/s，0 表示不限速
+    pub rate_limit_kb: u64,
+    pub trickle_download: bool,
+    pub retention: RetentionPolicy,
+    pub weather_provider: ProviderKind,
+    pub weather_units: Units,
+    /// None 表示使用自动解析的当前位置
+    pub weather_location: Option<WeatherLocation>,
+    pub manual_location: Option<ManualLocation>,
+    pub use_system_location: bool,
+    pub frame_location: bool,
+    pub show_location_marker: bool,
+    /// 键为显示器索引
+    pub text_overlays: HashMap<usize, TextOverlayConfig>,
+    pub map_overlay: MapOverlayConfig,
+    pub places: Vec<Place>,
+    pub place_markers: PlaceMarkerConfig,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            schema_version: SCHEMA_VERSION,
+            locale: None,
+            level: 4,
+            auto_set_wallpaper: false,
+            update_interval_minutes: 30,
+            monitor_indexes: Vec::new(),
+            rate_limit_kb: 0,
+            trickle_download: false,
+            retention: RetentionPolicy::default(),
+            weather_provider: ProviderKind::default(),
+            weather_units: Units::default(),
+            weather_location: None,
+            manual_location: None,
+            use_system_location: true,
+            frame_location: false,
+            show_location_marker: false,
+            text_overlays: HashMap::new(),
+            map_overlay: MapOverlayConfig::default(),
+            places: Vec::new(),
+            place_markers: PlaceMarkerConfig::default(),
+        }
+    }
+}
+
+impl Settings {
+    /// 从 settings.json 的全部键值读取设置，旧版本的键先迁移；返回设置和是否做过迁移
+    ///
+    /// 不认识的键原样留在文件里；格式不对或校验不通过的字段记录日志并使用默认值，不影响其他字段。
+    pub fn from_entries(mut entries: Map<String, Value>) -> (Self, bool) {
+        let migrated = migrate(&mut entries);
+        let defaults = match serde_json::to_value(Settings::default()) {
+            Ok(Value::Object(map)) => map,
+            _ => Map::new(),
+        };
+        let mut merged = defaults.clone();
+        for key in defaults.keys() {
+            if let Some(value) = entries.get(key).filter(|value| !value.is_null()) {
+                merged.insert(key.clone(), value.clone());
+            }
+        }
+        if let Ok(settings) = serde_json::from_value::<Settings>(Value::Object(merged.clone()))
+            && settings.validate().is_ok()
+        {
+            return (settings, migrated);
+        }
+        // 整体解析或校验失败时逐个字段检查，把解析不了或超出范围的字段换回默认值
+        for key in defaults.keys() {
+            let mut single = defaults.clone();
+            single.insert(key.clone(), merged[key].clone());
+            let checked = serde_json::from_value::<Settings>(Value::Object(single))
+                .map_err(|e| e.to_string())
+                .and_then(|settings| settings.validate().map_err(|e| e.to_string()));
+            if let Err(e) = checked {
+                warn!(key = %key, error = %e, "设置项格式错误或超出范围，使用默认值");
+                merged.insert(key.clone(), defaults[key].clone());
+            }
+        }
+        let settings = serde_json::from_value(Value::Object(merged)).unwrap_or_default();
+        (settings, migrated)
+    }
+
+    /// 解析导入的设置：旧版本先迁移，缺少的字段取默认值，不认识或格式不对的字段报错
+    pub fn from_import(value: Value) -> EarthResult<Self> {
+        let Value::Object(mut entries) = value else {
+            return Err(EarthError::invalid("设置格式错误"));
+        };
+        migrate(&mut entries);
+        let settings = Settings::default().merged(Value::Object(entries))?;
+        settings.validate()?;
+        Ok(settings)
+    }
+
+    /// 序列化为 settings.json 的顶层键值，值为 null 的键应从文件中删除
+    pub fn to_entries(&self) -> EarthResult<Map<String, Value>> {
+        match serde_json::to_value(self) {
+            Ok(Value::Object(map)) => Ok(map),
+            Ok(_) => Ok(Map::new()),
+            Err(e) => Err(EarthError::internal("序列化设置失败", e)),
+        }
+    }
+
+    /// 把 patch 中的顶层键合并进当前设置，未出现的字段保持不变
+    pub fn merged(&self, patch: Value) -> EarthResult<Self> {
+        let Value::Object(patch) = patch else {
+            return Err(EarthError::invalid("设置格式错误"));
+        };
+        let mut entries = self.to_entries()?;
+        for (key, value) in patch {
+            if !entries.contains_key(&key) {
+                return Err(EarthError::invalid(i18n::trf("未知的设置项: {}", &[&key])));
+            }
+            entries.insert(key, value);
+        }
+        let mut settings: Settings = serde_json::from_value(Value::Object(entries))
+            .map_err(|e| EarthError::invalid(i18n::trf("设置格式错误: {}", &[&e])))?;
+        settings.schema_version = SCHEMA_VERSION;
+        Ok(settings)
+    }
+
+    pub fn validate(&self) -> EarthResult<()> {
+        if !LEVELS.contains(&self.level) {
+            return Err(EarthError::invalid("不支持的分辨率档位"));
+        }
+        if !(10..=1440).contains(&self.update_interval_minutes) {
+            return Err(EarthError::invalid("更新间隔需在 10 到 1440 分钟之间"));
+        }
+        if let Some(location) = &self.manual_location {
+            location.validate()?;
+        }
+        for config in self.text_overlays.values() {
+            config.validate()?;
+        }
+        self.map_overlay.validate()?;
+        for place in &self.places {
+            place.validate()?;
+        }
+        self.place_markers.validate()
+    }
+
+    pub fn download_config(&self) -> DownloadConfig {
+        DownloadConfig {
+            rate_limit_bps: (self.rate_limit_kb > 0).then(|| self.rate_limit_kb * 1024),
+            trickle: self.trickle_download,
+        }
+    }
+
+    /// 要设置壁纸的显示器，None 表示所有显示器
+    pub fn target_monitors(&self) -> Option<Vec<usize>> {
+        (!self.monitor_indexes.is_empty()).then(|| self.monitor_indexes.clone())
+    }
+}
+
+/// 把旧版本的键迁移到当前格式，返回是否有改动
+fn migrate(entries: &mut Map<String, Value>) -> bool {
+    let version = entries
+        .get("schema_version")
+        .and_then(Value::as_u64)
+        .unwrap_or(0) as u32;
+    if version > SCHEMA_VERSION {
+        warn!(version, current = SCHEMA_VERSION, "设置文件来自更新的版本，按当前版本读取");
+        return false;
+    }
+    if version == SCHEMA_VERSION {
+        return false;
+    }
+    // 0 -> 1：前端使用的驼峰键改为下划线，天气城市名并入 weather_location
+    if version < 1 {
+        for (old, new) in [
+            ("autoSetWallpaperEnabled", "auto_set_wallpaper"),
+            ("autoWallpaperMonitorIndexes", "monitor_indexes"),
+        ] {
+            if let Some(value) = entries.remove(old) {
+                entries.entry(new).or_insert(value);
+            }
+        }
+        if let Some(Value::String(city)) = entries.remove("weather_city")
+            && !city.trim().is_empty()
+        {
+            entries
+                .entry("weather_location")
+                .or_insert_with(|| serde_json::json!({ "type": "city", "name": city.trim() }));
+        }
+    }
+    entries.insert("schema_version".to_string(), SCHEMA_VERSION.into());
+    info!(from = version, to = SCHEMA_VERSION, "设置已迁移");
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn entries(value: Value) -> Map<String, Value> {
+        match value {
+            Value::Object(map) => map,
+            _ => unreachable!(),
+        }
+    }
+
+    #[test]
+    fn migrates_v0_camel_case_keys_and_weather_city() {
+        let (settings, migrated) = Settings::from_entries(entries(json!({
+            "autoSetWallpaperEnabled": true,
+            "autoWallpaperMonitorIndexes": [1, 2],
+            "weather_city": " 北京 ",
+            "level": 8,
+        })));
+        assert!(migrated);
+        assert_eq!(settings.schema_version, SCHEMA_VERSION);
+        assert!(settings.auto_set_wallpaper);
+        assert_eq!(settings.monitor_indexes, vec![1, 2]);
+        assert_eq!(settings.weather_location, Some(WeatherLocation::City { name: "北京".to_string() }));
+        assert_eq!(settings.level, 8);
+    }
+
+    #[test]
+    fn migration_keeps_existing_new_keys() {
+        let mut map = entries(json!({
+            "autoSetWallpaperEnabled": true,
+            "auto_set_wallpaper": false,
+            "weather_city": "北京",
+            "weather_location": { "type": "city_id", "id": "101010100" },
+        }));
+        assert!(migrate(&mut map));
+        assert_eq!(map["auto_set_wallpaper"], json!(false));
+        assert_eq!(map["weather_location"], json!({ "type": "city_id", "id": "101010100" }));
+        assert!(!map.contains_key("autoSetWallpaperEnabled"));
+        assert!(!map.contains_key("weather_city"));
+        // 当前版本不再迁移
+        assert!(!migrate(&mut map));
+    }
+
+    #[test]
+    fn blank_weather_city_is_dropped() {
+        let (settings, _) = Settings::from_entries(entries(json!({ "weather_city": "  " })));
+        assert_eq!(settings.weather_location, None);
+    }
+
+    #[test]
+    fn invalid_field_falls_back_to_default() {
+        let (settings, migrated) = Settings::from_entries(entries(json!({
+            "schema_version": SCHEMA_VERSION,
+            "level": "huge",
+            "rate_limit_kb": 256,
+            "monitor_indexes": null,
+            "unknown_key": 1,
+        })));
+        assert!(!migrated);
+        assert_eq!(settings.level, Settings::default().level);
+        assert_eq!(settings.rate_limit_kb, 256);
+        assert_eq!(settings.monitor_indexes, Vec::<usize>::new());
+    }
+
+    #[test]
+    fn out_of_range_fields_fall_back_to_default() {
+        let (settings, _) = Settings::from_entries(entries(json!({
+            "schema_version": SCHEMA_VERSION,
+            "level": 3,
+            "update_interval_minutes": 0,
+            "manual_location": { "latitude": 120.0, "longitude": 0.0 },
+            "map_overlay": { "enabled": true, "opacity": 2.0 },
+            "text_overlays": { "0": { "enabled": true, "color": "white" } },
+            "rate_limit_kb": 256,
+            "auto_set_wallpaper": true,
+        })));
+        let defaults = Settings::default();
+        assert_eq!(settings.level, defaults.level);
+        assert_eq!(settings.update_interval_minutes, defaults.update_interval_minutes);
+        assert_eq!(settings.manual_location, None);
+        assert_eq!(settings.map_overlay, defaults.map_overlay);
+        assert_eq!(settings.text_overlays, defaults.text_overlays);
+        // 其他字段不受影响
+        assert_eq!(settings.rate_limit_kb, 256);
+        assert!(settings.auto_set_wallpaper);
+        assert!(settings.validate().is_ok());
+    }
+
+    #[test]
+    fn valid_fields_are_kept() {
+        let (settings, _) = Settings::from_entries(entries(json!({
+            "schema_version": SCHEMA_VERSION,
+            "level": 20,
+            "update_interval_minutes": 60,
+            "manual_location": { "latitude": 35.68, "longitude": 139.69 },
+        })));
+        assert_eq!(settings.level, 20);
+        assert_eq!(settings.update_interval_minutes, 60);
+        assert!(settings.manual_location.is_some());
+    }
+
+    #[test]
+    fn merged_rejects_unknown_keys() {
+        let settings = Settings::default();
+        assert!(matches!(
+            settings.merged(json!({ "levle": 8 })),
+            Err(EarthError::InvalidInput { .. })
+        ));
+        assert!(settings.merged(json!([1])).is_err());
+        assert!(settings.merged(json!({ "level": "huge" })).is_err());
+        let merged = settings.merged(json!({ "level": 8 })).unwrap();
+        assert_eq!(merged.level, 8);
+        assert_eq!(merged.rate_limit_kb, settings.rate_limit_kb);
+    }
+
+    #[test]
+    fn validate_rejects_unsupported_level() {
+        assert!(Settings::default().validate().is_ok());
+        let settings = Settings { level: 3, ..Settings::default() };
+        assert!(matches!(settings.validate(), Err(EarthError::InvalidInput { .. })));
+        // 导入时同样校验
+        assert!(Settings::from_import(json!({ "level": 3 })).is_err());
+        assert_eq!(Settings::from_import(json!({ "level": 20 })).unwrap().level, 20);
+    }
+
+    #[test]
+    fn validate_rejects_out_of_range_interval() {
+        let settings = Settings { update_interval_minutes: 5, ..Settings::default() };
+        assert!(settings.validate().is_err());
+    }
+}
@@ -0,0 +1,441 @@
+use chrono::{DateTime, Duration, DurationRound, Utc};
+use image::codecs::gif::{GifEncoder, Repeat};
+use image::codecs::webp::WebPEncoder;
+use image::imageops::{self, FilterType};
+use image::{ColorType, Delay, Frame, Rgba, RgbaImage};
+use serde::{Deserialize, Serialize};
+use std::fs::{self, File};
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+use tracing::{info, warn};
+
+use crate::atomic;
+use crate::download::CancelToken;
+use crate::error::{EarthError, EarthResult};
+use crate::i18n;
+use crate::history::HistoryIndex;
+
+/// 向日葵卫星的发布间隔（分钟），帧步长必须是它的整数倍
+const SLOT_MINUTES: i64 = 10;
+
+/// 一次导出最多的帧数，避免误选一整年的范围
+const MAX_FRAMES: usize = 2000;
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AnimationFormat {
+    Webp,
+    Apng,
+    Gif,
+}
+
+impl AnimationFormat {
+    fn extension(self) -> &'static str {
+        match self {
+            AnimationFormat::Webp => "webp",
+            AnimationFormat::Apng => "png",
+            AnimationFormat::Gif => "gif",
+        }
+    }
+}
+
+/// 前端传来的导出参数，时间均为 RFC 3339
+#[derive(Debug, Clone, Deserialize)]
+pub struct TimelapseRequest {
+    pub from: String,
+    pub to: String,
+    /// 帧步长（分钟），会向上取整到 10 分钟的倍数
+    pub step_minutes: u32,
+    pub width: u32,
+    pub height: u32,
+    pub format: AnimationFormat,
+    /// 每帧显示时长，默认 100 毫秒
+    pub frame_delay_ms: Option<u32>,
+    /// 输出文件路径，默认放在图片目录的 timelapse 子目录下
+    pub output: Option<String>,
+}
+
+/// 导出进度，stage 为 download / render / encode
+#[derive(Debug, Clone, Serialize)]
+pub struct TimelapseProgress {
+    pub stage: &'static str,
+    pub current: usize,
+    pub total: usize,
+}
+
+/// 按步长列出时间范围内的所有拍摄时刻
+pub fn frame_slots(from: DateTime<Utc>, to: DateTime<Utc>, step_minutes: u32) -> EarthResult<Vec<DateTime<Utc>>> {
+    if to < from {
+        return Err(EarthError::invalid("结束时间早于开始时间"));
+    }
+    let step_slots = (step_minutes as i64 + SLOT_MINUTES - 1) / SLOT_MINUTES;
+    let step_slots = step_slots.max(1);
+    let step = Duration::minutes(step_slots * SLOT_MINUTES);
+    let mut t = from
+        .duration_trunc(Duration::minutes(SLOT_MINUTES))
+        .map_err(|e| EarthError::invalid(i18n::trf("无效的开始时间: {}", &[&e])))?;
+    let mut slots = Vec::new();
+    while t <= to {
+        slots.push(t);
+        if slots.len() > MAX_FRAMES {
+            return Err(EarthError::invalid(i18n::trf("帧数超过上限 {}，请缩小时间范围或增大步长", &[&MAX_FRAMES])));
+        }
+        t += step;
+    }
+    Ok(slots)
+}
+
+/// 解析 RFC 3339 时间范围并列出拍摄时刻
+pub fn parse_slots(from: &str, to: &str, step_minutes: u32) -> EarthResult<Vec<DateTime<Utc>>> {
+    let from = crate::history::parse_time(Some(from.to_string()))?.unwrap_or_default();
+    let to = crate::history::parse_time(Some(to.to_string()))?.unwrap_or_default();
+    frame_slots(from, to, step_minutes)
+}
+
+/// 补齐缺失的拍摄后，返回每个可用时刻对应的拼接大图，单帧下载失败只跳过该帧
+pub fn ensure_frames<D, P>(
+    base: &Path,
+    slots: &[DateTime<Utc>],
+    cancel: &CancelToken,
+    download: D,
+    progress: &P,
+) -> EarthResult<Vec<(DateTime<Utc>, PathBuf)>>
+where
+    D: Fn(DateTime<Utc>) -> EarthResult<()>,
+    P: Fn(TimelapseProgress),
+{
+    let mut index = HistoryIndex::load(base);
+    index.prune_missing();
+    for (i, &slot) in slots.iter().enumerate() {
+        cancel.check()?;
+        if index.get(&slot_id(slot)).is_none() {
+            progress(TimelapseProgress {
+                stage: "download",
+                current: i + 1,
+                total: slots.len(),
+            });
+            if let Err(e) = download(slot) {
+                warn!(slot = %slot_id(slot), error = %e, "补下载失败，跳过");
+            }
+        }
+    }
+    let index = HistoryIndex::load(base);
+    let frames: Vec<(DateTime<Utc>, PathBuf)> = slots
+        .iter()
+        .filter_map(|&slot| index.get(&slot_id(slot)))
+        .map(|c| (c.timestamp, PathBuf::from(&c.merged_image)))
+        .filter(|(_, p)| p.exists())
+        .collect();
+    if frames.is_empty() {
+        return Err(EarthError::invalid("所选时间范围内没有可用的图片"));
+    }
+    Ok(frames)
+}
+
+/// 与历史索引中的编号一致：YYYYMMDD_HHMM
+pub fn slot_id(t: DateTime<Utc>) -> String {
+    t.format("%Y%m%d_%H%M").to_string()
+}
+
+/// 把地球圆盘等比缩放后居中放到黑色画布上
+pub fn render_frame(path: &Path, width: u32, height: u32) -> EarthResult<RgbaImage> {
+    let img = image::open(path).map_err(|e| EarthError::image(path, "打开图片失败", e))?;
+    let scaled = img.resize(width, height, FilterType::Triangle).to_rgba8();
+    let mut canvas = RgbaImage::from_pixel(width, height, Rgba([0, 0, 0, 255]));
+    let x = (width - scaled.width()) / 2;
+    let y = (height - scaled.height()) / 2;
+    imageops::replace(&mut canvas, &scaled, x as i64, y as i64);
+    Ok(canvas)
+}
+
+/// 导出延时动画，返回输出文件路径
+///
+/// `download` 用于补齐历史中缺失的拍摄，`progress` 用于上报进度。
+pub fn export<D, P>(
+    base: &Path,
+    request: &TimelapseRequest,
+    cancel: &CancelToken,
+    download: D,
+    progress: P,
+) -> EarthResult<String>
+where
+    D: Fn(DateTime<Utc>) -> EarthResult<()>,
+    P: Fn(TimelapseProgress),
+{
+    if request.width == 0 || request.height == 0 {
+        return Err(EarthError::invalid("输出尺寸无效"));
+    }
+    // GIF 的宽高字段只有 16 位，超出时编码器会截断或 panic
+    if matches!(request.format, AnimationFormat::Gif) && request.width.max(request.height) > u16::MAX as u32 {
+        return Err(EarthError::invalid("GIF 的宽高不能超过 65535 像素"));
+    }
+    // 1. 补齐缺失的拍摄
+    let slots = parse_slots(&request.from, &request.to, request.step_minutes)?;
+    let frames: Vec<PathBuf> = ensure_frames(base, &slots, cancel, download, &progress)?
+        .into_iter()
+        .map(|(_, path)| path)
+        .collect();
+
+    // 2. 逐帧缩放并编码
+    let output = match &request.output {
+        Some(path) => PathBuf::from(path),
+        None => {
+            let dir = base.join("timelapse");
+            fs::create_dir_all(&dir).map_err(|e| EarthError::io(&dir, "创建延时动画目录失败", e))?;
+            dir.join(format!(
+                "timelapse_{}_{}.{}",
+                slot_id(slots[0]),
+                slot_id(*slots.last().unwrap()),
+                request.format.extension()
+            ))
+        }
+    };
+    let delay_ms = request.frame_delay_ms.unwrap_or(100).max(10);
+    // 写到临时文件再改名，取消或失败时不留下半成品
+    atomic::write_with(&output, |tmp| {
+        let file = File::create(tmp).map_err(|e| EarthError::io(&output, "创建输出文件失败", e))?;
+        let mut encoder = AnimationEncoder::new(
+            BufWriter::new(file),
+            request.format,
+            request.width,
+            request.height,
+            frames.len() as u32,
+            delay_ms,
+        )?;
+        for (i, path) in frames.iter().enumerate() {
+            cancel.check()?;
+            progress(TimelapseProgress {
+                stage: "render",
+                current: i + 1,
+                total: frames.len(),
+            });
+            let frame = render_frame(path, request.width, request.height)?;
+            encoder.add_frame(frame)?;
+        }
+        progress(TimelapseProgress {
+            stage: "encode",
+            current: frames.len(),
+            total: frames.len(),
+        });
+        encoder.finish()
+    })?;
+    info!(frames = frames.len(), output = %output.display(), "延时动画已导出");
+    Ok(output.to_string_lossy().into_owned())
+}
+
+/// 三种动画格式的统一编码接口，逐帧写入，避免把所有帧留在内存里
+enum AnimationEncoder<W: Write> {
+    Gif(GifEncoder<W>, Delay),
+    Apng(png::Writer<W>),
+    /// WebP 的 ANMF 块需要在文件头写总长度，先缓存每帧压缩后的 VP8L 块
+    Webp {
+        writer: W,
+        width: u32,
+        height: u32,
+        delay_ms: u32,
+        frames: Vec<Vec<u8>>,
+    },
+}
+
+impl<W: Write> AnimationEncoder<W> {
+    fn new(writer: W, format: AnimationFormat, width: u32, height: u32, frame_count: u32, delay_ms: u32) -> EarthResult<Self> {
+        match format {
+            AnimationFormat::Gif => {
+                let mut encoder = GifEncoder::new_with_speed(writer, 10);
+                encoder
+                    .set_repeat(Repeat::Infinite)
+                    .map_err(|e| EarthError::internal("GIF 编码失败", e))?;
+                Ok(AnimationEncoder::Gif(encoder, Delay::from_numer_denom_ms(delay_ms, 1)))
+            }
+            AnimationFormat::Apng => {
+                let mut encoder = png::Encoder::new(writer, width, height);
+                encoder.set_color(png::ColorType::Rgba);
+                encoder.set_depth(png::BitDepth::Eight);
+                encoder
+                    .set_animated(frame_count, 0)
+                    .and_then(|_| encoder.set_frame_delay(delay_ms.min(u16::MAX as u32) as u16, 1000))
+                    .map_err(|e| EarthError::internal("APNG 编码失败", e))?;
+                let writer = encoder.write_header().map_err(|e| EarthError::internal("APNG 编码失败", e))?;
+                Ok(AnimationEncoder::Apng(writer))
+            }
+            AnimationFormat::Webp => Ok(AnimationEncoder::Webp {
+                writer,
+                width,
+                height,
+                delay_ms,
+                frames: Vec::new(),
+            }),
+        }
+    }
+
+    fn add_frame(&mut self, frame: RgbaImage) -> EarthResult<()> {
+        match self {
+            AnimationEncoder::Gif(encoder, delay) => encoder
+                .encode_frame(Frame::from_parts(frame, 0, 0, *delay))
+                .map_err(|e| EarthError::internal("GIF 编码失败", e)),
+            AnimationEncoder::Apng(writer) => writer
+                .write_image_data(frame.as_raw())
+                .map_err(|e| EarthError::internal("APNG 编码失败", e)),
+            AnimationEncoder::Webp { frames, .. } => {
+                let mut buf = Vec::new();
+                WebPEncoder::new_lossless(&mut buf)
+                    .encode(frame.as_raw(), frame.width(), frame.height(), ColorType::Rgba8)
+                    .map_err(|e| EarthError::internal("WebP 编码失败", e))?;
+                // 去掉 RIFF/WEBP 文件头，只保留 VP8L 块
+                frames.push(buf.split_off(12));
+                Ok(())
+            }
+        }
+    }
+
+    fn finish(self) -> EarthResult<()> {
+        match self {
+            AnimationEncoder::Gif(encoder, _) => {
+                drop(encoder);
+                Ok(())
+            }
+            AnimationEncoder::Apng(writer) => writer.finish().map_err(|e| EarthError::internal("APNG 编码失败", e)),
+            AnimationEncoder::Webp {
+                mut writer,
+                width,
+                height,
+                delay_ms,
+                frames,
+            } => write_animated_webp(&mut writer, width, height, delay_ms, &frames)
+                .and_then(|_| writer.flush())
+                .map_err(|e| EarthError::internal("WebP 编码失败", e)),
+        }
+    }
+}
+
+/// 写成 24 位小端整数，超出范围的值取最大值，避免高位被截掉
+fn u24(value: u32) -> [u8; 3] {
+    let b = value.min(0xFF_FFFF).to_le_bytes();
+    [b[0], b[1], b[2]]
+}
+
+/// 按 WebP 扩展格式写出动画：VP8X + ANIM + 每帧一个 ANMF
+fn write_animated_webp<W: Write>(w: &mut W, width: u32, height: u32, delay_ms: u32, frames: &[Vec<u8>]) -> std::io::Result<()> {
+    let anmf_len: usize = frames.iter().map(|f| 8 + 16 + f.len()).sum();
+    let riff_len = 4 + (8 + 10) + (8 + 6) + anmf_len;
+
+    w.write_all(b"RIFF")?;
+    w.write_all(&(riff_len as u32).to_le_bytes())?;
+    w.write_all(b"WEBP")?;
+
+    // VP8X：带透明通道和动画标记
+    w.write_all(b"VP8X")?;
+    w.write_all(&10u32.to_le_bytes())?;
+    w.write_all(&[0x10 | 0x02, 0, 0, 0])?;
+    w.write_all(&u24(width - 1))?;
+    w.write_all(&u24(height - 1))?;
+
+    // ANIM：黑色背景，无限循环
+    w.write_all(b"ANIM")?;
+    w.write_all(&6u32.to_le_bytes())?;
+    w.write_all(&[0, 0, 0, 255])?;
+    w.write_all(&0u16.to_le_bytes())?;
+
+    for frame in frames {
+        w.write_all(b"ANMF")?;
+        w.write_all(&((16 + frame.len()) as u32).to_le_bytes())?;
+        w.write_all(&u24(0))?;
+        w.write_all(&u24(0))?;
+        w.write_all(&u24(width - 1))?;
+        w.write_all(&u24(height - 1))?;
+        w.write_all(&u24(delay_ms))?;
+        // 不混合、不清除：每帧都是完整画面
+        w.write_all(&[0x02])?;
+        w.write_all(frame)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 读取一个 RIFF 块，返回 (四字符码, 内容, 下一块的位置)，奇数长度的块后有一个填充字节
+    fn chunk(data: &[u8], at: usize) -> (&[u8], &[u8], usize) {
+        let fourcc = &data[at..at + 4];
+        let size = u32::from_le_bytes(data[at + 4..at + 8].try_into().unwrap()) as usize;
+        let body = &data[at + 8..at + 8 + size];
+        (fourcc, body, at + 8 + size + size % 2)
+    }
+
+    fn u24_at(data: &[u8], at: usize) -> u32 {
+        u32::from_le_bytes([data[at], data[at + 1], data[at + 2], 0])
+    }
+
+    fn encode_webp(width: u32, height: u32, frames: u32) -> Vec<u8> {
+        let mut out = Vec::new();
+        let mut encoder = AnimationEncoder::new(&mut out, AnimationFormat::Webp, width, height, frames, 120).unwrap();
+        for i in 0..frames {
+            let shade = (i * 60) as u8;
+            encoder
+                .add_frame(RgbaImage::from_fn(width, height, |x, y| Rgba([shade, x as u8, y as u8, 255])))
+                .unwrap();
+        }
+        encoder.finish().unwrap();
+        out
+    }
+
+    #[test]
+    fn animated_webp_container_is_well_formed() {
+        // 奇数宽高让 VP8L 数据更可能是奇数长度，检查填充
+        for (width, height) in [(5, 3), (16, 9), (7, 7)] {
+            let data = encode_webp(width, height, 3);
+            assert_eq!(&data[0..4], b"RIFF");
+            assert_eq!(u32::from_le_bytes(data[4..8].try_into().unwrap()) as usize, data.len() - 8);
+            assert_eq!(&data[8..12], b"WEBP");
+
+            let (fourcc, vp8x, next) = chunk(&data, 12);
+            assert_eq!(fourcc, b"VP8X");
+            assert_eq!(vp8x.len(), 10);
+            assert_eq!(vp8x[0] & 0x02, 0x02, "缺少动画标记");
+            assert_eq!(u24_at(vp8x, 4) + 1, width);
+            assert_eq!(u24_at(vp8x, 7) + 1, height);
+
+            let (fourcc, anim, mut at) = chunk(&data, next);
+            assert_eq!(fourcc, b"ANIM");
+            assert_eq!(anim.len(), 6);
+
+            let mut frames = 0;
+            while at < data.len() {
+                let (fourcc, anmf, next) = chunk(&data, at);
+                assert_eq!(fourcc, b"ANMF");
+                assert_eq!(u24_at(anmf, 6) + 1, width);
+                assert_eq!(u24_at(anmf, 9) + 1, height);
+                assert_eq!(u24_at(anmf, 12), 120);
+                // 帧数据是一个完整的 VP8L 块，带自己的填充
+                let (fourcc, _, end) = chunk(anmf, 16);
+                assert_eq!(fourcc, b"VP8L");
+                assert_eq!(end, anmf.len());
+                frames += 1;
+                at = next;
+            }
+            assert_eq!(at, data.len());
+            assert_eq!(frames, 3);
+        }
+    }
+
+    #[test]
+    fn frame_slots_round_step_up() {
+        let from = DateTime::parse_from_rfc3339("2025-06-20T12:03:00Z").unwrap().with_timezone(&Utc);
+        let to = from + Duration::minutes(60);
+        let slots = frame_slots(from, to, 15).unwrap();
+        assert_eq!(slots.len(), 4);
+        assert_eq!(slot_id(slots[0]), "20250620_1200");
+        assert_eq!(slot_id(slots[1]), "20250620_1220");
+        assert!(frame_slots(to, from, 10).is_err());
+    }
+
+    #[test]
+    fn u24_clamps_instead_of_wrapping() {
+        assert_eq!(u24(120), [120, 0, 0]);
+        assert_eq!(u24(0xFF_FFFF), [0xFF, 0xFF, 0xFF]);
+        // 约 4.7 小时的帧间隔不能变成 0
+        assert_eq!(u24(0x100_0000), [0xFF, 0xFF, 0xFF]);
+        assert_eq!(u24(u32::MAX), [0xFF, 0xFF, 0xFF]);
+    }
+}
@@ -0,0 +1,259 @@
+use chrono::{DateTime, Utc};
+use image::{Rgba, RgbaImage};
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::env;
+use std::fs;
+use std::io::{BufRead, BufReader};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::sync::mpsc;
+use std::thread;
+use std::time::Duration;
+use tracing::{info, warn};
+
+use crate::atomic;
+use crate::download::CancelToken;
+use crate::error::{EarthError, EarthResult};
+use crate::overlay;
+use crate::timelapse::{self, TimelapseProgress};
+
+/// ffmpeg 失败时错误信息里附带的 stderr 行数
+const STDERR_TAIL_LINES: usize = 20;
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum VideoContainer {
+    Mp4,
+    Webm,
+    /// 只输出 PNG 序列帧，不调用 ffmpeg
+    Frames,
+}
+
+/// 前端传来的视频导出参数，时间均为 RFC 3339
+#[derive(Debug, Clone, Deserialize)]
+pub struct VideoRequest {
+    pub from: String,
+    pub to: String,
+    pub step_minutes: u32,
+    pub width: u32,
+    pub height: u32,
+    pub container: VideoContainer,
+    /// 帧率，默认 24
+    pub fps: Option<u32>,
+    /// ffmpeg 编码预设，如 ultrafast / medium / veryslow，默认 medium
+    pub preset: Option<String>,
+    /// 质量参数，数值越小质量越高，默认 mp4 为 23、webm 为 32
+    pub crf: Option<u32>,
+    /// 是否在画面左下角加上 UTC 时间
+    pub caption: bool,
+    /// 输出目录，默认放在图片目录的 video 子目录下
+    pub output_dir: Option<String>,
+}
+
+/// 导出结果
+#[derive(Debug, Clone, Serialize)]
+pub struct VideoResult {
+    /// PNG 序列帧所在目录
+    pub frames_dir: String,
+    pub frame_count: usize,
+    /// 视频文件，未找到 ffmpeg 或只导出序列帧时为 None
+    pub video: Option<String>,
+}
+
+/// 在 PATH 中查找 ffmpeg
+pub fn find_ffmpeg() -> Option<PathBuf> {
+    let name = if cfg!(target_os = "windows") { "ffmpeg.exe" } else { "ffmpeg" };
+    env::var_os("PATH").and_then(|paths| {
+        env::split_paths(&paths)
+            .map(|dir| dir.join(name))
+            .find(|path| path.is_file())
+    })
+}
+
+/// 导出 PNG 序列帧，找到 ffmpeg 时再合成视频
+pub fn export<D, P>(
+    base: &Path,
+    request: &VideoRequest,
+    cancel: &CancelToken,
+    download: D,
+    progress: P,
+) -> EarthResult<VideoResult>
+where
+    D: Fn(DateTime<Utc>) -> EarthResult<()>,
+    P: Fn(TimelapseProgress),
+{
+    if request.width == 0 || request.height == 0 {
+        return Err(EarthError::invalid("输出尺寸无效"));
+    }
+    // libx264 / yuv420p 要求宽高为偶数，奇数向上取整
+    let width = request.width.saturating_add(1) & !1;
+    let height = request.height.saturating_add(1) & !1;
+
+    let slots = timelapse::parse_slots(&request.from, &request.to, request.step_minutes)?;
+    let frames = timelapse::ensure_frames(base, &slots, cancel, download, &progress)?;
+
+    let name = format!(
+        "earth_{}_{}",
+        timelapse::slot_id(slots[0]),
+        timelapse::slot_id(*slots.last().unwrap())
+    );
+    let out_dir = match &request.output_dir {
+        Some(dir) => PathBuf::from(dir),
+        None => base.join("video"),
+    };
+    let frames_dir = out_dir.join(format!("{}_frames", name));
+    // 清掉上次同名导出留下的序列帧，避免帧数对不上
+    let _ = fs::remove_dir_all(&frames_dir);
+    fs::create_dir_all(&frames_dir).map_err(|e| EarthError::io(&frames_dir, "创建序列帧目录失败", e))?;
+
+    // 1. 渲染序列帧
+    for (i, (time, path)) in frames.iter().enumerate() {
+        cancel.check()?;
+        progress(TimelapseProgress {
+            stage: "render",
+            current: i + 1,
+            total: frames.len(),
+        });
+        let mut frame = timelapse::render_frame(path, width, height)?;
+        if request.caption {
+            draw_caption(&mut frame, &time.format("%Y-%m-%d %H:%M UTC").to_string());
+        }
+        let frame_path = frames_dir.join(format!("frame_{:05}.png", i + 1));
+        atomic::save_image(&frame_path, "保存序列帧失败", |tmp, format| {
+            frame.save_with_format(tmp, format)
+        })?;
+    }
+    let mut result = VideoResult {
+        frames_dir: frames_dir.to_string_lossy().into_owned(),
+        frame_count: frames.len(),
+        video: None,
+    };
+
+    // 2. 调用 ffmpeg 合成视频
+    let ext = match request.container {
+        VideoContainer::Mp4 => "mp4",
+        VideoContainer::Webm => "webm",
+        VideoContainer::Frames => return Ok(result),
+    };
+    let Some(ffmpeg) = find_ffmpeg() else {
+        warn!(frames_dir = %result.frames_dir, "未找到 ffmpeg，只导出序列帧");
+        return Ok(result);
+    };
+    let output = out_dir.join(format!("{}.{}", name, ext));
+    encode(&ffmpeg, &frames_dir, &output, request, frames.len(), cancel, &progress)?;
+    info!(output = %output.display(), "延时视频已导出");
+    result.video = Some(output.to_string_lossy().into_owned());
+    Ok(result)
+}
+
+/// 运行 ffmpeg，解析 -progress 输出上报进度，取消时结束进程
+fn encode<P>(
+    ffmpeg: &Path,
+    frames_dir: &Path,
+    output: &Path,
+    request: &VideoRequest,
+    frame_count: usize,
+    cancel: &CancelToken,
+    progress: &P,
+) -> EarthResult<()>
+where
+    P: Fn(TimelapseProgress),
+{
+    let fps = request.fps.unwrap_or(24).max(1).to_string();
+    let preset = request.preset.clone().unwrap_or_else(|| "medium".to_string());
+    let input = frames_dir.join("frame_%05d.png");
+
+    let mut cmd = Command::new(ffmpeg);
+    cmd.args(["-y", "-hide_banner", "-nostats", "-progress", "pipe:1"])
+        .args(["-framerate", &fps])
+        .arg("-i")
+        .arg(&input)
+        .args(["-pix_fmt", "yuv420p"]);
+    match request.container {
+        VideoContainer::Webm => {
+            let crf = request.crf.unwrap_or(32).to_string();
+            // libvpx-vp9 没有 preset，用 deadline 近似
+            let deadline = if preset.contains("fast") { "realtime" } else { "good" };
+            cmd.args(["-c:v", "libvpx-vp9", "-b:v", "0", "-crf", &crf, "-deadline", deadline]);
+        }
+        _ => {
+            let crf = request.crf.unwrap_or(23).to_string();
+            cmd.args(["-c:v", "libx264", "-preset", &preset, "-crf", &crf, "-movflags", "+faststart"]);
+        }
+    }
+    cmd.arg(output).stdin(Stdio::null()).stdout(Stdio::piped()).stderr(Stdio::piped());
+
+    let mut child = cmd.spawn().map_err(|e| EarthError::external("ffmpeg", "启动 ffmpeg 失败", e))?;
+    let stdout = child
+        .stdout
+        .take()
+        .ok_or_else(|| EarthError::external("ffmpeg", "无法读取 ffmpeg 输出", "stdout"))?;
+    let stderr = child
+        .stderr
+        .take()
+        .ok_or_else(|| EarthError::external("ffmpeg", "无法读取 ffmpeg 输出", "stderr"))?;
+
+    // stderr 必须持续读取，否则缓冲区写满后 ffmpeg 会卡住；只保留最后几行用于报错
+    let stderr_tail = thread::spawn(move || {
+        let mut tail = VecDeque::with_capacity(STDERR_TAIL_LINES);
+        for line in BufReader::new(stderr).lines().map_while(Result::ok) {
+            if tail.len() == STDERR_TAIL_LINES {
+                tail.pop_front();
+            }
+            tail.push_back(line);
+        }
+        Vec::from(tail).join("\n")
+    });
+
+    // 在单独的线程里逐行读取，主线程负责检查取消
+    let (tx, rx) = mpsc::channel();
+    thread::spawn(move || {
+        for line in BufReader::new(stdout).lines().map_while(Result::ok) {
+            if tx.send(line).is_err() {
+                break;
+            }
+        }
+    });
+
+    loop {
+        if cancel.is_cancelled() {
+            let _ = child.kill();
+            let _ = child.wait();
+            let _ = fs::remove_file(output);
+            return Err(EarthError::cancelled());
+        }
+        match rx.recv_timeout(Duration::from_millis(200)) {
+            Ok(line) => {
+                if let Some(frame) = line.strip_prefix("frame=").and_then(|v| v.trim().parse::<usize>().ok()) {
+                    progress(TimelapseProgress {
+                        stage: "encode",
+                        current: frame.min(frame_count),
+                        total: frame_count,
+                    });
+                }
+            }
+            Err(mpsc::RecvTimeoutError::Timeout) => {}
+            Err(mpsc::RecvTimeoutError::Disconnected) => break,
+        }
+    }
+
+    let status = child
+        .wait()
+        .map_err(|e| EarthError::external("ffmpeg", "等待 ffmpeg 结束失败", e))?;
+    let tail = stderr_tail.join().unwrap_or_default();
+    if !status.success() {
+        let _ = fs::remove_file(output);
+        warn!(%status, stderr = %tail, "ffmpeg 编码失败");
+        return Err(EarthError::external("ffmpeg", "ffmpeg 编码失败", format!("{}\n{}", status, tail)));
+    }
+    Ok(())
+}
+
+/// 在画面左下角绘制白字黑影的时间标注，字号随画面高度缩放
+fn draw_caption(img: &mut RgbaImage, text: &str) {
+    let px = (img.height() as f32 / 30.0).max(10.0);
+    let margin = px as f64 * 0.6;
+    let y = img.height() as f64 - margin - px as f64 / 2.0;
+    overlay::draw_label(img, text, margin, y, 0.0, px, Rgba([255, 255, 255, 255]));
+}
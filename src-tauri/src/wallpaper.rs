@@ -1,41 +1,133 @@
-use chrono::{Duration, Timelike, Utc};
+use chrono::{DateTime, Duration, Timelike, Utc};
 use image::{self, DynamicImage, GenericImageView, Rgba, RgbaImage, imageops};
 use std::fs;
 use std::path::{Path, PathBuf};
 use tauri::{AppHandle, Manager};
 use std::process::Command;
+use tracing::{debug, warn};
+
+use crate::atomic;
+use crate::error::{EarthError, EarthResult};
+use crate::i18n;
+use crate::location::Framing;
+use crate::overlay::{self, TextOverlays};
+use crate::projection;
 
 /// 为每个显示器创建独立的壁纸目录
 pub fn create_monitor_wallpaper_dir(
     app: &AppHandle,
     monitor_index: usize,
-) -> Result<PathBuf, String> {
+) -> EarthResult<PathBuf> {
     let app_data_dir = app
         .path()
         .app_local_data_dir()
-        .map_err(|e| format!("无法获取应用本地数据目录: {}", e))?;
+        .map_err(|e| EarthError::internal("无法获取应用本地数据目录", e))?;
 
     let mut base_path = PathBuf::from(app_data_dir);
     base_path.push("immediate_earth");
     base_path.push(format!("monitor_{}", monitor_index));
 
-    fs::create_dir_all(&base_path).map_err(|e| format!("创建显示器壁纸目录失败: {}", e))?;
+    fs::create_dir_all(&base_path).map_err(|e| EarthError::io(&base_path, "创建显示器壁纸目录失败", e))?;
 
     Ok(base_path)
 }
 
-/// 为特定显示器裁剪图片，保持原有的裁剪逻辑
+/// 为特定显示器裁剪图片，保持原有的裁剪逻辑；该显示器开启了文字叠加时把文字画到壁纸上
 pub async fn crop_image_for_monitor(
     app: &AppHandle,
     image_path: &str,
     monitor_index: usize,
-) -> Result<String, String> {
+    framing: &Framing,
+    text: Option<&TextOverlays>,
+) -> EarthResult<String> {
+    let (mut cropped_img, width, height) = render_for_monitor(app, image_path, monitor_index, framing)?;
+    if let Some((config, lines)) = text.and_then(|text| text.for_monitor(monitor_index)) {
+        let mut canvas = cropped_img.to_rgba8();
+        // 壁纸会被桌面缩放到显示器大小，字号按缩放比例换算
+        let scale = canvas.height() as f64 / height.max(1) as f64;
+        overlay::draw_text(&mut canvas, &lines, config, scale)?;
+        cropped_img = DynamicImage::ImageRgba8(canvas);
+    }
+    let monitor_dir = create_monitor_wallpaper_dir(app, monitor_index)?;
+    let new_path = monitor_dir.join(wallpaper_file_name(width, height, Utc::now()));
+    atomic::save_image(&new_path, "保存裁剪后图片失败", |tmp, format| {
+        cropped_img.save_with_format(tmp, format)
+    })?;
+    Ok(new_path.to_string_lossy().into_owned())
+}
+
+/// 每次设置壁纸都用新的文件名，形如 wallpaper_1920x1080_20250620_120000123.png
+///
+/// Windows、macOS 和 GNOME 会按路径缓存壁纸，同一路径再次设置时桌面不会刷新。
+pub fn wallpaper_file_name(width: u32, height: u32, time: DateTime<Utc>) -> String {
+    format!("wallpaper_{}x{}_{}.png", width, height, time.format("%Y%m%d_%H%M%S%3f"))
+}
+
+/// 显示器目录中由 crop_image_for_monitor 生成的壁纸，包括旧版本固定文件名的 wallpaper_WxH.png
+fn is_wallpaper_file(path: &Path) -> bool {
+    path.file_name()
+        .and_then(|name| name.to_str())
+        .is_some_and(|name| name.starts_with("wallpaper_") && name.ends_with(".png"))
+}
+
+/// 显示器目录中除 keep 以外的壁纸文件
+pub fn stale_wallpapers(dir: &Path, keep: &[&Path]) -> Vec<PathBuf> {
+    let Ok(read) = fs::read_dir(dir) else {
+        return Vec::new();
+    };
+    let mut stale: Vec<PathBuf> = read
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| path.is_file() && is_wallpaper_file(path) && !keep.contains(&path.as_path()))
+        .collect();
+    stale.sort();
+    stale
+}
+
+/// 新壁纸设置成功后清理该显示器的旧壁纸，返回删除的个数
+///
+/// 被替换的上一张仍然保留：GNOME 的渐变 XML 还引用着它，
+/// macOS/KDE 的设置命令也是异步执行的。它会在下一次设置后被删除。
+pub fn remove_stale_wallpapers(current: &Path, previous: Option<&Path>) -> usize {
+    let Some(dir) = current.parent() else {
+        return 0;
+    };
+    let keep: Vec<&Path> = std::iter::once(current).chain(previous).collect();
+    stale_wallpapers(dir, &keep)
+        .into_iter()
+        .filter(|path| match fs::remove_file(path) {
+            Ok(()) => {
+                debug!(path = %path.display(), "已删除旧壁纸");
+                true
+            }
+            Err(e) => {
+                warn!(path = %path.display(), error = %e, "删除旧壁纸失败");
+                false
+            }
+        })
+        .count()
+}
+
+/// 裁剪窗口起点，使 center 尽量位于窗口中央且窗口不越出图片
+fn centered_start(center: f64, window: u32, total: u32) -> u32 {
+    (center - window as f64 / 2.0).round().clamp(0.0, (total - window) as f64) as u32
+}
+
+/// 按显示器尺寸裁剪图片但不保存，返回裁剪结果和显示器的宽高
+///
+/// image_path 是加黑边的大图；framing 带有位置时可以按位置取景并画标记，带有地点时画上地点标注。
+pub fn render_for_monitor(
+    app: &AppHandle,
+    image_path: &str,
+    monitor_index: usize,
+    framing: &Framing,
+) -> EarthResult<(DynamicImage, u32, u32)> {
     let monitors = app
         .available_monitors()
-        .map_err(|e| format!("获取显示器信息失败: {}", e))?;
+        .map_err(|e| EarthError::monitor(None, i18n::trf("获取显示器信息失败: {}", &[&e])))?;
 
     if monitor_index >= monitors.len() {
-        return Err(format!("无效的显示器索引: {}", monitor_index));
+        return Err(EarthError::monitor(Some(monitor_index), i18n::trf("无效的显示器索引: {}", &[&monitor_index])));
     }
 
     let monitor = &monitors[monitor_index];
@@ -46,10 +138,8 @@ pub async fn crop_image_for_monitor(
     let target_height = (height as f64 * scale_factor) as u32;
     let screen_ratio = target_width as f64 / target_height as f64;
 
-    let monitor_dir = create_monitor_wallpaper_dir(app, monitor_index)?;
-
     let path = Path::new(image_path);
-    let mut img = image::open(path).map_err(|e| format!("打开图片失败: {}", e))?;
+    let mut img = image::open(path).map_err(|e| EarthError::image(path, "打开图片失败", e))?;
 
     let (img_width, img_height) = img.dimensions();
     let black_border = (img_width as f32 / 12.0).round() as u32 * 2;
@@ -59,6 +149,9 @@ pub async fn crop_image_for_monitor(
     let japan_time = now_utc + Duration::hours(9);
     let japan_hour = japan_time.hour();
 
+    // 黑边处理时图像内容的水平平移量，投影到大图上的坐标要跟着平移
+    let mut shift = 0.0;
+
     // 横屏处理黑边，竖屏不处理黑边
     if screen_is_landscape {
         // 处理黑边
@@ -69,6 +162,7 @@ pub async fn crop_image_for_monitor(
                 let mut canvas = RgbaImage::from_pixel(img_width, img_height, Rgba([0, 0, 0, 255]));
                 imageops::replace(&mut canvas, &cropped, 0, 0);
                 img = DynamicImage::ImageRgba8(canvas);
+                shift = -(black_border as f64);
             }
         } else if japan_hour >= 15 {
             // 下午
@@ -77,48 +171,78 @@ pub async fn crop_image_for_monitor(
                 let mut canvas = RgbaImage::from_pixel(img_width, img_height, Rgba([0, 0, 0, 255]));
                 imageops::replace(&mut canvas, &cropped, black_border as i64, 0);
                 img = DynamicImage::ImageRgba8(canvas);
+                shift = black_border as f64;
             }
         }
     }
+    let to_pixel = |uv| {
+        let (x, y) = projection::to_padded_pixel(uv, img_width);
+        (x + shift, y)
+    };
+    // 所在位置在大图上的像素坐标
+    let focus = framing
+        .location
+        .as_ref()
+        .and_then(|l| projection::to_disk(l.latitude, l.longitude))
+        .map(to_pixel);
     // 重新获取处理后的图片尺寸
     let (img_width, img_height) = img.dimensions();
     let img_ratio = img_width as f64 / img_height as f64;
-    let cropped_img = if screen_ratio > img_ratio {
+    let (crop_width, crop_height) = if screen_ratio > img_ratio {
         // 如果屏幕比例大于图片比例，则裁剪高度
         let target_height = (img_width as f64 / screen_ratio).round() as u32;
-        let crop_height = target_height.min(img_height);
-        if screen_is_landscape || japan_hour >= 6 {
-            // 横屏或竖屏非凌晨，裁剪顶部
-            img.crop(0, 0, img_width, crop_height)
-        } else {
-            // 凌晨，保留底部
-            img.crop(0, img_height - crop_height, img_width, crop_height)
-        }
+        (img_width, target_height.min(img_height))
     } else {
         // 如果屏幕比例小于图片比例，则裁剪宽度
         let target_width = (img_height as f64 * screen_ratio).round() as u32;
-        let crop_width = target_width.min(img_width);
-        if screen_is_landscape || japan_hour >= 6 {
-            // 横屏或竖屏非凌晨，裁剪左侧
-            img.crop(0, 0, crop_width, img_height)
-        } else {
-            // 凌晨，保留右侧
-            img.crop(img_width - crop_width, 0, crop_width, img_height)
-        }
+        (target_width.min(img_width), img_height)
     };
-    let new_path = monitor_dir.join(format!("wallpaper_{}x{}.png", width, height));
-    cropped_img
-        .save(&new_path)
-        .map_err(|e| format!("保存裁剪后图片失败: {}", e))?;
-    Ok(new_path.to_string_lossy().into_owned())
+    let (crop_x, crop_y) = match focus {
+        // 按位置取景：窗口以所在位置为中心
+        Some((x, y)) if framing.center => (
+            centered_start(x, crop_width, img_width),
+            centered_start(y, crop_height, img_height),
+        ),
+        // 横屏或竖屏非凌晨，保留顶部/左侧
+        _ if screen_is_landscape || japan_hour >= 6 => (0, 0),
+        // 凌晨，保留底部/右侧
+        _ => (img_width - crop_width, img_height - crop_height),
+    };
+    let mut cropped_img = img.crop(crop_x, crop_y, crop_width, crop_height);
+
+    if let Some(places) = &framing.places {
+        let mut canvas = cropped_img.to_rgba8();
+        // 与叠加文字一样，标签字号按壁纸缩放到显示器的比例换算
+        let scale = canvas.height() as f64 / height.max(1) as f64;
+        places.draw(&mut canvas, scale, |uv| {
+            let (x, y) = to_pixel(uv);
+            (x - crop_x as f64, y - crop_y as f64)
+        })?;
+        cropped_img = DynamicImage::ImageRgba8(canvas);
+    }
+    if framing.marker
+        && let Some((x, y)) = focus
+    {
+        let mut canvas = cropped_img.to_rgba8();
+        let radius = (img_width as f64 / 150.0).max(4.0);
+        overlay::draw_location_marker(&mut canvas, x - crop_x as f64, y - crop_y as f64, radius);
+        cropped_img = DynamicImage::ImageRgba8(canvas);
+    }
+    Ok((cropped_img, width, height))
 }
 
 /// 为单个显示器设置壁纸
+///
+/// fade 为 true 时 GNOME/Cinnamon 从上一张渐变到新壁纸，否则直接切换。
 pub async fn set_wallpaper_for_monitor(
     image_path: String,
     platform: String,
     monitor_index: usize,
-) -> Result<(), String> {
+    fade: bool,
+) -> EarthResult<()> {
+    // 只有 Linux 的 GNOME/Cinnamon 支持渐变
+    #[cfg(not(target_os = "linux"))]
+    let _ = fade;
     match platform.as_str() {
         "windows" => {
             #[cfg(target_os = "windows")]{
@@ -131,8 +255,7 @@ pub async fn set_wallpaper_for_monitor(
 
             // 验证文件路径是否存在
             if !Path::new(&image_path).exists() {
-                return Err(format!("壁纸文件不存在: {}
-请检查文件路径是否正确", image_path));
+                return Err(EarthError::io(&image_path, "壁纸文件不存在", i18n::tr("请检查文件路径是否正确")));
             }
 
             // 设置壁纸
@@ -146,8 +269,7 @@ pub async fn set_wallpaper_for_monitor(
             };
 
             if result.is_err() {
-                 return Err(format!("SystemParametersInfoW调用失败
- 文件路径: {}", image_path));
+                 return Err(EarthError::external("SystemParametersInfoW", "设置壁纸失败", i18n::trf("文件路径: {}", &[&image_path])));
              };
             }
         }
@@ -161,26 +283,19 @@ pub async fn set_wallpaper_for_monitor(
             Command::new("osascript")
                 .args(&["-e", &cmd])
                 .spawn()
-                .map_err(|e| format!("macOS 设置失败: {}", e))?;
+                .map_err(|e| EarthError::external("osascript", "macOS 设置失败", e))?;
             }
         }
         "linux" => {
             #[cfg(target_os = "linux")] {
             if let Ok(desktop) = std::env::var("XDG_CURRENT_DESKTOP") {
-                if desktop.contains("GNOME") {
-                    let cmd = format!("file://{}", image_path);
-                    Command::new("gsettings")
-                        .args(&[
-                            "set",
-                            &format!("org.gnome.desktop.background.picture-uri-{}", monitor_index),
-                            &cmd,
-                        ])
-                        .spawn()
-                        .map_err(|e| format!("GNOME 设置失败: {}", e))?;
+                if let Some(schema) = gnome_background_schema(&desktop) {
+                    gnome::set_wallpaper_with_fade(schema, &image_path, monitor_index, fade)?;
                 } else if desktop.contains("KDE") {
                     let script = format!(
-                        "string:var allDesktops = desktops(); \\n                        d = allDesktops[{}]; \\n                        d.wallpaperPlugin = \"org.kde.image\"; \\n                        d.currentConfigGroup = Array(\"Wallpaper\", \"org.kde.image\", \"General\"); \\n                        d.writeConfig(\"Image\", \"file://{}\")",
-                        monitor_index, image_path
+                        "string:var allDesktops = desktops(); \\n                        d = allDesktops[{}]; \\n                        d.wallpaperPlugin = \"org.kde.image\"; \\n                        d.currentConfigGroup = Array(\"Wallpaper\", \"org.kde.image\", \"General\"); \\n                        d.writeConfig(\"Image\", {})",
+                        monitor_index,
+                        js_string(&format!("file://{}", image_path))
                     );
                     Command::new("dbus-send")
                         .args(&[
@@ -192,12 +307,301 @@ pub async fn set_wallpaper_for_monitor(
                             &script,
                         ])
                         .spawn()
-                        .map_err(|e| format!("KDE 设置失败: {}", e))?;
+                        .map_err(|e| EarthError::external("dbus-send", "KDE 设置失败", e))?;
                 }
             }
           }
         }
-        _ => return Err("不支持的平台".into()),
+        _ => return Err(EarthError::unsupported(platform.as_str(), "不支持的平台")),
+    }
+    Ok(())
+}
+
+/// 转成 KDE 脚本中的 JS 字符串字面量，路径里的引号和反斜杠不会破坏脚本
+#[cfg(target_os = "linux")]
+fn js_string(value: &str) -> String {
+    serde_json::to_string(value).unwrap_or_default()
+}
+
+/// 当前桌面是否支持原生幻灯片：KDE Plasma 的 org.kde.slideshow 插件，或 GNOME/Cinnamon 的背景 XML
+pub fn supports_native_slideshow(platform: &str) -> bool {
+    platform == "linux"
+        && std::env::var("XDG_CURRENT_DESKTOP")
+            .is_ok_and(|desktop| desktop.contains("KDE") || gnome_background_schema(&desktop).is_some())
+}
+
+/// 使用桌面自带的幻灯片功能按文件名顺序轮播目录中的图片，过渡动画由桌面自己完成
+pub fn set_slideshow_for_monitor(
+    dir: &Path,
+    interval_secs: u64,
+    monitor_index: usize,
+) -> EarthResult<()> {
+    #[cfg(target_os = "linux")] {
+    let desktop = std::env::var("XDG_CURRENT_DESKTOP").unwrap_or_default();
+    if let Some(schema) = gnome_background_schema(&desktop) {
+        // GNOME 的 XML 自带渐变，只需要关键帧
+        let mut files: Vec<String> = fs::read_dir(dir)
+            .map_err(|e| EarthError::io(dir, "读取动态壁纸目录失败", e))?
+            .flatten()
+            .map(|entry| entry.path().to_string_lossy().into_owned())
+            .filter(|path| path.ends_with("_00.png"))
+            .collect();
+        files.sort();
+        let frames: Vec<gnome::SlideshowFrame> = files
+            .into_iter()
+            .map(|path| gnome::SlideshowFrame { path, duration_secs: interval_secs as f64 })
+            .collect();
+        let xml = gnome::slideshow_xml(chrono::Local::now().naive_local(), &frames, gnome::TRANSITION_SECS, true);
+        let xml_path = dir.join("slideshow.xml");
+        atomic::write_bytes(&xml_path, xml, "写入 GNOME 幻灯片失败")?;
+        return gnome::set_picture_uri(schema, &xml_path.to_string_lossy());
     }
+    // SlideshowMode 1 表示按文件名顺序播放
+    let script = format!(
+        "string:var allDesktops = desktops(); \
+         d = allDesktops[{}]; \
+         d.wallpaperPlugin = \"org.kde.slideshow\"; \
+         d.currentConfigGroup = Array(\"Wallpaper\", \"org.kde.slideshow\", \"General\"); \
+         d.writeConfig(\"SlidePaths\", {}); \
+         d.writeConfig(\"SlideInterval\", {}); \
+         d.writeConfig(\"SlideshowMode\", 1)",
+        monitor_index,
+        js_string(&dir.to_string_lossy()),
+        interval_secs
+    );
+    Command::new("dbus-send")
+        .args([
+            "--session",
+            "--dest=org.kde.plasmashell",
+            "--type=method_call",
+            "/PlasmaShell",
+            "org.kde.PlasmaShell.evaluateScript",
+            &script,
+        ])
+        .spawn()
+        .map_err(|e| EarthError::external("dbus-send", "KDE 幻灯片设置失败", e))?;
     Ok(())
+    }
+    #[cfg(not(target_os = "linux"))] {
+    let _ = (dir, interval_secs, monitor_index);
+    Err(EarthError::unsupported(std::env::consts::OS, "当前桌面不支持幻灯片壁纸"))
+    }
+}
+
+/// 根据 XDG_CURRENT_DESKTOP 找到背景设置所在的 gsettings schema
+pub fn gnome_background_schema(desktop: &str) -> Option<&'static str> {
+    if desktop.contains("Cinnamon") {
+        Some("org.cinnamon.desktop.background")
+    } else if desktop.contains("GNOME") {
+        Some("org.gnome.desktop.background")
+    } else {
+        None
+    }
+}
+
+/// GNOME/Cinnamon 的背景 XML，只在 Linux 上使用
+#[cfg(target_os = "linux")]
+pub mod gnome {
+    use chrono::{Datelike, Duration, Local, NaiveDateTime, Timelike};
+    use std::collections::BTreeMap;
+    use std::path::Path;
+    use std::process::Command;
+    use std::sync::Mutex;
+    use tracing::debug;
+
+    use crate::atomic;
+    use crate::error::{EarthError, EarthResult};
+
+    /// GNOME/Cinnamon 背景切换时的渐变时长（秒）
+    pub const TRANSITION_SECS: f64 = 5.0;
+
+    /// 渐变结束后新壁纸的停留时长，取 u32::MAX 秒（约 136 年）
+    ///
+    /// GNOME 按 XML 的总时长循环播放，停留时长有限时到点会渐变回上一张；
+    /// 不能指望下一次更新按时到来（自动更新可能关闭或失败）。
+    pub const HOLD_SECS: f64 = u32::MAX as f64;
+
+    /// 各显示器上一次通过 GNOME/Cinnamon 设置的图片，用于生成渐变的起点
+    static LAST_GNOME_WALLPAPER: Mutex<BTreeMap<usize, String>> = Mutex::new(BTreeMap::new());
+
+    /// GNOME 背景 XML 中的一张图片及其停留时长
+    pub struct SlideshowFrame {
+        pub path: String,
+        pub duration_secs: f64,
+    }
+
+    fn xml_escape(value: &str) -> String {
+        value
+            .replace('&', "&amp;")
+            .replace('<', "&lt;")
+            .replace('>', "&gt;")
+            .replace('"', "&quot;")
+    }
+
+    /// 生成 GNOME 背景 XML：每张图先 static 停留，再 transition 渐变到下一张
+    ///
+    /// looped 为 true 时最后一张渐变回第一张；GNOME 会从 start 开始按总时长循环播放。
+    pub fn slideshow_xml(
+        start: NaiveDateTime,
+        frames: &[SlideshowFrame],
+        transition_secs: f64,
+        looped: bool,
+    ) -> String {
+        let mut xml = String::from("<background>\n");
+        xml.push_str(&format!(
+            "  <starttime>\n    <year>{}</year>\n    <month>{}</month>\n    <day>{}</day>\n    <hour>{}</hour>\n    <minute>{}</minute>\n    <second>{}</second>\n  </starttime>\n",
+            start.year(),
+            start.month(),
+            start.day(),
+            start.hour(),
+            start.minute(),
+            start.second()
+        ));
+        for (i, frame) in frames.iter().enumerate() {
+            let file = xml_escape(&frame.path);
+            xml.push_str(&format!(
+                "  <static>\n    <duration>{:.1}</duration>\n    <file>{}</file>\n  </static>\n",
+                frame.duration_secs, file
+            ));
+            let next = if i + 1 < frames.len() {
+                Some(&frames[i + 1])
+            } else if looped && frames.len() > 1 {
+                Some(&frames[0])
+            } else {
+                None
+            };
+            if let Some(next) = next {
+                xml.push_str(&format!(
+                    "  <transition type=\"overlay\">\n    <duration>{:.1}</duration>\n    <from>{}</from>\n    <to>{}</to>\n  </transition>\n",
+                    transition_secs,
+                    file,
+                    xml_escape(&next.path)
+                ));
+            }
+        }
+        xml.push_str("</background>\n");
+        xml
+    }
+
+    /// 从 previous 渐变到 current 后一直停留在 current 的背景 XML
+    pub fn fade_xml(start: NaiveDateTime, previous: &str, current: &str) -> String {
+        let frames = [
+            SlideshowFrame { path: previous.to_string(), duration_secs: 1.0 },
+            SlideshowFrame { path: current.to_string(), duration_secs: HOLD_SECS },
+        ];
+        slideshow_xml(start, &frames, TRANSITION_SECS, false)
+    }
+
+    /// 设置 picture-uri；GNOME 42 以后深色模式读取 picture-uri-dark，一并设置
+    pub fn set_picture_uri(schema: &str, path: &str) -> EarthResult<()> {
+        let uri = format!("file://{}", path);
+        let mut keys = vec!["picture-uri"];
+        if schema.starts_with("org.gnome") {
+            keys.push("picture-uri-dark");
+        }
+        for key in keys {
+            Command::new("gsettings")
+                .args(["set", schema, key, &uri])
+                .spawn()
+                .map_err(|e| EarthError::external("gsettings", "GNOME 设置失败", e))?;
+        }
+        Ok(())
+    }
+
+    /// 写一个从上一张壁纸渐变到新壁纸的 XML 并指向它，首次设置或 fade 为 false 时没有渐变
+    ///
+    /// picture-uri 是全局设置，所有显示器共用一张壁纸，多个显示器时以最后设置的为准。
+    pub fn set_wallpaper_with_fade(schema: &str, image_path: &str, monitor_index: usize, fade: bool) -> EarthResult<()> {
+        let previous = LAST_GNOME_WALLPAPER
+            .lock()
+            .unwrap()
+            .insert(monitor_index, image_path.to_string())
+            .filter(|prev| prev != image_path && Path::new(prev).exists());
+        let Some(previous) = previous.filter(|_| fade) else {
+            return set_picture_uri(schema, image_path);
+        };
+
+        // 从上一张的最后一秒开始播放，紧接着渐变到新图
+        let start = Local::now().naive_local() - Duration::seconds(1);
+        let xml = fade_xml(start, &previous, image_path);
+        let xml_path = Path::new(image_path).with_file_name("slideshow.xml");
+        atomic::write_bytes(&xml_path, xml, "写入 GNOME 背景 XML 失败")?;
+        debug!(xml = %xml_path.display(), "GNOME 渐变");
+        set_picture_uri(schema, &xml_path.to_string_lossy())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn touch(dir: &Path, name: &str) -> PathBuf {
+        let path = dir.join(name);
+        fs::write(&path, "").unwrap();
+        path
+    }
+
+    #[test]
+    fn file_names_are_unique_and_sortable_within_a_second() {
+        let start = Utc.with_ymd_and_hms(2025, 6, 20, 12, 0, 0).unwrap();
+        let names: Vec<String> = [0, 1, 10, 999]
+            .iter()
+            .map(|ms| wallpaper_file_name(1920, 1080, start + Duration::milliseconds(*ms)))
+            .collect();
+        assert_eq!(names[0], "wallpaper_1920x1080_20250620_120000000.png");
+        let mut sorted = names.clone();
+        sorted.sort();
+        sorted.dedup();
+        assert_eq!(sorted, names);
+        // 跨秒后仍然排在后面
+        assert!(wallpaper_file_name(1920, 1080, start + Duration::seconds(1)) > names[3]);
+    }
+
+    #[test]
+    fn legacy_fixed_name_is_stale() {
+        let dir = tempfile::tempdir().unwrap();
+        let legacy = touch(dir.path(), "wallpaper_1920x1080.png");
+        let current = touch(dir.path(), "wallpaper_1920x1080_20250620_120000000.png");
+        assert_eq!(stale_wallpapers(dir.path(), &[&current]), vec![legacy]);
+    }
+
+    #[test]
+    fn remove_keeps_current_and_previous() {
+        let dir = tempfile::tempdir().unwrap();
+        let older = touch(dir.path(), "wallpaper_1920x1080_20250620_115000000.png");
+        let previous = touch(dir.path(), "wallpaper_1920x1080_20250620_120000000.png");
+        let current = touch(dir.path(), "wallpaper_1920x1080_20250620_121000000.png");
+        assert_eq!(remove_stale_wallpapers(&current, Some(&previous)), 1);
+        assert!(!older.exists());
+        assert!(previous.exists());
+        assert!(current.exists());
+    }
+
+    #[test]
+    fn remove_never_touches_other_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let others = [
+            touch(dir.path(), "earth_20250620_1200.png"),
+            touch(dir.path(), "wallpaper_1920x1080.jpg"),
+            touch(dir.path(), "notes.txt"),
+            touch(dir.path(), ".wallpaper_1920x1080_20250620_120000000.png.1.0.tmp"),
+        ];
+        fs::create_dir(dir.path().join("wallpaper_live.png")).unwrap();
+        let current = touch(dir.path(), "wallpaper_1920x1080_20250620_121000000.png");
+        assert_eq!(remove_stale_wallpapers(&current, None), 0);
+        assert!(others.iter().all(|path| path.exists()));
+        assert!(dir.path().join("wallpaper_live.png").is_dir());
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn fade_holds_the_new_wallpaper_without_looping_back() {
+        let start = Utc.with_ymd_and_hms(2025, 6, 20, 12, 0, 0).unwrap().naive_utc();
+        let xml = gnome::fade_xml(start, "/a/old.png", "/a/new & improved.png");
+        assert!(xml.contains("<duration>4294967295.0</duration>\n    <file>/a/new &amp; improved.png</file>"));
+        // 只有旧图到新图的一次渐变，不会渐变回旧图
+        assert_eq!(xml.matches("<transition").count(), 1);
+        assert!(xml.contains("<from>/a/old.png</from>\n    <to>/a/new &amp; improved.png</to>"));
+    }
 }
@@ -0,0 +1,233 @@
+use serde::Serialize;
+use std::net::{SocketAddr, TcpStream, ToSocketAddrs};
+use std::sync::{Arc, mpsc};
+use std::thread;
+use std::time::{Duration, SystemTime};
+use tauri::{AppHandle, Emitter, Manager};
+use tracing::{info, warn};
+
+use crate::pipeline::{Pipeline, UPDATE_KEY};
+
+/// 后台检测的间隔
+const TICK: Duration = Duration::from_secs(5);
+
+/// 墙上时间比单调时间多走这么久，就认为系统睡眠过
+const SLEEP_GAP: Duration = Duration::from_secs(30);
+
+/// 唤醒后网络还没恢复时，每隔多少个 tick 重新探测一次
+const PROBE_EVERY_TICKS: u32 = 6;
+
+/// 用于探测网络是否可达的主机，直接用卫星图的源站
+const PROBE_HOST: (&str, u16) = ("himawari.asia", 443);
+const PROBE_TIMEOUT: Duration = Duration::from_secs(3);
+
+#[derive(Clone, Serialize)]
+struct NetworkChanged {
+    online: bool,
+}
+
+#[derive(Clone, Serialize)]
+struct SystemResumed {
+    /// 睡眠时长（秒）
+    slept_secs: u64,
+}
+
+/// 不计入睡眠时间的单调时钟读数
+///
+/// Windows 上 Instant 基于 QueryPerformanceCounter，睡眠期间照常计时，
+/// 改用不含睡眠和休眠时间的 QueryUnbiasedInterruptTime（单位 100 纳秒）。
+#[cfg(target_os = "windows")]
+fn awake_clock() -> Duration {
+    use windows::Win32::System::WindowsProgramming::QueryUnbiasedInterruptTime;
+    let mut ticks = 0u64;
+    let _ = unsafe { QueryUnbiasedInterruptTime(&mut ticks) };
+    Duration::from_nanos(ticks.saturating_mul(100))
+}
+
+/// Linux 的 CLOCK_MONOTONIC 和 macOS 的 mach_absolute_time 在睡眠期间都不走
+#[cfg(not(target_os = "windows"))]
+fn awake_clock() -> Duration {
+    use std::sync::OnceLock;
+    use std::time::Instant;
+    static START: OnceLock<Instant> = OnceLock::new();
+    START.get_or_init(Instant::now).elapsed()
+}
+
+/// 检测系统睡眠：不含睡眠的单调时钟在睡眠期间不走，墙上时间照常走，两者差值即为睡眠时长
+pub struct SleepDetector {
+    awake: Duration,
+    wall: SystemTime,
+}
+
+impl Default for SleepDetector {
+    fn default() -> Self {
+        SleepDetector::new(awake_clock(), SystemTime::now())
+    }
+}
+
+impl SleepDetector {
+    fn new(awake: Duration, wall: SystemTime) -> Self {
+        SleepDetector { awake, wall }
+    }
+
+    /// 返回自上次调用以来的睡眠时长，没有睡眠过返回 None
+    pub fn poll(&mut self) -> Option<Duration> {
+        self.poll_at(awake_clock(), SystemTime::now())
+    }
+
+    fn poll_at(&mut self, awake: Duration, wall: SystemTime) -> Option<Duration> {
+        let awake_elapsed = awake.saturating_sub(self.awake);
+        // 用户手动把时钟往回调时 duration_since 会失败，当作没有睡眠
+        let wall_elapsed = wall.duration_since(self.wall).unwrap_or_default();
+        self.awake = awake;
+        self.wall = wall;
+        let slept = wall_elapsed.saturating_sub(awake_elapsed);
+        if slept > SLEEP_GAP { Some(slept) } else { None }
+    }
+}
+
+/// 在单独的线程里解析域名，超时返回 None
+///
+/// to_socket_addrs 没有超时参数，断网时可能卡住几十秒；超时后解析线程自行结束。
+fn resolve_with_timeout(host: (&'static str, u16), timeout: Duration) -> Option<Vec<SocketAddr>> {
+    let (tx, rx) = mpsc::channel();
+    thread::spawn(move || {
+        let _ = tx.send(host.to_socket_addrs().map(Iterator::collect));
+    });
+    rx.recv_timeout(timeout).ok()?.ok()
+}
+
+/// 探测卫星图源站是否可达
+pub fn network_reachable() -> bool {
+    let Some(addrs) = resolve_with_timeout(PROBE_HOST, PROBE_TIMEOUT) else {
+        return false;
+    };
+    addrs
+        .iter()
+        .any(|addr| TcpStream::connect_timeout(addr, PROBE_TIMEOUT).is_ok())
+}
+
+/// 启动后台线程，监听系统睡眠唤醒
+///
+/// 桌面平台没有统一的睡眠前通知，因此睡眠只能在唤醒后检测到：
+/// 唤醒时取消进行中的下载（睡眠前发起的连接大多已经失效），
+/// 之后探测网络，恢复后在新线程里调用 `on_catch_up` 补一次更新。
+/// 平时不探测网络，避免每隔几十秒就连一次源站。
+pub fn spawn<F>(app: AppHandle, on_catch_up: F)
+where
+    F: Fn(&AppHandle) + Send + Sync + 'static,
+{
+    let on_catch_up = Arc::new(on_catch_up);
+    thread::spawn(move || {
+        let mut detector = SleepDetector::default();
+        let mut online = true;
+        let mut pending_catch_up = false;
+        let mut ticks = 0u32;
+
+        loop {
+            thread::sleep(TICK);
+            ticks += 1;
+
+            if let Some(slept) = detector.poll() {
+                info!(slept_secs = slept.as_secs(), "检测到系统睡眠");
+                app.state::<Pipeline>().cancel_key(UPDATE_KEY);
+                let _ = app.emit(
+                    "system-resumed",
+                    SystemResumed {
+                        slept_secs: slept.as_secs(),
+                    },
+                );
+                pending_catch_up = true;
+                // 唤醒后立即探测网络，不等下一个周期
+                ticks = PROBE_EVERY_TICKS;
+            }
+
+            if !pending_catch_up || ticks < PROBE_EVERY_TICKS {
+                continue;
+            }
+            ticks = 0;
+
+            let now_online = network_reachable();
+            if now_online != online {
+                if now_online {
+                    info!("网络已恢复");
+                } else {
+                    warn!("网络不可用，等待恢复后补充更新");
+                }
+                let _ = app.emit("network-changed", NetworkChanged { online: now_online });
+                online = now_online;
+            }
+
+            if online {
+                pending_catch_up = false;
+                info!("网络可用，开始补充更新");
+                // 下载可能要几分钟，放到单独的线程，不耽误检测下一次睡眠
+                let app = app.clone();
+                let on_catch_up = on_catch_up.clone();
+                thread::spawn(move || on_catch_up(&app));
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const START: Duration = Duration::from_secs(1000);
+
+    fn detector() -> (SleepDetector, SystemTime) {
+        let wall = SystemTime::UNIX_EPOCH + Duration::from_secs(1_750_000_000);
+        (SleepDetector::new(START, wall), wall)
+    }
+
+    #[test]
+    fn no_sleep_when_clocks_agree() {
+        let (mut detector, wall) = detector();
+        assert_eq!(detector.poll_at(START + TICK, wall + TICK), None);
+    }
+
+    #[test]
+    fn gap_below_threshold_is_ignored() {
+        // 墙上时间被 NTP 校正几秒不算睡眠
+        let (mut detector, wall) = detector();
+        assert_eq!(detector.poll_at(START + TICK, wall + TICK + SLEEP_GAP), None);
+    }
+
+    #[test]
+    fn reports_sleep_duration() {
+        let (mut detector, wall) = detector();
+        let slept = Duration::from_secs(3600);
+        assert_eq!(detector.poll_at(START + TICK, wall + TICK + slept), Some(slept));
+        // 之后的周期以唤醒时刻为基准
+        assert_eq!(detector.poll_at(START + TICK * 2, wall + TICK * 2 + slept), None);
+    }
+
+    #[test]
+    fn clock_set_backwards_is_not_sleep() {
+        let (mut detector, wall) = detector();
+        let earlier = wall - Duration::from_secs(3600);
+        assert_eq!(detector.poll_at(START + TICK, earlier), None);
+        assert_eq!(detector.poll_at(START + TICK * 2, earlier + TICK), None);
+    }
+
+    #[test]
+    fn awake_clock_is_monotonic() {
+        let first = awake_clock();
+        thread::sleep(Duration::from_millis(20));
+        assert!(awake_clock() > first);
+    }
+
+    #[test]
+    fn unresolvable_host_is_unreachable() {
+        // .invalid 保留域名永远解析不到
+        assert!(resolve_with_timeout(("probe.invalid", 443), PROBE_TIMEOUT).is_none_or(|addrs| addrs.is_empty()));
+    }
+
+    #[test]
+    fn resolve_returns_within_timeout() {
+        let started = std::time::Instant::now();
+        let _ = resolve_with_timeout(PROBE_HOST, Duration::from_millis(1));
+        assert!(started.elapsed() < Duration::from_secs(1));
+    }
+}
@@ -0,0 +1,1321 @@
+use chrono::{DateTime, FixedOffset, NaiveDate, NaiveTime, Utc};
+use reqwest::blocking::Client;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::str::FromStr;
+use std::sync::OnceLock;
+use std::time::Duration;
+
+use crate::error::{EarthError, EarthResult};
+use crate::i18n;
+use crate::weather_cache::WeatherCache;
+
+/// 预报最多的天数
+pub const MAX_FORECAST_DAYS: u8 = 7;
+/// 逐小时预报覆盖的小时数
+const FORECAST_HOURS: usize = 24;
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(15);
+
+/// 天气请求共用的 HTTP 客户端和缓存
+#[derive(Default)]
+pub struct WeatherState {
+    client: OnceLock<Client>,
+    pub cache: WeatherCache,
+}
+
+impl WeatherState {
+    /// 第一次使用时创建客户端，之后复用连接
+    pub fn client(&self) -> EarthResult<&Client> {
+        if let Some(client) = self.client.get() {
+            return Ok(client);
+        }
+        let client = Client::builder()
+            .timeout(REQUEST_TIMEOUT)
+            .build()
+            .map_err(|e| EarthError::internal("创建 HTTP 客户端失败", e))?;
+        Ok(self.client.get_or_init(|| client))
+    }
+}
+
+/// 天气数据来源，在设置中选择，保存在 settings.json 的 weather_provider 字段
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ProviderKind {
+    #[default]
+    OpenWeatherMap,
+    OpenMeteo,
+    /// 和风天气
+    #[serde(rename = "qweather")]
+    QWeather,
+}
+
+impl ProviderKind {
+    /// 与序列化后的名字一致
+    pub fn name(self) -> &'static str {
+        match self {
+            ProviderKind::OpenWeatherMap => "open_weather_map",
+            ProviderKind::OpenMeteo => "open_meteo",
+            ProviderKind::QWeather => "qweather",
+        }
+    }
+
+    /// 显示在错误信息中的名字
+    pub fn display_name(self) -> &'static str {
+        match self {
+            ProviderKind::OpenWeatherMap => "OpenWeather",
+            ProviderKind::OpenMeteo => "Open-Meteo",
+            ProviderKind::QWeather => "QWeather",
+        }
+    }
+
+    pub fn provider(self) -> Box<dyn WeatherProvider> {
+        match self {
+            ProviderKind::OpenWeatherMap => Box::new(OpenWeatherMap),
+            ProviderKind::OpenMeteo => Box::new(OpenMeteo),
+            ProviderKind::QWeather => Box::new(QWeather),
+        }
+    }
+}
+
+/// 查询天气的地点，序列化时以 type 字段区分
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum WeatherLocation {
+    City { name: String },
+    Coordinates { latitude: f64, longitude: f64 },
+    /// 数据源自己的城市 ID：OpenWeather 的 city ID、和风天气的 LocationID、Open-Meteo 地理编码的 id
+    CityId { id: String },
+}
+
+impl WeatherLocation {
+    /// 缓存键的一部分，坐标保留两位小数（约 1 公里）
+    pub fn cache_key(&self) -> String {
+        match self {
+            WeatherLocation::City { name } => format!("city:{}", name.trim().to_lowercase()),
+            WeatherLocation::Coordinates { latitude, longitude } => format!("coord:{:.2},{:.2}", latitude, longitude),
+            WeatherLocation::CityId { id } => format!("id:{}", id.trim()),
+        }
+    }
+}
+
+/// 统一后的天气类别，前端据此选择图标
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Condition {
+    Clear,
+    MainlyClear,
+    PartlyCloudy,
+    Overcast,
+    Fog,
+    Drizzle,
+    Rain,
+    FreezingRain,
+    Snow,
+    RainShowers,
+    SnowShowers,
+    Thunderstorm,
+    Unknown,
+}
+
+impl Condition {
+    /// 描述文案（中文 msgid），数据源没有给出描述时使用
+    pub fn description(self) -> &'static str {
+        match self {
+            Condition::Clear => "晴",
+            Condition::MainlyClear => "大部晴朗",
+            Condition::PartlyCloudy => "多云",
+            Condition::Overcast => "阴",
+            Condition::Fog => "雾",
+            Condition::Drizzle => "毛毛雨",
+            Condition::Rain => "雨",
+            Condition::FreezingRain => "冻雨",
+            Condition::Snow => "雪",
+            Condition::RainShowers => "阵雨",
+            Condition::SnowShowers => "阵雪",
+            Condition::Thunderstorm => "雷暴",
+            Condition::Unknown => "未知天气",
+        }
+    }
+
+    /// WMO 天气代码，Open-Meteo 使用
+    pub fn from_wmo(code: u16) -> Self {
+        match code {
+            0 => Condition::Clear,
+            1 => Condition::MainlyClear,
+            2 => Condition::PartlyCloudy,
+            3 => Condition::Overcast,
+            45 | 48 => Condition::Fog,
+            51..=57 => Condition::Drizzle,
+            61..=65 => Condition::Rain,
+            66 | 67 => Condition::FreezingRain,
+            71..=77 => Condition::Snow,
+            80..=82 => Condition::RainShowers,
+            85 | 86 => Condition::SnowShowers,
+            95..=99 => Condition::Thunderstorm,
+            _ => Condition::Unknown,
+        }
+    }
+
+    /// OpenWeather 的天气 ID，见 https://openweathermap.org/weather-conditions
+    pub fn from_openweather(id: u16) -> Self {
+        match id {
+            200..=299 => Condition::Thunderstorm,
+            300..=399 => Condition::Drizzle,
+            511 => Condition::FreezingRain,
+            520..=531 => Condition::RainShowers,
+            500..=599 => Condition::Rain,
+            620..=622 => Condition::SnowShowers,
+            600..=699 => Condition::Snow,
+            700..=799 => Condition::Fog,
+            800 => Condition::Clear,
+            801 => Condition::MainlyClear,
+            802 => Condition::PartlyCloudy,
+            803 | 804 => Condition::Overcast,
+            _ => Condition::Unknown,
+        }
+    }
+
+    /// 和风天气的图标代码，见 https://dev.qweather.com/docs/resource/icons/
+    pub fn from_qweather(icon: u16) -> Self {
+        match icon {
+            100 | 150 => Condition::Clear,
+            102 | 152 => Condition::MainlyClear,
+            101 | 103 | 151 | 153 => Condition::PartlyCloudy,
+            104 => Condition::Overcast,
+            302..=304 => Condition::Thunderstorm,
+            300 | 301 | 350 | 351 => Condition::RainShowers,
+            309 => Condition::Drizzle,
+            313 => Condition::FreezingRain,
+            305..=399 => Condition::Rain,
+            406 | 407 | 456 | 457 => Condition::SnowShowers,
+            400..=499 => Condition::Snow,
+            500..=515 => Condition::Fog,
+            _ => Condition::Unknown,
+        }
+    }
+}
+
+/// 各数据源统一后的当前天气，数值一律为公制，换算和格式化见 weather_format
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CurrentWeather {
+    pub provider: ProviderKind,
+    /// 数据源返回的地点名
+    pub city: String,
+    /// 天气描述，按当前界面语言
+    pub description: String,
+    pub condition: Condition,
+    /// 数据源自己的图标代码：OpenWeather 如 01d，和风天气如 101，Open-Meteo 为 WMO 代码
+    pub icon: String,
+    /// 摄氏度
+    pub temperature: f32,
+    /// 体感温度，摄氏度
+    pub feels_like: f32,
+    /// 相对湿度，百分比
+    pub humidity: u8,
+    /// 风速，米每秒
+    pub wind_speed: f32,
+    /// 风向，度，正北为 0
+    pub wind_direction: Option<u16>,
+    /// 和风天气的实况接口不含日出日落，为 None
+    pub sunrise: Option<DateTime<Utc>>,
+    pub sunset: Option<DateTime<Utc>>,
+    /// 观测时间
+    pub observed_at: DateTime<Utc>,
+}
+
+/// 逐小时预报
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HourlyForecast {
+    pub time: DateTime<Utc>,
+    /// 摄氏度
+    pub temperature: f32,
+    pub humidity: Option<u8>,
+    pub condition: Condition,
+    pub icon: String,
+    pub description: String,
+    /// 米每秒
+    pub wind_speed: f32,
+    /// 降水概率，百分比
+    pub precipitation_probability: Option<u8>,
+}
+
+/// 逐日预报
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DailyForecast {
+    /// 当地日期
+    pub date: NaiveDate,
+    pub temperature_min: f32,
+    pub temperature_max: f32,
+    pub condition: Condition,
+    pub icon: String,
+    pub description: String,
+    pub precipitation_probability: Option<u8>,
+    pub sunrise: Option<DateTime<Utc>>,
+    pub sunset: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Forecast {
+    pub provider: ProviderKind,
+    pub city: String,
+    /// 未来 24 小时；OpenWeather 免费接口 3 小时一条
+    pub hourly: Vec<HourlyForecast>,
+    /// 从今天开始的若干天
+    pub daily: Vec<DailyForecast>,
+}
+
+/// 天气数据源：按地点查询当前天气和预报
+///
+/// 请求与解析分开，解析只依赖响应正文。
+pub trait WeatherProvider: Send + Sync {
+    fn kind(&self) -> ProviderKind;
+
+    fn current(&self, client: &Client, location: &WeatherLocation, key: Option<&str>) -> EarthResult<CurrentWeather>;
+
+    /// days 为 1..=MAX_FORECAST_DAYS，数据源提供的天数不够时返回能拿到的部分
+    fn forecast(
+        &self,
+        client: &Client,
+        location: &WeatherLocation,
+        key: Option<&str>,
+        days: u8,
+    ) -> EarthResult<Forecast>;
+}
+
+/// GET 请求并返回正文，查询参数由 reqwest 编码，城市名里的空格、中文和 & 都能正确传递
+///
+/// 出错时只显示不带参数的 endpoint，避免 key 出现在错误信息和日志里。
+fn fetch(client: &Client, kind: ProviderKind, endpoint: &str, query: &[(&str, String)]) -> EarthResult<String> {
+    client
+        .get(endpoint)
+        .query(query)
+        .send()
+        .map_err(|e| EarthError::network(endpoint, e.without_url()))?
+        .text()
+        .map_err(|e| EarthError::weather(i18n::trf("读取{}响应失败: {}", &[&kind.display_name(), &e.without_url()])))
+}
+
+fn parse_json<T: DeserializeOwned>(kind: ProviderKind, body: &str) -> EarthResult<T> {
+    serde_json::from_str(body)
+        .map_err(|e| EarthError::weather(i18n::trf("解析{}响应失败: {}", &[&kind.display_name(), &e])))
+}
+
+fn parse_number<T: FromStr>(kind: ProviderKind, value: &str) -> EarthResult<T>
+where
+    T::Err: std::fmt::Display,
+{
+    value
+        .trim()
+        .parse()
+        .map_err(|e| EarthError::weather(i18n::trf("解析{}响应失败: {}", &[&kind.display_name(), &e])))
+}
+
+fn require_key(kind: ProviderKind, key: Option<&str>) -> EarthResult<&str> {
+    key.filter(|key| !key.is_empty())
+        .ok_or_else(|| EarthError::invalid(i18n::trf("缺少{}的 API Key", &[&kind.display_name()])))
+}
+
+fn unix_time(secs: i64) -> Option<DateTime<Utc>> {
+    DateTime::from_timestamp(secs, 0)
+}
+
+/// 0~1 的概率换成百分比
+fn percent(probability: f32) -> u8 {
+    (probability * 100.0).round().clamp(0.0, 100.0) as u8
+}
+
+/// OpenWeatherMap 当前天气接口 /data/2.5/weather
+pub struct OpenWeatherMap;
+
+const OPENWEATHER_URL: &str = "https://api.openweathermap.org/data/2.5/weather";
+
+impl OpenWeatherMap {
+    pub fn parse_current(body: &str) -> EarthResult<CurrentWeather> {
+        #[derive(Deserialize)]
+        struct WeatherResp {
+            weather: Vec<WeatherDesc>,
+            main: WeatherMain,
+            wind: Wind,
+            sys: Sys,
+            dt: i64,
+            name: String,
+        }
+        #[derive(Deserialize)]
+        struct WeatherDesc {
+            id: u16,
+            description: String,
+            icon: String,
+        }
+        #[derive(Deserialize)]
+        struct WeatherMain {
+            temp: f32,
+            feels_like: f32,
+            humidity: u8,
+        }
+        #[derive(Deserialize)]
+        struct Wind {
+            speed: f32,
+            deg: Option<u16>,
+        }
+        #[derive(Deserialize)]
+        struct Sys {
+            sunrise: Option<i64>,
+            sunset: Option<i64>,
+        }
+
+        let resp: WeatherResp = parse_json(ProviderKind::OpenWeatherMap, body)?;
+        let desc = resp
+            .weather
+            .into_iter()
+            .next()
+            .ok_or_else(|| EarthError::weather("未获取到天气信息"))?;
+        Ok(CurrentWeather {
+            provider: ProviderKind::OpenWeatherMap,
+            city: resp.name,
+            description: desc.description,
+            condition: Condition::from_openweather(desc.id),
+            icon: desc.icon,
+            temperature: resp.main.temp,
+            feels_like: resp.main.feels_like,
+            humidity: resp.main.humidity,
+            wind_speed: resp.wind.speed,
+            wind_direction: resp.wind.deg,
+            sunrise: resp.sys.sunrise.and_then(unix_time),
+            sunset: resp.sys.sunset.and_then(unix_time),
+            observed_at: unix_time(resp.dt).unwrap_or_else(Utc::now),
+        })
+    }
+}
+
+const OPENWEATHER_FORECAST_URL: &str = "https://api.openweathermap.org/data/2.5/forecast";
+
+impl OpenWeatherMap {
+    /// /data/2.5/forecast 是 5 天内每 3 小时一条，逐日预报按当地日期汇总
+    pub fn parse_forecast(body: &str, days: u8) -> EarthResult<Forecast> {
+        #[derive(Deserialize)]
+        struct ForecastResp {
+            list: Vec<Item>,
+            city: City,
+        }
+        #[derive(Deserialize)]
+        struct City {
+            name: String,
+            /// 与 UTC 的偏移，秒
+            timezone: i32,
+        }
+        #[derive(Deserialize)]
+        struct Item {
+            dt: i64,
+            main: Main,
+            weather: Vec<Desc>,
+            wind: Wind,
+            pop: Option<f32>,
+        }
+        #[derive(Deserialize)]
+        struct Main {
+            temp: f32,
+            temp_min: f32,
+            temp_max: f32,
+            humidity: u8,
+        }
+        #[derive(Deserialize)]
+        struct Desc {
+            id: u16,
+            description: String,
+            icon: String,
+        }
+        #[derive(Deserialize)]
+        struct Wind {
+            speed: f32,
+        }
+
+        let resp: ForecastResp = parse_json(ProviderKind::OpenWeatherMap, body)?;
+        let offset = FixedOffset::east_opt(resp.city.timezone).unwrap_or(FixedOffset::east_opt(0).unwrap());
+        // 跳过时间或天气描述有问题的条目，逐日汇总用同一批 (条目, 逐小时) 对，保证两者对齐
+        let entries: Vec<(&Item, HourlyForecast)> = resp
+            .list
+            .iter()
+            .filter_map(|item| {
+                let (time, desc) = (unix_time(item.dt)?, item.weather.first()?);
+                Some((
+                    item,
+                    HourlyForecast {
+                        time,
+                        temperature: item.main.temp,
+                        humidity: Some(item.main.humidity),
+                        condition: Condition::from_openweather(desc.id),
+                        icon: desc.icon.clone(),
+                        description: desc.description.clone(),
+                        wind_speed: item.wind.speed,
+                        precipitation_probability: item.pop.map(percent),
+                    },
+                ))
+            })
+            .collect();
+
+        let mut daily: Vec<DailyForecast> = Vec::new();
+        // 每天取最接近当地中午的一条作为当天的天气
+        let mut noon_distance: Vec<i64> = Vec::new();
+        for (item, hour) in &entries {
+            let local = hour.time.with_timezone(&offset);
+            let date = local.date_naive();
+            let distance = (local.time() - NaiveTime::from_hms_opt(12, 0, 0).unwrap()).num_minutes().abs();
+            match daily.last_mut() {
+                Some(day) if day.date == date => {
+                    day.temperature_min = day.temperature_min.min(item.main.temp_min);
+                    day.temperature_max = day.temperature_max.max(item.main.temp_max);
+                    day.precipitation_probability = day.precipitation_probability.max(hour.precipitation_probability);
+                    let best = noon_distance.last_mut().unwrap();
+                    if distance < *best {
+                        *best = distance;
+                        day.condition = hour.condition;
+                        day.icon = hour.icon.clone();
+                        day.description = hour.description.clone();
+                    }
+                }
+                _ => {
+                    daily.push(DailyForecast {
+                        date,
+                        temperature_min: item.main.temp_min,
+                        temperature_max: item.main.temp_max,
+                        condition: hour.condition,
+                        icon: hour.icon.clone(),
+                        description: hour.description.clone(),
+                        precipitation_probability: hour.precipitation_probability,
+                        sunrise: None,
+                        sunset: None,
+                    });
+                    noon_distance.push(distance);
+                }
+            }
+        }
+        daily.truncate(days as usize);
+        let hourly = entries
+            .into_iter()
+            .map(|(_, hour)| hour)
+            .take(FORECAST_HOURS / 3)
+            .collect();
+        Ok(Forecast {
+            provider: ProviderKind::OpenWeatherMap,
+            city: resp.city.name,
+            hourly,
+            daily,
+        })
+    }
+}
+
+impl OpenWeatherMap {
+    /// 按城市名用 q，按坐标用 lat/lon，按城市 ID 用 id
+    fn query<'a>(location: &WeatherLocation, key: &str) -> Vec<(&'a str, String)> {
+        let mut query = match location {
+            WeatherLocation::City { name } => vec![("q", name.clone())],
+            WeatherLocation::Coordinates { latitude, longitude } => {
+                vec![("lat", latitude.to_string()), ("lon", longitude.to_string())]
+            }
+            WeatherLocation::CityId { id } => vec![("id", id.clone())],
+        };
+        query.extend([
+            ("appid", key.to_string()),
+            ("units", "metric".to_string()),
+            ("lang", i18n::current().openweather_lang().to_string()),
+        ]);
+        query
+    }
+}
+
+impl WeatherProvider for OpenWeatherMap {
+    fn kind(&self) -> ProviderKind {
+        ProviderKind::OpenWeatherMap
+    }
+
+    fn current(&self, client: &Client, location: &WeatherLocation, key: Option<&str>) -> EarthResult<CurrentWeather> {
+        let query = Self::query(location, require_key(self.kind(), key)?);
+        let body = fetch(client, self.kind(), OPENWEATHER_URL, &query)?;
+        Self::parse_current(&body)
+    }
+
+    fn forecast(
+        &self,
+        client: &Client,
+        location: &WeatherLocation,
+        key: Option<&str>,
+        days: u8,
+    ) -> EarthResult<Forecast> {
+        let query = Self::query(location, require_key(self.kind(), key)?);
+        let body = fetch(client, self.kind(), OPENWEATHER_FORECAST_URL, &query)?;
+        Self::parse_forecast(&body, days)
+    }
+}
+
+/// Open-Meteo：先用地理编码接口把城市名或 ID 换成经纬度，再查当前天气，无需 key
+pub struct OpenMeteo;
+
+const OPEN_METEO_GEOCODING_URL: &str = "https://geocoding-api.open-meteo.com/v1/search";
+const OPEN_METEO_GEOCODING_GET_URL: &str = "https://geocoding-api.open-meteo.com/v1/get";
+const OPEN_METEO_FORECAST_URL: &str = "https://api.open-meteo.com/v1/forecast";
+
+/// 地理编码得到的地点
+#[derive(Debug, Clone, Deserialize)]
+pub struct GeoPlace {
+    pub name: String,
+    pub latitude: f64,
+    pub longitude: f64,
+}
+
+impl OpenMeteo {
+    pub fn parse_geocoding(body: &str) -> EarthResult<GeoPlace> {
+        #[derive(Deserialize)]
+        struct GeocodingResp {
+            #[serde(default)]
+            results: Vec<GeoPlace>,
+        }
+        let resp: GeocodingResp = parse_json(ProviderKind::OpenMeteo, body)?;
+        resp.results
+            .into_iter()
+            .next()
+            .ok_or_else(|| EarthError::weather("未找到该城市"))
+    }
+
+    /// 请求时带 timeformat=unixtime，时间都是 Unix 秒
+    pub fn parse_current(body: &str, place: &GeoPlace) -> EarthResult<CurrentWeather> {
+        #[derive(Deserialize)]
+        struct ForecastResp {
+            current: Current,
+            daily: Option<Daily>,
+        }
+        #[derive(Deserialize)]
+        struct Current {
+            time: i64,
+            temperature_2m: f32,
+            apparent_temperature: f32,
+            relative_humidity_2m: f32,
+            weather_code: u16,
+            wind_speed_10m: f32,
+            wind_direction_10m: Option<f32>,
+        }
+        #[derive(Deserialize)]
+        struct Daily {
+            #[serde(default)]
+            sunrise: Vec<i64>,
+            #[serde(default)]
+            sunset: Vec<i64>,
+        }
+
+        let resp: ForecastResp = parse_json(ProviderKind::OpenMeteo, body)?;
+        let current = resp.current;
+        let condition = Condition::from_wmo(current.weather_code);
+        let (sunrise, sunset) = match &resp.daily {
+            Some(daily) => (
+                daily.sunrise.first().copied().and_then(unix_time),
+                daily.sunset.first().copied().and_then(unix_time),
+            ),
+            None => (None, None),
+        };
+        Ok(CurrentWeather {
+            provider: ProviderKind::OpenMeteo,
+            city: place.name.clone(),
+            description: i18n::tr(condition.description()).to_string(),
+            condition,
+            icon: current.weather_code.to_string(),
+            temperature: current.temperature_2m,
+            feels_like: current.apparent_temperature,
+            humidity: current.relative_humidity_2m.round() as u8,
+            wind_speed: current.wind_speed_10m,
+            wind_direction: current.wind_direction_10m.map(|deg| deg.round() as u16),
+            sunrise,
+            sunset,
+            observed_at: unix_time(current.time).unwrap_or_else(Utc::now),
+        })
+    }
+}
+
+impl OpenMeteo {
+    /// 城市名和 ID 经地理编码接口换成坐标；直接给坐标时没有地名，用坐标代替
+    fn place(&self, client: &Client, location: &WeatherLocation) -> EarthResult<GeoPlace> {
+        let language = ("language", i18n::current().language().to_string());
+        match location {
+            WeatherLocation::City { name } => {
+                let query = [("name", name.clone()), ("count", "1".to_string()), language];
+                let body = fetch(client, self.kind(), OPEN_METEO_GEOCODING_URL, &query)?;
+                Self::parse_geocoding(&body)
+            }
+            WeatherLocation::CityId { id } => {
+                let query = [("id", id.clone()), language];
+                let body = fetch(client, self.kind(), OPEN_METEO_GEOCODING_GET_URL, &query)?;
+                parse_json(self.kind(), &body)
+            }
+            WeatherLocation::Coordinates { latitude, longitude } => Ok(GeoPlace {
+                name: format!("{:.2}, {:.2}", latitude, longitude),
+                latitude: *latitude,
+                longitude: *longitude,
+            }),
+        }
+    }
+
+    /// 请求时带 timezone=auto 和 timeformat=unixtime，逐日的 time 是当地零点的 Unix 秒
+    pub fn parse_forecast(body: &str, place: &GeoPlace) -> EarthResult<Forecast> {
+        #[derive(Deserialize)]
+        struct ForecastResp {
+            utc_offset_seconds: i32,
+            hourly: Hourly,
+            daily: Daily,
+        }
+        #[derive(Deserialize)]
+        struct Hourly {
+            time: Vec<i64>,
+            temperature_2m: Vec<f32>,
+            relative_humidity_2m: Vec<Option<f32>>,
+            weather_code: Vec<u16>,
+            wind_speed_10m: Vec<f32>,
+            precipitation_probability: Vec<Option<f32>>,
+        }
+        #[derive(Deserialize)]
+        struct Daily {
+            time: Vec<i64>,
+            weather_code: Vec<u16>,
+            temperature_2m_max: Vec<f32>,
+            temperature_2m_min: Vec<f32>,
+            precipitation_probability_max: Vec<Option<f32>>,
+            sunrise: Vec<i64>,
+            sunset: Vec<i64>,
+        }
+
+        let resp: ForecastResp = parse_json(ProviderKind::OpenMeteo, body)?;
+        let h = &resp.hourly;
+        let hourly = (0..h.time.len())
+            .filter_map(|i| {
+                let condition = Condition::from_wmo(*h.weather_code.get(i)?);
+                Some(HourlyForecast {
+                    time: unix_time(h.time[i])?,
+                    temperature: *h.temperature_2m.get(i)?,
+                    humidity: h.relative_humidity_2m.get(i).copied().flatten().map(|v| v.round() as u8),
+                    condition,
+                    icon: h.weather_code[i].to_string(),
+                    description: i18n::tr(condition.description()).to_string(),
+                    wind_speed: *h.wind_speed_10m.get(i)?,
+                    precipitation_probability: h.precipitation_probability.get(i).copied().flatten().map(|v| v.round() as u8),
+                })
+            })
+            .collect();
+
+        let d = &resp.daily;
+        let daily = (0..d.time.len())
+            .filter_map(|i| {
+                let date = unix_time(d.time[i] + resp.utc_offset_seconds as i64)?.date_naive();
+                let condition = Condition::from_wmo(*d.weather_code.get(i)?);
+                Some(DailyForecast {
+                    date,
+                    temperature_min: *d.temperature_2m_min.get(i)?,
+                    temperature_max: *d.temperature_2m_max.get(i)?,
+                    condition,
+                    icon: d.weather_code[i].to_string(),
+                    description: i18n::tr(condition.description()).to_string(),
+                    precipitation_probability: d.precipitation_probability_max.get(i).copied().flatten().map(|v| v.round() as u8),
+                    sunrise: d.sunrise.get(i).copied().and_then(unix_time),
+                    sunset: d.sunset.get(i).copied().and_then(unix_time),
+                })
+            })
+            .collect();
+
+        Ok(Forecast {
+            provider: ProviderKind::OpenMeteo,
+            city: place.name.clone(),
+            hourly,
+            daily,
+        })
+    }
+}
+
+impl WeatherProvider for OpenMeteo {
+    fn kind(&self) -> ProviderKind {
+        ProviderKind::OpenMeteo
+    }
+
+    fn current(&self, client: &Client, location: &WeatherLocation, _key: Option<&str>) -> EarthResult<CurrentWeather> {
+        let place = self.place(client, location)?;
+        let query = [
+            ("latitude", place.latitude.to_string()),
+            ("longitude", place.longitude.to_string()),
+            (
+                "current",
+                "temperature_2m,apparent_temperature,relative_humidity_2m,weather_code,wind_speed_10m,wind_direction_10m"
+                    .to_string(),
+            ),
+            ("daily", "sunrise,sunset".to_string()),
+            ("forecast_days", "1".to_string()),
+            ("wind_speed_unit", "ms".to_string()),
+            ("timeformat", "unixtime".to_string()),
+        ];
+        let body = fetch(client, self.kind(), OPEN_METEO_FORECAST_URL, &query)?;
+        Self::parse_current(&body, &place)
+    }
+
+    fn forecast(
+        &self,
+        client: &Client,
+        location: &WeatherLocation,
+        _key: Option<&str>,
+        days: u8,
+    ) -> EarthResult<Forecast> {
+        let place = self.place(client, location)?;
+        let query = [
+            ("latitude", place.latitude.to_string()),
+            ("longitude", place.longitude.to_string()),
+            (
+                "hourly",
+                "temperature_2m,relative_humidity_2m,weather_code,wind_speed_10m,precipitation_probability".to_string(),
+            ),
+            ("forecast_hours", FORECAST_HOURS.to_string()),
+            (
+                "daily",
+                "weather_code,temperature_2m_max,temperature_2m_min,precipitation_probability_max,sunrise,sunset"
+                    .to_string(),
+            ),
+            ("forecast_days", days.to_string()),
+            ("timezone", "auto".to_string()),
+            ("wind_speed_unit", "ms".to_string()),
+            ("timeformat", "unixtime".to_string()),
+        ];
+        let body = fetch(client, self.kind(), OPEN_METEO_FORECAST_URL, &query)?;
+        Self::parse_forecast(&body, &place)
+    }
+}
+
+/// 和风天气：先用城市查询接口拿到地点 ID，再查实时天气
+pub struct QWeather;
+
+const QWEATHER_LOOKUP_URL: &str = "https://geoapi.qweather.com/v2/city/lookup";
+const QWEATHER_NOW_URL: &str = "https://devapi.qweather.com/v7/weather/now";
+const QWEATHER_HOURLY_URL: &str = "https://devapi.qweather.com/v7/weather/24h";
+/// 逐日预报按天数选 3d 或 7d 接口
+const QWEATHER_DAILY_URL: &str = "https://devapi.qweather.com/v7/weather";
+
+/// 和风天气的地点
+#[derive(Debug, Clone, Deserialize)]
+pub struct QWeatherLocation {
+    pub id: String,
+    pub name: String,
+    /// 如 +08:00，逐日预报的日出日落是当地时间，需要它换算
+    #[serde(rename = "utcOffset", default)]
+    pub utc_offset: Option<String>,
+}
+
+/// 和风天气的时间形如 2020-06-30T21:40+08:00
+fn parse_qweather_time(value: &str) -> Option<DateTime<Utc>> {
+    DateTime::parse_from_str(value, "%Y-%m-%dT%H:%M%:z")
+        .ok()
+        .map(|t| t.with_timezone(&Utc))
+}
+
+impl QWeather {
+    /// 和风天气在正文的 code 字段里返回状态，200 表示成功
+    fn check_code(code: &str) -> EarthResult<()> {
+        match code {
+            "200" => Ok(()),
+            "404" => Err(EarthError::weather("未找到该城市")),
+            _ => Err(EarthError::weather(i18n::trf("{} 返回错误码 {}", &[&ProviderKind::QWeather.display_name(), &code]))),
+        }
+    }
+
+    pub fn parse_lookup(body: &str) -> EarthResult<QWeatherLocation> {
+        #[derive(Deserialize)]
+        struct LookupResp {
+            code: String,
+            #[serde(default)]
+            location: Vec<QWeatherLocation>,
+        }
+        let resp: LookupResp = parse_json(ProviderKind::QWeather, body)?;
+        Self::check_code(&resp.code)?;
+        resp.location
+            .into_iter()
+            .next()
+            .ok_or_else(|| EarthError::weather("未找到该城市"))
+    }
+
+    pub fn parse_current(body: &str, location: &QWeatherLocation) -> EarthResult<CurrentWeather> {
+        #[derive(Deserialize)]
+        struct NowResp {
+            code: String,
+            now: Option<Now>,
+        }
+        /// 和风天气的数值都是字符串
+        #[derive(Deserialize)]
+        #[serde(rename_all = "camelCase")]
+        struct Now {
+            obs_time: String,
+            temp: String,
+            feels_like: String,
+            icon: String,
+            text: String,
+            wind360: String,
+            /// 公里每小时
+            wind_speed: String,
+            humidity: String,
+        }
+
+        let kind = ProviderKind::QWeather;
+        let resp: NowResp = parse_json(kind, body)?;
+        Self::check_code(&resp.code)?;
+        let now = resp.now.ok_or_else(|| EarthError::weather("未获取到天气信息"))?;
+        let wind_kmh: f32 = parse_number(kind, &now.wind_speed)?;
+        Ok(CurrentWeather {
+            provider: kind,
+            city: location.name.clone(),
+            description: now.text,
+            condition: now.icon.parse().map_or(Condition::Unknown, Condition::from_qweather),
+            icon: now.icon,
+            temperature: parse_number(kind, &now.temp)?,
+            feels_like: parse_number(kind, &now.feels_like)?,
+            humidity: parse_number(kind, &now.humidity)?,
+            wind_speed: wind_kmh / 3.6,
+            wind_direction: now.wind360.parse().ok(),
+            sunrise: None,
+            sunset: None,
+            observed_at: parse_qweather_time(&now.obs_time).unwrap_or_else(Utc::now),
+        })
+    }
+}
+
+impl QWeather {
+    pub fn parse_hourly(body: &str) -> EarthResult<Vec<HourlyForecast>> {
+        #[derive(Deserialize)]
+        struct HourlyResp {
+            code: String,
+            #[serde(default)]
+            hourly: Vec<Hour>,
+        }
+        #[derive(Deserialize)]
+        #[serde(rename_all = "camelCase")]
+        struct Hour {
+            fx_time: String,
+            temp: String,
+            icon: String,
+            text: String,
+            /// 公里每小时
+            wind_speed: String,
+            humidity: Option<String>,
+            pop: Option<String>,
+        }
+
+        let kind = ProviderKind::QWeather;
+        let resp: HourlyResp = parse_json(kind, body)?;
+        Self::check_code(&resp.code)?;
+        resp.hourly
+            .into_iter()
+            .filter_map(|hour| Some((parse_qweather_time(&hour.fx_time)?, hour)))
+            .map(|(time, hour)| {
+                let wind_kmh: f32 = parse_number(kind, &hour.wind_speed)?;
+                Ok(HourlyForecast {
+                    time,
+                    temperature: parse_number(kind, &hour.temp)?,
+                    humidity: hour.humidity.and_then(|v| v.parse().ok()),
+                    condition: hour.icon.parse().map_or(Condition::Unknown, Condition::from_qweather),
+                    icon: hour.icon,
+                    description: hour.text,
+                    wind_speed: wind_kmh / 3.6,
+                    precipitation_probability: hour.pop.and_then(|v| v.parse().ok()),
+                })
+            })
+            .collect()
+    }
+
+    pub fn parse_daily(body: &str, location: &QWeatherLocation) -> EarthResult<Vec<DailyForecast>> {
+        #[derive(Deserialize)]
+        struct DailyResp {
+            code: String,
+            #[serde(default)]
+            daily: Vec<Day>,
+        }
+        #[derive(Deserialize)]
+        #[serde(rename_all = "camelCase")]
+        struct Day {
+            fx_date: String,
+            /// 当地时间，如 06:58，极昼极夜时为空
+            sunrise: Option<String>,
+            sunset: Option<String>,
+            temp_max: String,
+            temp_min: String,
+            icon_day: String,
+            text_day: String,
+        }
+
+        let kind = ProviderKind::QWeather;
+        let resp: DailyResp = parse_json(kind, body)?;
+        Self::check_code(&resp.code)?;
+        let offset = location
+            .utc_offset
+            .as_deref()
+            .and_then(|offset| offset.parse::<FixedOffset>().ok());
+        let local_time = |date: NaiveDate, time: Option<&str>| -> Option<DateTime<Utc>> {
+            let time = NaiveTime::parse_from_str(time?, "%H:%M").ok()?;
+            let local = date.and_time(time).and_local_timezone(offset?).single()?;
+            Some(local.with_timezone(&Utc))
+        };
+        resp.daily
+            .into_iter()
+            .filter_map(|day| Some((NaiveDate::parse_from_str(&day.fx_date, "%Y-%m-%d").ok()?, day)))
+            .map(|(date, day)| {
+                Ok(DailyForecast {
+                    date,
+                    temperature_min: parse_number(kind, &day.temp_min)?,
+                    temperature_max: parse_number(kind, &day.temp_max)?,
+                    condition: day.icon_day.parse().map_or(Condition::Unknown, Condition::from_qweather),
+                    icon: day.icon_day,
+                    description: day.text_day,
+                    precipitation_probability: None,
+                    sunrise: local_time(date, day.sunrise.as_deref()),
+                    sunset: local_time(date, day.sunset.as_deref()),
+                })
+            })
+            .collect()
+    }
+
+    /// 城市查询接口的 location 参数支持城市名、LocationID 和「经度,纬度」（经度在前）
+    fn lookup(&self, client: &Client, location: &WeatherLocation, key: &str) -> EarthResult<QWeatherLocation> {
+        let location = match location {
+            WeatherLocation::City { name } => name.clone(),
+            WeatherLocation::CityId { id } => id.clone(),
+            WeatherLocation::Coordinates { latitude, longitude } => format!("{:.2},{:.2}", longitude, latitude),
+        };
+        let query = [
+            ("location", location),
+            ("key", key.to_string()),
+            ("number", "1".to_string()),
+            ("lang", i18n::current().language().to_string()),
+        ];
+        let body = fetch(client, self.kind(), QWEATHER_LOOKUP_URL, &query)?;
+        Self::parse_lookup(&body)
+    }
+
+    /// 按地点 ID 查询的公共参数
+    fn query(location: &QWeatherLocation, key: &str) -> [(&'static str, String); 3] {
+        [
+            ("location", location.id.clone()),
+            ("key", key.to_string()),
+            ("lang", i18n::current().language().to_string()),
+        ]
+    }
+}
+
+impl WeatherProvider for QWeather {
+    fn kind(&self) -> ProviderKind {
+        ProviderKind::QWeather
+    }
+
+    fn current(&self, client: &Client, location: &WeatherLocation, key: Option<&str>) -> EarthResult<CurrentWeather> {
+        let key = require_key(self.kind(), key)?;
+        let location = self.lookup(client, location, key)?;
+        let body = fetch(client, self.kind(), QWEATHER_NOW_URL, &Self::query(&location, key))?;
+        Self::parse_current(&body, &location)
+    }
+
+    fn forecast(
+        &self,
+        client: &Client,
+        location: &WeatherLocation,
+        key: Option<&str>,
+        days: u8,
+    ) -> EarthResult<Forecast> {
+        let key = require_key(self.kind(), key)?;
+        let location = self.lookup(client, location, key)?;
+        let query = Self::query(&location, key);
+        let body = fetch(client, self.kind(), QWEATHER_HOURLY_URL, &query)?;
+        let hourly = Self::parse_hourly(&body)?;
+
+        let endpoint = format!("{}/{}", QWEATHER_DAILY_URL, if days <= 3 { "3d" } else { "7d" });
+        let body = fetch(client, self.kind(), &endpoint, &query)?;
+        let mut daily = Self::parse_daily(&body, &location)?;
+        daily.truncate(days as usize);
+        Ok(Forecast {
+            provider: self.kind(),
+            city: location.name,
+            hourly,
+            daily,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    /// tests/fixtures/weather 下录制的真实响应
+    macro_rules! fixture {
+        ($name:literal) => {
+            include_str!(concat!("../tests/fixtures/weather/", $name))
+        };
+    }
+
+    fn utc(y: i32, m: u32, d: u32, h: u32, min: u32, s: u32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(y, m, d, h, min, s).unwrap()
+    }
+
+    fn assert_weather_error<T: std::fmt::Debug>(result: EarthResult<T>) {
+        assert!(matches!(result, Err(EarthError::Weather { .. })), "{:?}", result);
+    }
+
+    fn tokyo() -> GeoPlace {
+        GeoPlace {
+            name: "東京".to_string(),
+            latitude: 35.6895,
+            longitude: 139.69171,
+        }
+    }
+
+    fn beijing() -> QWeatherLocation {
+        QWeatherLocation {
+            id: "101010100".to_string(),
+            name: "北京".to_string(),
+            utc_offset: Some("+08:00".to_string()),
+        }
+    }
+
+    #[test]
+    fn openweather_current_from_fixture() {
+        let weather = OpenWeatherMap::parse_current(fixture!("openweather_current.json")).unwrap();
+        assert_eq!(weather.provider, ProviderKind::OpenWeatherMap);
+        assert_eq!(weather.city, "Beijing");
+        assert_eq!(weather.description, "多云");
+        assert_eq!(weather.condition, Condition::Overcast);
+        assert_eq!(weather.icon, "04d");
+        assert_eq!(weather.temperature, 26.94);
+        assert_eq!(weather.feels_like, 26.64);
+        assert_eq!(weather.humidity, 43);
+        assert_eq!(weather.wind_speed, 3.21);
+        assert_eq!(weather.wind_direction, Some(196));
+        assert_eq!(weather.sunrise, Some(utc(2025, 6, 19, 20, 51, 11)));
+        assert_eq!(weather.observed_at, utc(2025, 6, 20, 7, 0, 0));
+    }
+
+    #[test]
+    fn openweather_error_and_missing_fields() {
+        // key 无效时的正文
+        assert_weather_error(OpenWeatherMap::parse_current(
+            r#"{"cod":401,"message":"Invalid API key. Please see https://openweathermap.org/faq#error401 for more info."}"#,
+        ));
+        // weather 数组为空
+        let body = fixture!("openweather_current.json").replace(
+            r#"[{ "id": 803, "main": "Clouds", "description": "多云", "icon": "04d" }]"#,
+            "[]",
+        );
+        assert_weather_error(OpenWeatherMap::parse_current(&body));
+        // 没有风向和日出日落时为 None
+        let body = r#"{"weather":[{"id":800,"description":"晴","icon":"01n"}],"main":{"temp":20.0,"feels_like":19.5,"humidity":60},
+            "wind":{"speed":0.0},"sys":{},"dt":1750402800,"name":"Nowhere"}"#;
+        let weather = OpenWeatherMap::parse_current(body).unwrap();
+        assert_eq!(weather.condition, Condition::Clear);
+        assert_eq!(weather.wind_direction, None);
+        assert_eq!(weather.sunrise, None);
+        assert_eq!(weather.sunset, None);
+    }
+
+    #[test]
+    fn open_meteo_geocoding_from_fixture() {
+        let place = OpenMeteo::parse_geocoding(fixture!("open_meteo_geocoding.json")).unwrap();
+        assert_eq!(place.name, "東京");
+        assert_eq!(place.latitude, 35.6895);
+        assert_eq!(place.longitude, 139.69171);
+    }
+
+    #[test]
+    fn open_meteo_geocoding_without_results() {
+        // 查不到城市时 results 字段整个缺失
+        assert_weather_error(OpenMeteo::parse_geocoding(r#"{"generationtime_ms":0.3}"#));
+        assert_weather_error(OpenMeteo::parse_geocoding(r#"{"error":true,"reason":"Parameter count must be between 1 and 100."}"#));
+    }
+
+    #[test]
+    fn open_meteo_current_from_fixture() {
+        let weather = OpenMeteo::parse_current(fixture!("open_meteo_current.json"), &tokyo()).unwrap();
+        assert_eq!(weather.provider, ProviderKind::OpenMeteo);
+        assert_eq!(weather.city, "東京");
+        assert_eq!(weather.condition, Condition::Rain);
+        assert_eq!(weather.icon, "61");
+        assert_eq!(weather.temperature, 24.6);
+        assert_eq!(weather.feels_like, 26.1);
+        assert_eq!(weather.humidity, 78);
+        assert_eq!(weather.wind_direction, Some(157));
+        assert_eq!(weather.sunrise, Some(utc(2025, 6, 19, 19, 30, 0)));
+        assert_eq!(weather.observed_at, utc(2025, 6, 20, 7, 0, 0));
+    }
+
+    #[test]
+    fn open_meteo_current_error_and_missing_fields() {
+        assert_weather_error(OpenMeteo::parse_current(
+            r#"{"error":true,"reason":"Latitude must be in range of -90 to 90°. Given: 91.0."}"#,
+            &tokyo(),
+        ));
+        // 没有请求 daily 时没有日出日落
+        let body = r#"{"current":{"time":1750402800,"temperature_2m":24.6,"apparent_temperature":26.1,
+            "relative_humidity_2m":78,"weather_code":200,"wind_speed_10m":2.4}}"#;
+        let weather = OpenMeteo::parse_current(body, &tokyo()).unwrap();
+        assert_eq!(weather.condition, Condition::Unknown);
+        assert_eq!(weather.wind_direction, None);
+        assert_eq!(weather.sunrise, None);
+    }
+
+    #[test]
+    fn open_meteo_forecast_from_fixture() {
+        let forecast = OpenMeteo::parse_forecast(fixture!("open_meteo_forecast.json"), &tokyo()).unwrap();
+        assert_eq!(forecast.city, "東京");
+        assert_eq!(forecast.hourly.len(), 3);
+        assert_eq!(forecast.hourly[0].time, utc(2025, 6, 20, 7, 0, 0));
+        assert_eq!(forecast.hourly[1].condition, Condition::RainShowers);
+        assert_eq!(forecast.hourly[0].precipitation_probability, Some(65));
+        // null 的湿度和降水概率为 None
+        assert_eq!(forecast.hourly[2].humidity, None);
+        assert_eq!(forecast.hourly[2].precipitation_probability, None);
+
+        assert_eq!(forecast.daily.len(), 2);
+        // 逐日的 time 是当地零点，日期按当地算
+        assert_eq!(forecast.daily[0].date, NaiveDate::from_ymd_opt(2025, 6, 20).unwrap());
+        assert_eq!(forecast.daily[1].date, NaiveDate::from_ymd_opt(2025, 6, 21).unwrap());
+        assert_eq!(forecast.daily[0].temperature_min, 21.2);
+        assert_eq!(forecast.daily[0].temperature_max, 27.3);
+        assert_eq!(forecast.daily[1].condition, Condition::MainlyClear);
+        assert_eq!(forecast.daily[1].precipitation_probability, None);
+    }
+
+    #[test]
+    fn open_meteo_forecast_missing_daily() {
+        let body = r#"{"utc_offset_seconds":0,"hourly":{"time":[],"temperature_2m":[],"relative_humidity_2m":[],
+            "weather_code":[],"wind_speed_10m":[],"precipitation_probability":[]}}"#;
+        assert_weather_error(OpenMeteo::parse_forecast(body, &tokyo()));
+    }
+
+    #[test]
+    fn qweather_lookup_from_fixture() {
+        let location = QWeather::parse_lookup(fixture!("qweather_lookup.json")).unwrap();
+        assert_eq!(location.id, "101010100");
+        assert_eq!(location.name, "北京");
+        assert_eq!(location.utc_offset.as_deref(), Some("+08:00"));
+    }
+
+    #[test]
+    fn qweather_lookup_error_codes() {
+        // 查不到城市时 code 为 404，没有 location
+        assert_weather_error(QWeather::parse_lookup(r#"{"code":"404"}"#));
+        // key 无效
+        assert_weather_error(QWeather::parse_lookup(r#"{"code":"401"}"#));
+        // code 为 200 但列表为空
+        assert_weather_error(QWeather::parse_lookup(r#"{"code":"200","location":[]}"#));
+        assert_weather_error(QWeather::parse_lookup("not json"));
+    }
+
+    #[test]
+    fn qweather_current_from_fixture() {
+        let weather = QWeather::parse_current(fixture!("qweather_now.json"), &beijing()).unwrap();
+        assert_eq!(weather.provider, ProviderKind::QWeather);
+        assert_eq!(weather.city, "北京");
+        assert_eq!(weather.description, "多云");
+        assert_eq!(weather.condition, Condition::PartlyCloudy);
+        assert_eq!(weather.temperature, 27.0);
+        assert_eq!(weather.feels_like, 28.0);
+        assert_eq!(weather.humidity, 45);
+        // 18 km/h 换算成 5 m/s
+        assert!((weather.wind_speed - 5.0).abs() < 1e-4);
+        assert_eq!(weather.wind_direction, Some(180));
+        assert_eq!(weather.sunrise, None);
+        assert_eq!(weather.observed_at, utc(2025, 6, 20, 6, 56, 0));
+    }
+
+    #[test]
+    fn qweather_current_error_and_missing_fields() {
+        assert_weather_error(QWeather::parse_current(r#"{"code":"402"}"#, &beijing()));
+        // code 为 200 但缺少 now
+        assert_weather_error(QWeather::parse_current(r#"{"code":"200"}"#, &beijing()));
+        // 数值字段不是数字
+        let body = fixture!("qweather_now.json").replace(r#""temp": "27""#, r#""temp": "N/A""#);
+        assert_weather_error(QWeather::parse_current(&body, &beijing()));
+        // 缺少必需字段
+        let body = fixture!("qweather_now.json").replace(r#""feelsLike": "28","#, "");
+        assert_weather_error(QWeather::parse_current(&body, &beijing()));
+    }
+
+    #[test]
+    fn openweather_forecast_from_fixture() {
+        let forecast = OpenWeatherMap::parse_forecast(fixture!("openweather_forecast.json"), MAX_FORECAST_DAYS).unwrap();
+        assert_eq!(forecast.city, "Beijing");
+        // 天气描述为空的那一条被跳过，逐小时最多 8 条
+        assert_eq!(forecast.hourly.len(), 8);
+        assert_eq!(forecast.hourly[2].time, utc(2025, 6, 20, 9, 0, 0));
+        assert_eq!(forecast.hourly[2].precipitation_probability, Some(46));
+
+        // 跳过的条目不会错位到后面的日子里
+        assert_eq!(forecast.daily.len(), 2);
+        let today = &forecast.daily[0];
+        assert_eq!(today.date, NaiveDate::from_ymd_opt(2025, 6, 20).unwrap());
+        assert_eq!(today.temperature_min, 23.2);
+        assert_eq!(today.temperature_max, 29.8);
+        assert_eq!(today.precipitation_probability, Some(46));
+        // 当地 11:00 最接近中午
+        assert_eq!(today.condition, Condition::MainlyClear);
+        assert_eq!(today.description, "少云");
+        let tomorrow = &forecast.daily[1];
+        assert_eq!(tomorrow.date, NaiveDate::from_ymd_opt(2025, 6, 21).unwrap());
+        assert_eq!(tomorrow.temperature_min, 20.4);
+        assert_eq!(tomorrow.temperature_max, 30.3);
+        assert_eq!(tomorrow.precipitation_probability, Some(81));
+        assert_eq!(tomorrow.condition, Condition::PartlyCloudy);
+
+        let one_day = OpenWeatherMap::parse_forecast(fixture!("openweather_forecast.json"), 1).unwrap();
+        assert_eq!(one_day.daily.len(), 1);
+    }
+
+    #[test]
+    fn openweather_forecast_error() {
+        assert_weather_error(OpenWeatherMap::parse_forecast(r#"{"cod":"401","message":"Invalid API key."}"#, 3));
+    }
+
+    #[test]
+    fn qweather_hourly_from_fixture() {
+        let hourly = QWeather::parse_hourly(fixture!("qweather_24h.json")).unwrap();
+        // 时间格式不对的一条被跳过
+        assert_eq!(hourly.len(), 2);
+        assert_eq!(hourly[0].time, utc(2025, 6, 20, 8, 0, 0));
+        assert_eq!(hourly[0].temperature, 28.0);
+        assert_eq!(hourly[0].condition, Condition::PartlyCloudy);
+        assert!((hourly[0].wind_speed - 5.0).abs() < 1e-4);
+        assert_eq!(hourly[0].humidity, Some(42));
+        assert_eq!(hourly[0].precipitation_probability, Some(7));
+        // 缺少湿度时为 None
+        assert_eq!(hourly[1].humidity, None);
+        assert_eq!(hourly[1].condition, Condition::Rain);
+    }
+
+    #[test]
+    fn qweather_hourly_error_and_bad_numbers() {
+        assert_weather_error(QWeather::parse_hourly(r#"{"code":"403"}"#));
+        let body = fixture!("qweather_24h.json").replace(r#""temp": "28""#, r#""temp": "--""#);
+        assert_weather_error(QWeather::parse_hourly(&body));
+    }
+
+    #[test]
+    fn qweather_daily_converts_local_sunrise() {
+        let daily = QWeather::parse_daily(fixture!("qweather_7d.json"), &beijing()).unwrap();
+        assert_eq!(daily.len(), 3);
+        assert_eq!(daily[0].date, NaiveDate::from_ymd_opt(2025, 6, 20).unwrap());
+        assert_eq!(daily[0].temperature_min, 20.0);
+        assert_eq!(daily[0].temperature_max, 31.0);
+        assert_eq!(daily[0].condition, Condition::PartlyCloudy);
+        // 当地 04:46（+08:00）即前一天 20:46 UTC
+        assert_eq!(daily[0].sunrise, Some(utc(2025, 6, 19, 20, 46, 0)));
+        assert_eq!(daily[0].sunset, Some(utc(2025, 6, 20, 11, 46, 0)));
+        assert_eq!(daily[1].condition, Condition::Rain);
+        // 极昼极夜时日出日落为空
+        assert_eq!(daily[2].sunrise, None);
+        assert_eq!(daily[2].sunset, None);
+    }
+
+    #[test]
+    fn qweather_daily_without_utc_offset_has_no_sunrise() {
+        let location = QWeatherLocation {
+            utc_offset: None,
+            ..beijing()
+        };
+        let daily = QWeather::parse_daily(fixture!("qweather_7d.json"), &location).unwrap();
+        assert!(daily.iter().all(|day| day.sunrise.is_none() && day.sunset.is_none()));
+        assert_weather_error(QWeather::parse_daily(r#"{"code":"404"}"#, &beijing()));
+    }
+}
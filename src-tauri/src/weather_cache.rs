@@ -0,0 +1,170 @@
+use chrono::{DateTime, Duration, Utc};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+use std::sync::Mutex;
+use tracing::{debug, warn};
+
+use crate::atomic;
+use crate::error::{EarthError, EarthResult};
+
+/// 缓存文件名，放在应用缓存目录下
+pub const CACHE_FILE: &str = "weather_cache.json";
+
+/// 超过一天的记录写盘时丢弃，避免换过的城市一直留在文件里
+const MAX_AGE_HOURS: i64 = 24;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct CacheEntry {
+    fetched_at: DateTime<Utc>,
+    value: serde_json::Value,
+}
+
+/// 天气数据的内存 + 磁盘缓存
+///
+/// 界面反复刷新和重启应用时不再重复请求接口。磁盘上的记录在第一次使用时读入内存，
+/// 之后每次写入都整体保存一次。
+#[derive(Default)]
+pub struct WeatherCache {
+    /// None 表示还没有从磁盘读取
+    entries: Mutex<Option<HashMap<String, CacheEntry>>>,
+}
+
+fn load(file: &Path) -> HashMap<String, CacheEntry> {
+    fs::read_to_string(file)
+        .ok()
+        .and_then(|s| serde_json::from_str(&s).ok())
+        .unwrap_or_default()
+}
+
+impl WeatherCache {
+    /// 缓存中有未过期的记录时直接返回，否则调用 fetch 并写入缓存；请求期间不持有锁
+    pub fn get_or_fetch<T, F>(&self, file: &Path, key: &str, ttl: Duration, fetch: F) -> EarthResult<T>
+    where
+        T: Serialize + DeserializeOwned,
+        F: FnOnce() -> EarthResult<T>,
+    {
+        let now = Utc::now();
+        {
+            let mut entries = self.entries.lock().unwrap();
+            let entries = entries.get_or_insert_with(|| load(file));
+            if let Some(entry) = entries.get(key)
+                && now - entry.fetched_at < ttl
+                && let Ok(value) = serde_json::from_value(entry.value.clone())
+            {
+                debug!(key, fetched_at = %entry.fetched_at, "命中天气缓存");
+                return Ok(value);
+            }
+        }
+
+        let value = fetch()?;
+        let json = serde_json::to_value(&value).map_err(|e| EarthError::internal("序列化天气缓存失败", e))?;
+        let mut entries = self.entries.lock().unwrap();
+        let entries = entries.get_or_insert_with(|| load(file));
+        entries.retain(|_, entry| now - entry.fetched_at < Duration::hours(MAX_AGE_HOURS));
+        entries.insert(key.to_string(), CacheEntry { fetched_at: now, value: json });
+        if let Err(e) = save(file, entries) {
+            warn!(error = %e, "保存天气缓存失败");
+        }
+        Ok(value)
+    }
+}
+
+fn save(file: &Path, entries: &HashMap<String, CacheEntry>) -> EarthResult<()> {
+    if let Some(dir) = file.parent() {
+        fs::create_dir_all(dir).map_err(|e| EarthError::io(dir, "创建缓存目录失败", e))?;
+    }
+    let json = serde_json::to_string(entries).map_err(|e| EarthError::internal("序列化天气缓存失败", e))?;
+    atomic::write_bytes(file, json, "保存天气缓存失败")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::Cell;
+
+    /// 按 fetched_at 写一份缓存文件
+    fn write_cache(file: &Path, entries: &[(&str, DateTime<Utc>, u32)]) {
+        let map: HashMap<String, CacheEntry> = entries
+            .iter()
+            .map(|(key, fetched_at, value)| {
+                let entry = CacheEntry {
+                    fetched_at: *fetched_at,
+                    value: (*value).into(),
+                };
+                (key.to_string(), entry)
+            })
+            .collect();
+        save(file, &map).unwrap();
+    }
+
+    #[test]
+    fn hit_within_ttl_skips_fetch() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join(CACHE_FILE);
+        let cache = WeatherCache::default();
+        let calls = Cell::new(0);
+        let fetch = || {
+            calls.set(calls.get() + 1);
+            Ok(7u32)
+        };
+        assert_eq!(cache.get_or_fetch(&file, "beijing", Duration::minutes(10), fetch).unwrap(), 7);
+        assert_eq!(cache.get_or_fetch(&file, "beijing", Duration::minutes(10), fetch).unwrap(), 7);
+        assert_eq!(calls.get(), 1);
+        // 重启后从磁盘读到同一条记录
+        let reloaded = WeatherCache::default();
+        let value: u32 = reloaded
+            .get_or_fetch(&file, "beijing", Duration::minutes(10), || panic!("不应请求"))
+            .unwrap();
+        assert_eq!(value, 7);
+    }
+
+    #[test]
+    fn expired_entry_is_fetched_again() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join(CACHE_FILE);
+        write_cache(&file, &[("beijing", Utc::now() - Duration::hours(2), 1)]);
+        let cache = WeatherCache::default();
+        assert_eq!(cache.get_or_fetch(&file, "beijing", Duration::hours(1), || Ok(2u32)).unwrap(), 2);
+        // 更长的 TTL 下旧记录仍然有效，但内存里已经是新值
+        assert_eq!(cache.get_or_fetch(&file, "beijing", Duration::hours(3), || Ok(3u32)).unwrap(), 2);
+    }
+
+    #[test]
+    fn entries_older_than_a_day_are_pruned_on_save() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join(CACHE_FILE);
+        let now = Utc::now();
+        write_cache(&file, &[("old", now - Duration::hours(25), 1), ("recent", now - Duration::hours(23), 2)]);
+        let cache = WeatherCache::default();
+        cache.get_or_fetch(&file, "new", Duration::hours(1), || Ok(3u32)).unwrap();
+        let saved = load(&file);
+        assert!(!saved.contains_key("old"));
+        assert!(saved.contains_key("recent"));
+        assert!(saved.contains_key("new"));
+    }
+
+    #[test]
+    fn corrupt_file_is_treated_as_empty_and_rewritten() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join(CACHE_FILE);
+        fs::write(&file, "{not json").unwrap();
+        let cache = WeatherCache::default();
+        assert_eq!(cache.get_or_fetch(&file, "beijing", Duration::hours(1), || Ok(5u32)).unwrap(), 5);
+        assert_eq!(load(&file).len(), 1);
+    }
+
+    #[test]
+    fn fetch_error_is_not_cached() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join(CACHE_FILE);
+        let cache = WeatherCache::default();
+        let result: EarthResult<u32> =
+            cache.get_or_fetch(&file, "beijing", Duration::hours(1), || Err(EarthError::weather("未获取到天气信息")));
+        assert!(result.is_err());
+        assert!(!file.exists());
+        assert_eq!(cache.get_or_fetch(&file, "beijing", Duration::hours(1), || Ok(6u32)).unwrap(), 6);
+    }
+}
@@ -0,0 +1,125 @@
+use serde::{Deserialize, Serialize};
+
+use crate::i18n;
+use crate::weather::CurrentWeather;
+
+/// 显示单位，保存在 settings.json 的 weather_units 字段
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Units {
+    /// 摄氏度、米每秒
+    #[default]
+    Metric,
+    /// 华氏度、英里每小时
+    Imperial,
+}
+
+impl Units {
+    /// 温度，四舍五入到整数，如 25°C / 77°F
+    pub fn temperature(self, celsius: f32) -> String {
+        match self {
+            Units::Metric => format!("{}°C", whole(celsius)),
+            Units::Imperial => format!("{}°F", whole(celsius * 9.0 / 5.0 + 32.0)),
+        }
+    }
+
+    /// 风速，保留一位小数，如 3.2 m/s / 7.2 mph
+    pub fn wind_speed(self, meters_per_sec: f32) -> String {
+        match self {
+            Units::Metric => format!("{:.1} m/s", meters_per_sec),
+            Units::Imperial => format!("{:.1} mph", meters_per_sec * 2.236_936),
+        }
+    }
+}
+
+/// 四舍五入到整数，-0.4 这样的值显示为 0 而不是 -0
+fn whole(value: f32) -> f32 {
+    let rounded = value.round();
+    if rounded == 0.0 { 0.0 } else { rounded }
+}
+
+/// 一行天气摘要，如「晴 25°C 湿度60% (Beijing)」，按当前界面语言
+pub fn summary(weather: &CurrentWeather, units: Units) -> String {
+    i18n::trf(
+        "{} {} 湿度{}% ({})",
+        &[
+            &weather.description,
+            &units.temperature(weather.temperature),
+            &weather.humidity,
+            &weather.city,
+        ],
+    )
+}
+
+/// 体感温度和风速，如「体感 27°C，风速 3.2 m/s」
+pub fn details(weather: &CurrentWeather, units: Units) -> String {
+    i18n::trf(
+        "体感 {}，风速 {}",
+        &[&units.temperature(weather.feels_like), &units.wind_speed(weather.wind_speed)],
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::weather::{Condition, ProviderKind};
+    use chrono::{TimeZone, Utc};
+
+    fn weather() -> CurrentWeather {
+        CurrentWeather {
+            provider: ProviderKind::OpenMeteo,
+            city: "Beijing".to_string(),
+            description: "晴".to_string(),
+            condition: Condition::Clear,
+            icon: "0".to_string(),
+            temperature: 25.4,
+            feels_like: 27.0,
+            humidity: 60,
+            wind_speed: 3.2,
+            wind_direction: Some(90),
+            sunrise: None,
+            sunset: None,
+            observed_at: Utc.with_ymd_and_hms(2025, 6, 20, 4, 0, 0).unwrap(),
+        }
+    }
+
+    #[test]
+    fn metric_temperature_rounds_without_negative_zero() {
+        let cases = [(25.4, "25°C"), (25.5, "26°C"), (0.0, "0°C"), (-0.0, "0°C"), (-0.4, "0°C"), (-0.5, "-1°C"), (-12.6, "-13°C")];
+        for (celsius, expected) in cases {
+            assert_eq!(Units::Metric.temperature(celsius), expected, "{}", celsius);
+        }
+    }
+
+    #[test]
+    fn imperial_temperature_converts_from_celsius() {
+        // -17.9°C 约为 -0.2°F
+        let cases = [(0.0, "32°F"), (25.0, "77°F"), (-40.0, "-40°F"), (-17.9, "0°F"), (-18.1, "-1°F"), (37.0, "99°F")];
+        for (celsius, expected) in cases {
+            assert_eq!(Units::Imperial.temperature(celsius), expected, "{}", celsius);
+        }
+    }
+
+    #[test]
+    fn wind_speed_keeps_one_decimal() {
+        assert_eq!(Units::Metric.wind_speed(3.24), "3.2 m/s");
+        assert_eq!(Units::Imperial.wind_speed(3.2), "7.2 mph");
+        assert_eq!(Units::Imperial.wind_speed(0.0), "0.0 mph");
+    }
+
+    #[test]
+    fn summary_and_details() {
+        let weather = weather();
+        assert_eq!(summary(&weather, Units::Metric), "晴 25°C 湿度60% (Beijing)");
+        assert_eq!(summary(&weather, Units::Imperial), "晴 78°F 湿度60% (Beijing)");
+        assert_eq!(details(&weather, Units::Metric), "体感 27°C，风速 3.2 m/s");
+        assert_eq!(details(&weather, Units::Imperial), "体感 81°F，风速 7.2 mph");
+        let freezing = CurrentWeather {
+            temperature: -0.3,
+            feels_like: -0.2,
+            ..weather
+        };
+        assert_eq!(summary(&freezing, Units::Metric), "晴 0°C 湿度60% (Beijing)");
+        assert_eq!(details(&freezing, Units::Metric), "体感 0°C，风速 3.2 m/s");
+    }
+}